@@ -0,0 +1,61 @@
+use bach::{autoscale::Pool, environment::default::Runtime, ext::*, sync::channel};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn scales_with_queue_depth() {
+    let history = Arc::new(Mutex::new(vec![]));
+
+    Runtime::new().run({
+        let history = history.clone();
+        move || {
+            let (sender, receiver) = channel::unbounded::<()>();
+
+            let pool = Pool::builder(move |_id| {
+                let receiver = receiver.clone();
+                async move {
+                    while receiver.recv().await.is_ok() {
+                        100.ms().sleep().await;
+                    }
+                }
+            })
+            .with_max(4)
+            .with_metric({
+                let sender = sender.clone();
+                move || sender.len() as f64
+            })
+            .with_target(10.0)
+            .with_reaction_delay(2.s())
+            .spawn();
+
+            async move {
+                // a burst of 30 jobs per second for 5 seconds
+                for _ in 0..150 {
+                    sender.send(()).await.unwrap();
+                    33.ms().sleep().await;
+                }
+                // wait for the pool to drain the queue and scale back down
+                while !sender.is_empty() {
+                    1.s().sleep().await;
+                }
+                10.s().sleep().await;
+                *history.lock().unwrap() = pool.history();
+                pool.stop();
+            }
+            .primary()
+            .spawn_named("load");
+        }
+    });
+
+    let history: Vec<_> = history
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|change| (change.at.elapsed_since_start(), change.workers))
+        .collect();
+    // each decision takes effect 2s after the observation that triggered it
+    //
+    // * at 1s, 31 jobs have arrived and 10 were processed, so 3 workers are needed
+    // * at 4s, the backlog is still growing so the pool is scaled up to the max
+    // * at 7s, the load has stopped and the queue is drained
+    assert_eq!(history, [(0.s(), 1), (3.s(), 3), (6.s(), 4), (9.s(), 1)]);
+}
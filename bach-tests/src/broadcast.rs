@@ -0,0 +1,148 @@
+use bach::{environment::default::Runtime, ext::*, sync::broadcast, time::Instant};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Phase {
+    Started,
+    Verify,
+}
+
+#[test]
+fn control_phases() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let log = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let control = broadcast::new();
+
+        for node in 0..3 {
+            let phases = control.subscribe();
+            let log = log.clone();
+            async move {
+                while let Ok(phase) = phases.recv().await {
+                    log.lock().unwrap().push((node, phase, Instant::now()));
+                }
+            }
+            .group(&format!("node{node}"))
+            .primary()
+            .spawn();
+        }
+
+        async move {
+            assert_eq!(control.send(Phase::Started), 3);
+            1.s().sleep().await;
+            assert_eq!(control.send(Phase::Verify), 3);
+            // dropping the last sender closes the subscribers
+        }
+        .group("orchestrator")
+        .primary()
+        .spawn();
+    });
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 6);
+    for node in 0..3 {
+        let phases: Vec<_> = log
+            .iter()
+            .filter(|(n, _, _)| *n == node)
+            .map(|(_, phase, time)| (phase.clone(), time.elapsed_since_start()))
+            .collect();
+        assert_eq!(phases, [(Phase::Started, 0.s()), (Phase::Verify, 1.s())]);
+    }
+}
+
+#[test]
+fn lagging_subscriber() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    Runtime::new().run(|| {
+        let sender = broadcast::bounded(2);
+        let fast = sender.subscribe();
+        let slow = sender.subscribe();
+
+        async move {
+            for i in 0..5 {
+                assert_eq!(sender.send(i), 2);
+                1.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn();
+
+        let log = received.clone();
+        async move {
+            while let Ok(i) = fast.recv().await {
+                log.lock().unwrap().push(("fast", i));
+            }
+            assert_eq!(fast.lagged(), 0);
+        }
+        .primary()
+        .spawn();
+
+        let log = received.clone();
+        async move {
+            // only starts reading once the sender is done
+            10.ms().sleep().await;
+            assert_eq!(slow.lagged(), 3);
+            // the count is reset once it's been observed
+            assert_eq!(slow.lagged(), 0);
+            while let Ok(i) = slow.recv().await {
+                log.lock().unwrap().push(("slow", i));
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    let received = received.lock().unwrap();
+    let fast: Vec<_> = received.iter().filter(|(n, _)| *n == "fast").collect();
+    assert_eq!(fast.len(), 5);
+    // the slow subscriber lost the oldest messages without holding up the fast one
+    let slow: Vec<_> = received.iter().filter(|(n, _)| *n == "slow").collect();
+    assert_eq!(slow, [&("slow", 3), &("slow", 4)]);
+}
+
+#[test]
+fn coop_subscribers() {
+    use std::collections::BTreeSet;
+
+    static ORDERS: Mutex<Vec<Vec<u8>>> = Mutex::new(vec![]);
+
+    bolero::check!().exhaustive().run(|| {
+        let order = Arc::new(Mutex::new(vec![]));
+        let mut rt = Runtime::new().with_coop(true).with_rand(None);
+
+        rt.run(|| {
+            let sender = broadcast::new();
+
+            for id in 0..3u8 {
+                let receiver = sender.subscribe();
+                let order = order.clone();
+                async move {
+                    // receive after the message was sent so all of the subscribers contend for it
+                    2.ms().sleep().await;
+                    receiver.recv().await.unwrap();
+                    order.lock().unwrap().push(id);
+                }
+                .primary()
+                .spawn();
+            }
+
+            async move {
+                1.ms().sleep().await;
+                sender.send(());
+            }
+            .primary()
+            .spawn();
+        });
+
+        let order = order.lock().unwrap().clone();
+        ORDERS.lock().unwrap().push(order);
+    });
+
+    // every order in which the subscribers observe the message is explored
+    let orders: BTreeSet<_> = ORDERS.lock().unwrap().iter().cloned().collect();
+    assert_eq!(orders.len(), 6);
+}
@@ -0,0 +1,144 @@
+use bach::{
+    environment::default::Runtime,
+    ext::*,
+    sync::{Condvar, Mutex},
+    time::Instant,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+#[derive(Default)]
+struct Buffer {
+    items: Mutex<VecDeque<u32>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+const CAPACITY: usize = 2;
+
+#[test]
+fn bounded_buffer() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let received = Arc::new(StdMutex::new(vec![]));
+
+    rt.run(|| {
+        let buffer = Arc::new(Buffer::default());
+
+        for producer in 0..2 {
+            let buffer = buffer.clone();
+            async move {
+                for i in 0..5 {
+                    let items = buffer.items.lock().await;
+                    let mut items = buffer
+                        .not_full
+                        .wait_while(items, |items| items.len() >= CAPACITY)
+                        .await;
+                    items.push_back(producer * 100 + i);
+                    drop(items);
+                    buffer.not_empty.notify_one();
+                    1.ms().sleep().await;
+                }
+            }
+            .primary()
+            .spawn_named(format!("producer_{producer}"));
+        }
+
+        let received = received.clone();
+        async move {
+            for _ in 0..10 {
+                let items = buffer.items.lock().await;
+                let mut items = buffer
+                    .not_empty
+                    .wait_while(items, |items| items.is_empty())
+                    .await;
+                let item = items.pop_front().unwrap();
+                drop(items);
+                buffer.not_full.notify_one();
+                received.lock().unwrap().push(item);
+                // consume slower than the producers to fill up the buffer
+                5.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn_named("consumer");
+    });
+
+    let mut received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 10);
+    received.sort();
+    assert_eq!(received, [0, 1, 2, 3, 4, 100, 101, 102, 103, 104]);
+}
+
+#[test]
+fn notify_all() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let woken = Arc::new(StdMutex::new(vec![]));
+
+    rt.run(|| {
+        let state = Arc::new((Mutex::new(false), Condvar::new()));
+
+        for _ in 0..3 {
+            let state = state.clone();
+            let woken = woken.clone();
+            async move {
+                let (ready, condvar) = &*state;
+                let _ready = condvar
+                    .wait_while(ready.lock().await, |ready| !*ready)
+                    .await;
+                woken
+                    .lock()
+                    .unwrap()
+                    .push(Instant::now().elapsed_since_start());
+            }
+            .primary()
+            .spawn();
+        }
+
+        async move {
+            10.ms().sleep().await;
+            let (ready, condvar) = &*state;
+            *ready.lock().await = true;
+            condvar.notify_all();
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(*woken.lock().unwrap(), [10.ms(); 3]);
+}
+
+#[test]
+#[should_panic(
+    expected = "lost wakeup: task 1 (consumer) started waiting on a condvar at 0:00:00.010000000, but the notification from task 0 (producer) at 0:00:00.000000000 found no waiters"
+)]
+fn lost_wakeup() {
+    let mut rt = Runtime::new().with_lost_wakeup_detection(true);
+
+    rt.run(|| {
+        let state = Arc::new((Mutex::new(()), Condvar::new()));
+
+        {
+            let state = state.clone();
+            async move {
+                let (_lock, condvar) = &*state;
+                condvar.notify_one();
+            }
+            .spawn_named("producer");
+        }
+
+        async move {
+            10.ms().sleep().await;
+            let (lock, condvar) = &*state;
+            // waiting without checking a condition misses the notification that was already sent
+            let _guard = condvar.wait(lock.lock().await).await;
+        }
+        .primary()
+        .spawn_named("consumer");
+    });
+}
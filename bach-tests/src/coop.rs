@@ -1,6 +1,6 @@
-use std::sync::Mutex;
+use std::{cell::Cell, sync::Mutex};
 
-use bach::{environment::default::Runtime, ext::*, sync::queue::vec_deque::Queue};
+use bach::{coop::Operation, environment::default::Runtime, ext::*, sync::queue::vec_deque::Queue};
 
 fn sim(f: impl Fn()) -> impl Fn() {
     crate::testing::init_tracing();
@@ -50,3 +50,318 @@ fn interleavings() {
 
     insta::assert_debug_snapshot!(LOG.lock().unwrap());
 }
+
+/// Returns the number of interleavings explored for tasks acquiring the operations returned by
+/// `setup`
+fn interleaving_count(setup: fn() -> Vec<Operation>) -> usize {
+    // tests run in parallel so keep the count local to the thread
+    thread_local! {
+        static ITERATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+    ITERATIONS.with(|count| count.set(0));
+
+    bolero::check!().exhaustive().run(sim(move || {
+        ITERATIONS.with(|count| count.set(count.get() + 1));
+
+        for operation in setup() {
+            async move {
+                operation.acquire().await;
+            }
+            .primary()
+            .spawn();
+        }
+    }));
+
+    ITERATIONS.with(|count| count.get())
+}
+
+#[test]
+fn commutative_operations() {
+    let shared = || {
+        let operation = Operation::register();
+        vec![operation; 3]
+    };
+    assert_eq!(interleaving_count(shared), 6);
+
+    let commutative = || {
+        let operation = Operation::register().commutative();
+        vec![operation; 3]
+    };
+    assert_eq!(interleaving_count(commutative), 1);
+}
+
+#[test]
+fn atomic_blocks() {
+    thread_local! {
+        static ITERATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // each task acquires the same operation twice
+    let count = |is_atomic: bool| {
+        ITERATIONS.with(|count| count.set(0));
+
+        bolero::check!().exhaustive().run(sim(move || {
+            ITERATIONS.with(|count| count.set(count.get() + 1));
+
+            let operation = Operation::register();
+            for _ in 0..3 {
+                let steps = async move {
+                    operation.acquire().await;
+                    operation.acquire().await;
+                };
+                async move {
+                    if is_atomic {
+                        bach::coop::atomic(steps).await;
+                    } else {
+                        steps.await;
+                    }
+                }
+                .primary()
+                .spawn();
+            }
+        }));
+
+        ITERATIONS.with(|count| count.get())
+    };
+
+    assert_eq!(count(false), 36);
+    // the block runs to completion without yielding to the scheduler
+    assert_eq!(count(true), 1);
+}
+
+#[test]
+fn conflicting_operations() {
+    let independent = || vec![Operation::register(), Operation::register()];
+    assert_eq!(interleaving_count(independent), 1);
+
+    let conflicting = || {
+        let a = Operation::register();
+        let b = Operation::register();
+        a.conflicts_with(&b);
+        vec![a, b]
+    };
+    assert_eq!(interleaving_count(conflicting), 2);
+}
+
+fn ordered_acquires(rt: &mut Runtime) -> Vec<u8> {
+    let order = std::sync::Arc::new(Mutex::new(vec![]));
+    rt.run(|| {
+        let operation = Operation::register();
+        for id in 0..3 {
+            let order = order.clone();
+            async move {
+                operation.acquire().await;
+                order.lock().unwrap().push(id);
+            }
+            .primary()
+            .spawn();
+        }
+    });
+    let order = order.lock().unwrap().clone();
+    order
+}
+
+#[test]
+fn schedule_replay() {
+    static RUNS: Mutex<Vec<(String, Vec<u8>)>> = Mutex::new(vec![]);
+
+    bolero::check!().exhaustive().run(|| {
+        let mut rt = Runtime::new().with_coop(true).with_rand(None);
+        let order = ordered_acquires(&mut rt);
+        let schedule = rt.schedule().to_string();
+        RUNS.lock().unwrap().push((schedule, order));
+    });
+
+    let runs = RUNS.lock().unwrap();
+    let orders: std::collections::BTreeSet<_> = runs.iter().map(|(_, order)| order).collect();
+    assert_eq!(orders.len(), 6);
+
+    for (schedule, order) in runs.iter() {
+        let mut rt = Runtime::new().with_rand(None).with_schedule(schedule);
+        assert_eq!(&ordered_acquires(&mut rt), order, "schedule {schedule:?}");
+    }
+}
+
+#[test]
+fn schedule_encoding() {
+    use bach::coop::Schedule;
+
+    let schedule: Schedule = "01z{62}{100}".parse().unwrap();
+    assert_eq!(schedule.to_string(), "01z{62}{100}");
+    assert!("01-".parse::<Schedule>().is_err());
+    assert!("{abc}".parse::<Schedule>().is_err());
+}
+
+#[test]
+fn max_depth() {
+    thread_local! {
+        static ITERATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    bolero::check!().exhaustive().run(|| {
+        ITERATIONS.with(|count| count.set(count.get() + 1));
+        let mut rt = Runtime::new()
+            .with_coop(true)
+            .with_coop_max_depth(1)
+            .with_rand(None);
+        ordered_acquires(&mut rt);
+    });
+
+    // only the first task's position is explored
+    assert_eq!(ITERATIONS.with(|count| count.get()), 3);
+}
+
+#[test]
+fn stats() {
+    use std::collections::BTreeSet;
+
+    static REORDERS: Mutex<Vec<u64>> = Mutex::new(vec![]);
+
+    bolero::check!().exhaustive().run(|| {
+        let mut rt = Runtime::new().with_coop(true).with_rand(None);
+        let mut operations = None;
+
+        rt.run(|| {
+            let contended = Operation::register();
+            let exclusive = Operation::register();
+            operations = Some((contended, exclusive));
+
+            for _ in 0..3 {
+                async move {
+                    contended.acquire().await;
+                }
+                .primary()
+                .spawn();
+            }
+
+            async move {
+                exclusive.acquire().await;
+                // the stats are also available from within the simulation
+                let stats = bach::coop::stats().unwrap();
+                assert_eq!(stats.operation(&exclusive).acquisitions, 1);
+            }
+            .primary()
+            .spawn();
+        });
+
+        let (contended, exclusive) = operations.unwrap();
+        let stats = rt.coop_stats();
+
+        assert_eq!(stats.operation(&contended).acquisitions, 3);
+        assert_eq!(stats.operation(&contended).contended_rounds, 1);
+        assert_eq!(stats.operation(&exclusive).acquisitions, 1);
+        assert_eq!(stats.operation(&exclusive).contended_rounds, 0);
+        assert_eq!(stats.operation(&exclusive).reorders, 0);
+
+        REORDERS
+            .lock()
+            .unwrap()
+            .push(stats.operation(&contended).reorders);
+    });
+
+    let reorders: BTreeSet<_> = REORDERS.lock().unwrap().iter().copied().collect();
+    // the identity ordering doesn't reorder anything, while the rest swap one or two pairs
+    assert_eq!(reorders, [0, 2, 4].into());
+}
+
+#[test]
+fn replay_file() {
+    use bach::{coop::Schedule, environment::replay::Replay};
+    use std::panic::{self, AssertUnwindSafe};
+
+    let path = std::env::temp_dir().join(format!("bach-replay-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let run = |rt: &mut Runtime| {
+        let order = ordered_acquires(rt);
+        let values = rt.run(|| (0..4).map(|_| (0..u64::MAX).any()).collect::<Vec<_>>());
+        (order, values)
+    };
+
+    // runs the scenario and fails, which writes the replay file
+    let record = |rt: Runtime| {
+        let mut recorded = None;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut rt = rt.with_replay_recording(&path);
+            recorded = Some(run(&mut rt));
+            panic!("the run failed");
+        }));
+        assert!(res.is_err());
+        recorded.unwrap()
+    };
+
+    // passing runs don't leave a file behind
+    let mut rt = Runtime::new().with_coop(true).with_replay_recording(&path);
+    run(&mut rt);
+    drop(rt);
+    assert!(!path.exists());
+
+    // find a seed that doesn't keep the arrival order so the replay has something to reproduce
+    let (seed, recorded) = (0..)
+        .find_map(|seed| {
+            let recorded = record(Runtime::new().with_seed(seed).with_coop(true));
+            (recorded.0 != [0, 1, 2]).then_some((seed, recorded))
+        })
+        .unwrap();
+
+    let replay = Replay::read(&path).unwrap();
+    assert_eq!(replay.seed, Some(seed));
+    assert!(replay.coop);
+    assert!(!replay.schedule.is_empty());
+    assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+
+    // the settings from the file take precedence over the runtime's
+    let mut rt = Runtime::new().with_seed(seed + 1).with_replay(&path);
+    assert_eq!(run(&mut rt), recorded);
+    drop(rt);
+
+    std::fs::remove_file(&path).unwrap();
+
+    // replaying a missing file fails instead of silently running something else
+    let res = panic::catch_unwind(AssertUnwindSafe(|| Runtime::new().with_replay(&path)));
+    assert!(res.is_err());
+
+    assert!(Replay::from_bytes(b"BACH").is_err());
+    assert!(Replay::from_bytes(b"not a replay").is_err());
+    let empty = Replay {
+        seed: Some(1),
+        coop: false,
+        schedule: Schedule::default(),
+    };
+    assert_eq!(Replay::from_bytes(&empty.to_bytes()).unwrap(), empty);
+}
+
+#[test]
+fn replay_file_bolero() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    static ORDERS: Mutex<Vec<Vec<u8>>> = Mutex::new(vec![]);
+
+    let path = std::env::temp_dir().join(format!("bach-replay-bolero-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    bolero::check!().exhaustive().run(|| {
+        let mut order = vec![];
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut rt = Runtime::new()
+                .with_coop(true)
+                .with_rand(None)
+                .with_replay_recording(&path);
+            order = ordered_acquires(&mut rt);
+            // only the iteration that reverses the order fails
+            assert_ne!(order, [2, 1, 0]);
+        }));
+        ORDERS.lock().unwrap().push(order);
+    });
+
+    // recording doesn't affect the exploration
+    let orders: std::collections::BTreeSet<_> = ORDERS.lock().unwrap().iter().cloned().collect();
+    assert_eq!(orders.len(), 6);
+
+    // runs without an RNG replay the recorded decisions of the failed iteration
+    let mut rt = Runtime::new().with_replay(&path);
+    assert_eq!(ordered_acquires(&mut rt), [2, 1, 0]);
+    drop(rt);
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,95 @@
+use bach::{
+    deadline::{self, Deadline, Error},
+    environment::default::Runtime,
+    ext::*,
+    sync::queue::vec_deque::Queue,
+    time::Instant,
+};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn pipeline_deadline() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let log = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let (to_server, server_requests) = Queue::default().latent(30.ms()).channel();
+        let (to_backend, backend_requests) = Queue::default().latent(10.ms()).channel();
+
+        async move {
+            let deadline = Deadline::after(100.ms());
+            to_server.send((deadline, "request")).await.unwrap();
+        }
+        .primary()
+        .spawn_named("client");
+
+        // the server does some work and forwards the request to the backend
+        async move {
+            while let Ok((deadline, request)) = server_requests.recv().await {
+                let to_backend = to_backend.clone();
+                async move {
+                    50.ms().sleep().await;
+                    let deadline = deadline::current().unwrap();
+                    assert_eq!(deadline.remaining(), Some(20.ms()));
+                    to_backend.send((deadline, request)).await.unwrap();
+                }
+                .with_deadline(deadline)
+                .primary()
+                .spawn_named("server");
+            }
+        }
+        .primary()
+        .spawn();
+
+        // the backend runs out of time before it can complete the request
+        let log = log.clone();
+        async move {
+            while let Ok((deadline, _request)) = backend_requests.recv().await {
+                let res = deadline.enforce(50.ms().sleep()).await;
+                log.lock().unwrap().push((res, Instant::now()));
+            }
+        }
+        .primary()
+        .spawn_named("backend");
+    });
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].0, Err(Error::Expired));
+    assert_eq!(log[0].1.elapsed_since_start(), 100.ms());
+}
+
+#[test]
+fn cancellation() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let log = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let parent = Deadline::new();
+        let child = parent.with_timeout(1.s());
+
+        let log = log.clone();
+        async move {
+            let res = child.enforce(10.s().sleep()).await;
+            log.lock().unwrap().push((res, Instant::now()));
+            assert_eq!(child.check(), Err(Error::Cancelled));
+        }
+        .primary()
+        .spawn();
+
+        async move {
+            10.ms().sleep().await;
+            parent.cancel();
+        }
+        .primary()
+        .spawn();
+    });
+
+    let log = log.lock().unwrap();
+    assert_eq!(log[0].0, Err(Error::Cancelled));
+    assert_eq!(log[0].1.elapsed_since_start(), 10.ms());
+}
@@ -0,0 +1,73 @@
+use bach::{
+    examples::{echo, failover, retry_storm},
+    ext::*,
+};
+
+#[test]
+fn echo() {
+    let config = echo::Config::new().with_clients(4).with_requests(10);
+    let report = config.run();
+    assert_eq!(report.completed, 40);
+    // each round trip crosses the link twice
+    assert!(report.mean_latency >= 11.ms(), "{report:?}");
+    assert!(report.max_latency >= report.mean_latency, "{report:?}");
+
+    // the same seed produces the same run
+    assert_eq!(config.run(), report);
+    assert_ne!(config.with_seed(1).run(), report);
+}
+
+#[test]
+fn retry_storm() {
+    let config = retry_storm::Config::new().with_clients(50);
+
+    // most of the server's time goes to requests that already timed out
+    let storm = config.run();
+    assert_eq!(storm.succeeded + storm.failed, 50);
+    assert!(storm.amplification() > 4.0, "{storm:?}");
+    assert!(storm.wasted > storm.succeeded * 10, "{storm:?}");
+
+    // backing off alone doesn't help while the queue is full of expired requests
+    let backoff = config.clone().with_backoff(100.ms()).run();
+    assert!(backoff.succeeded < 25, "{backoff:?}");
+
+    let shed = config.clone().with_shed_expired(true).run();
+    assert!(shed.succeeded > storm.succeeded * 3, "{shed:?}");
+    assert!(shed.wasted < storm.wasted, "{shed:?}");
+
+    // together, every request eventually succeeds with fewer retries
+    let both = config
+        .clone()
+        .with_backoff(100.ms())
+        .with_shed_expired(true)
+        .run();
+    assert_eq!(both.succeeded, 50, "{both:?}");
+    assert!(
+        both.amplification() < shed.amplification(),
+        "{both:?} {shed:?}"
+    );
+
+    assert_eq!(config.run(), storm);
+
+    // with enough capacity, nothing is retried
+    let idle = retry_storm::Config::new().with_clients(5).run();
+    assert_eq!(idle.attempts, 5);
+    assert_eq!(idle.wasted, 0);
+}
+
+#[test]
+fn failover() {
+    let report = failover::Config::new().with_fault(300.ms(), 500.ms()).run();
+    assert_eq!(report.succeeded + report.failed, 100);
+
+    // the backup takes over once the last heartbeat is older than the failover timeout
+    let failover_at = report.failover_at.unwrap();
+    assert!((340.ms()..=360.ms()).contains(&failover_at), "{report:?}");
+    assert!(report.failed > 0 && report.failed < 10, "{report:?}");
+    assert!(report.served[0] > 0 && report.served[1] > 0, "{report:?}");
+
+    // a pause shorter than the failover timeout doesn't trigger a failover
+    let report = failover::Config::new().with_fault(300.ms(), 20.ms()).run();
+    assert_eq!(report.failover_at, None);
+    assert_eq!(report.served[1], 0);
+}
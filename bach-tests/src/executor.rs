@@ -0,0 +1,317 @@
+use bach::{environment::default::Runtime, ext::*};
+use std::{
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+};
+
+#[test]
+fn foreign_wake_audit() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new().with_wake_audit(true);
+
+    let waker: Arc<Mutex<Option<Waker>>> = Default::default();
+
+    rt.run(|| {
+        let slot = waker.clone();
+        async move {
+            let mut is_registered = false;
+            core::future::poll_fn(|cx| {
+                if core::mem::replace(&mut is_registered, true) {
+                    return Poll::Ready(());
+                }
+                *slot.lock().unwrap() = Some(cx.waker().clone());
+                Poll::Pending
+            })
+            .await;
+        }
+        .primary()
+        .spawn_named("waiter");
+
+        let slot = waker.clone();
+        async move {
+            1.s().sleep().await;
+            let waker = slot.lock().unwrap().take().unwrap();
+            std::thread::spawn(move || waker.wake()).join().unwrap();
+        }
+        .primary()
+        .spawn_named("waker");
+    });
+
+    let wakes = rt.take_foreign_wakes();
+    assert_eq!(wakes.len(), 1);
+    assert!(rt.take_foreign_wakes().is_empty());
+}
+
+#[test]
+fn disable_wake_audit() {
+    let mut rt = Runtime::new().with_wake_audit(true);
+
+    let waker: Arc<Mutex<Option<Waker>>> = Default::default();
+    let woken = Arc::new(Mutex::new(false));
+
+    rt.run(|| {
+        let slot = waker.clone();
+        let woken = woken.clone();
+        async move {
+            let mut is_registered = false;
+            core::future::poll_fn(|cx| {
+                if core::mem::replace(&mut is_registered, true) {
+                    return Poll::Ready(());
+                }
+                *slot.lock().unwrap() = Some(cx.waker().clone());
+                Poll::Pending
+            })
+            .await;
+            *woken.lock().unwrap() = true;
+        }
+        .primary()
+        .spawn_named("waiter");
+
+        let slot = waker.clone();
+        async move {
+            1.s().sleep().await;
+            let waker = slot.lock().unwrap().take().unwrap();
+            std::thread::spawn(move || waker.wake()).join().unwrap();
+            // the held wake is released rather than stranded
+            bach::task::scope::borrow_with(|handle| handle.set_wake_audit(false));
+        }
+        .primary()
+        .spawn_named("waker");
+    });
+
+    assert!(*woken.lock().unwrap());
+    assert_eq!(rt.take_foreign_wakes().len(), 1);
+}
+
+fn sleep_loop(rt: &mut Runtime) -> std::time::Duration {
+    rt.run(|| {
+        async move {
+            for _ in 0..10 {
+                10.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn();
+    });
+    rt.elapsed()
+}
+
+#[test]
+fn scheduler_noise() {
+    crate::testing::init_tracing();
+
+    let mut rt = Runtime::new().with_scheduler_noise(|| 1.ms());
+    // each of the 10 wakeups is delayed by an extra 1ms
+    assert_eq!(sleep_loop(&mut rt), 110.ms());
+
+    let jittered = |seed| {
+        let mut rt = Runtime::new()
+            .with_seed(seed)
+            .with_scheduler_noise(|| (0..=5u64).any().ms());
+        sleep_loop(&mut rt)
+    };
+
+    let elapsed = jittered(123);
+    assert!(elapsed >= 100.ms() && elapsed <= 150.ms(), "{elapsed:?}");
+    // the noise is reproducible for a given seed
+    assert_eq!(elapsed, jittered(123));
+}
+
+#[test]
+fn task_finalizers() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let log = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let push = |log: &Arc<Mutex<Vec<String>>>, msg: &str| {
+            let log = log.clone();
+            let msg = msg.to_string();
+            move || log.lock().unwrap().push(msg)
+        };
+
+        let completed = {
+            let log = log.clone();
+            async move {
+                bach::task::on_task_end(push(&log, "completed: first"));
+                bach::task::on_task_end(push(&log, "completed: second"));
+                10.ms().sleep().await;
+            }
+            .spawn_named("completed")
+        };
+
+        let aborted = {
+            let log = log.clone();
+            async move {
+                bach::task::on_task_end(push(&log, "aborted"));
+                10.s().sleep().await;
+                unreachable!();
+            }
+            .spawn_named("aborted")
+        };
+
+        async move {
+            completed.await;
+            20.ms().sleep().await;
+            aborted.cancel();
+            // give the executor a chance to drop the cancelled task
+            1.ms().sleep().await;
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        ["completed: second", "completed: first", "aborted"]
+    );
+}
+
+#[test]
+fn orphan_check_allows_daemons() {
+    let mut rt = Runtime::new().with_orphan_check(true);
+
+    rt.run(|| {
+        async {
+            loop {
+                10.ms().sleep().await;
+            }
+        }
+        .daemon()
+        .spawn_named("heartbeat");
+
+        // daemons are marked when they're spawned, even if their group never lets them run
+        bach::group::Group::new("frozen").pause(1.s());
+        async {
+            loop {
+                10.ms().sleep().await;
+            }
+        }
+        .daemon()
+        .group("frozen")
+        .spawn_named("monitor");
+        async {
+            loop {
+                10.ms().sleep().await;
+            }
+        }
+        .group("frozen")
+        .daemon()
+        .spawn_named("heartbeat");
+
+        // finished background tasks aren't orphans
+        async {
+            5.ms().sleep().await;
+        }
+        .spawn();
+
+        async {
+            // group pauses spawn their own background tasks
+            bach::group::Group::new("node").pause(1.s());
+            50.ms().sleep().await;
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+#[should_panic(
+    expected = "1 task(s) were still pending after the primary tasks completed\n  task 1 (leaked)"
+)]
+fn orphan_check() {
+    let mut rt = Runtime::new().with_orphan_check(true);
+
+    rt.run(|| {
+        async {
+            async {
+                loop {
+                    10.ms().sleep().await;
+                }
+            }
+            .spawn_named("leaked");
+
+            50.ms().sleep().await;
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn orphans_untracked_without_check() {
+    let mut rt = Runtime::new();
+
+    rt.run(|| {
+        async {
+            async {
+                loop {
+                    10.ms().sleep().await;
+                }
+            }
+            .spawn_named("leaked");
+
+            50.ms().sleep().await;
+
+            // tasks are only tracked once the orphan check is enabled
+            let orphans = bach::task::scope::borrow_with(|handle| handle.orphans());
+            assert!(orphans.is_empty());
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+#[should_panic(
+    expected = "1 task(s) were still pending after the primary tasks completed\n  task 1 (worker_1) [request=42, stage=parse]"
+)]
+fn task_metadata() {
+    let mut rt = Runtime::new().with_orphan_check(true);
+
+    rt.run(|| {
+        async {
+            async {
+                bach::task::set_name("worker_1");
+                bach::task::set_tag("request", 41);
+                bach::task::set_tag("stage", "parse");
+                bach::task::set_tag("request", 42);
+                bach::task::set_tag("retry", true);
+                assert_eq!(bach::task::remove_tag("retry").as_deref(), Some("true"));
+
+                let info = bach::task::Info::current();
+                assert_eq!(info.name(), Some("worker"));
+                assert_eq!(info.current_name().as_deref(), Some("worker_1"));
+                assert_eq!(info.tag("request").as_deref(), Some("42"));
+
+                loop {
+                    10.ms().sleep().await;
+                }
+            }
+            .spawn_named("worker");
+
+            50.ms().sleep().await;
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn join_handle_is_finished() {
+    Runtime::new().run(|| {
+        async {
+            let task = async {
+                1.ms().sleep().await;
+            }
+            .spawn();
+
+            assert!(!task.is_finished());
+            2.ms().sleep().await;
+            assert!(task.is_finished());
+        }
+        .primary()
+        .spawn();
+    });
+}
@@ -0,0 +1,97 @@
+use bach::{
+    deadline::Deadline,
+    environment::default::Runtime,
+    ext::*,
+    failure_detector::{Detector, Monitor, PhiAccrual, Timeout},
+    sync::channel,
+    time::Instant,
+};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn phi_accrual() {
+    Runtime::new().run(|| {
+        let mut detector = PhiAccrual::new().with_min_std_dev(10.ms());
+        let start = Instant::now();
+        assert_eq!(detector.suspicion_at(start), 0.0);
+
+        for i in 0..10 {
+            detector.heartbeat_at(start + 100.ms() * i);
+        }
+        let last = start + 900.ms();
+        assert!((detector.mean() - 0.1).abs() < 1e-9);
+
+        // on time
+        assert!(detector.suspicion_at(last + 100.ms()) < 1.0);
+        // phi grows the longer the heartbeat is late
+        let late = detector.suspicion_at(last + 130.ms());
+        let later = detector.suspicion_at(last + 160.ms());
+        assert!(late < later, "{late} {later}");
+        assert!(detector.suspicion_at(last + 200.ms()) > detector.threshold());
+    });
+}
+
+#[test]
+fn timeout() {
+    Runtime::new().run(|| {
+        let mut detector = Timeout::new(1.s());
+        let start = Instant::now();
+        assert_eq!(detector.suspicion_at(start), 0.0);
+        detector.heartbeat_at(start);
+        assert_eq!(detector.suspicion_at(start + 500.ms()), 0.5);
+        assert_eq!(detector.suspicion_at(start + 1.s()), 1.0);
+    });
+}
+
+#[test]
+fn cluster() {
+    let suspected = Arc::new(Mutex::new(vec![]));
+
+    Runtime::new().with_seed(1).run(|| {
+        let (heartbeats, incoming) = channel::unbounded();
+
+        for peer in 0..3u32 {
+            let heartbeats = heartbeats.clone();
+            async move {
+                // the second peer crashes after 1s
+                let lifetime = if peer == 1 { 1.s() } else { 3.s() };
+                while Instant::now().elapsed_since_start() < lifetime {
+                    heartbeats.send(peer).await.unwrap();
+                    (90..=110u64).any().ms().sleep().await;
+                }
+            }
+            .spawn_named(format!("peer_{peer}"));
+        }
+        drop(heartbeats);
+
+        let suspected = suspected.clone();
+        async move {
+            let mut monitor = Monitor::new(|| PhiAccrual::new().with_min_std_dev(20.ms()));
+
+            loop {
+                let heartbeat = Deadline::after(10.ms()).enforce(incoming.recv()).await;
+                match heartbeat {
+                    Ok(Ok(peer)) => monitor.heartbeat(peer),
+                    Ok(Err(_)) => break,
+                    Err(_) => {}
+                }
+
+                for peer in monitor.suspected() {
+                    let mut suspected = suspected.lock().unwrap();
+                    if !suspected.iter().any(|(p, _)| *p == peer) {
+                        suspected.push((peer, Instant::now().elapsed_since_start()));
+                    }
+                }
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    let suspected = suspected.lock().unwrap();
+    assert_eq!(suspected.len(), 1, "{suspected:?}");
+    let (peer, at) = suspected[0];
+    assert_eq!(peer, 1);
+    // the last heartbeat was sent just after 1s and it takes a few missed intervals to be suspected
+    assert!(at > 1100.ms() && at < 1500.ms(), "{at:?}");
+}
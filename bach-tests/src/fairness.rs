@@ -0,0 +1,80 @@
+use bach::{environment::default::Runtime, ext::*, sync::channel, testing::Fairness};
+
+/// Two flows share a FIFO link that serves one item per millisecond
+fn shared_link(rates: [(&'static str, u64); 2]) -> Fairness {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+    let fairness = Fairness::new(20.ms());
+
+    rt.run(|| {
+        let (sender, receiver) = channel::unbounded();
+
+        for (flow, rate) in rates {
+            let sender = sender.clone();
+            async move {
+                for _ in 0..100 {
+                    for _ in 0..rate {
+                        sender.send(flow).await.unwrap();
+                    }
+                    1.ms().sleep().await;
+                }
+            }
+            .primary()
+            .spawn_named(flow);
+        }
+        drop(sender);
+
+        let fairness = fairness.clone();
+        async move {
+            while let Ok(flow) = receiver.recv().await {
+                fairness.record(flow, 1);
+                1.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn_named("link");
+    });
+
+    fairness
+}
+
+#[test]
+fn equal_flows() {
+    let report = shared_link([("a", 1), ("b", 1)]).report();
+    assert!(report.index() > 0.999, "{report}");
+    assert!(report.min_index() > 0.99, "{report}");
+}
+
+#[test]
+fn greedy_flow() {
+    let report = shared_link([("greedy", 3), ("polite", 1)]).report();
+
+    // the greedy flow gets 3/4 of the link: (3 + 1)^2 / (2 * (3^2 + 1^2)) = 0.8
+    assert!((report.index() - 0.8).abs() < 0.01, "{report}");
+
+    let window = &report.windows()[1];
+    assert!((window.share("greedy") - 0.75).abs() < 0.05, "{report}");
+}
+
+#[test]
+fn idle_window() {
+    let fairness = Fairness::new(10.ms());
+
+    Runtime::new().run(|| {
+        let fairness = fairness.clone();
+        async move {
+            fairness.record("a", 1);
+            // nothing is recorded in the second window
+            25.ms().sleep().await;
+            fairness.record("a", 1);
+        }
+        .primary()
+        .spawn();
+    });
+
+    let report = fairness.report();
+    let idle = &report.windows()[1];
+    assert_eq!(idle.total(), 0);
+    assert_eq!(idle.share("a"), 0.0);
+    assert!(!report.to_string().contains("NaN"), "{report}");
+}
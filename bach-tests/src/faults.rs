@@ -0,0 +1,76 @@
+use bach::{
+    environment::default::Runtime,
+    ext::*,
+    group::Group,
+    testing::{faults::Plan, Topology},
+    time::Instant,
+};
+use std::sync::{Arc, Mutex};
+
+fn topology() -> Topology {
+    let mut topology = Topology::new();
+    for (idx, az) in ["a", "a", "b", "b", "c"].into_iter().enumerate() {
+        let rack = format!("{az}{}", idx % 2);
+        topology.add_node(
+            Group::new(&format!("node-{idx}")),
+            [("az", az), ("rack", &rack)],
+        );
+    }
+    topology
+}
+
+fn run(seed: u64) -> Plan {
+    let mut rt = Runtime::new().with_seed(seed);
+    let plan = Arc::new(Mutex::new(None));
+
+    rt.run(|| {
+        let plan = plan.clone();
+        async move {
+            let topology = topology();
+            let generated = topology
+                .planner("az")
+                .with_faults(4)
+                .with_window(10.s())
+                .with_duration(1.s()..=3.s())
+                .plan();
+
+            let start = Instant::now();
+            generated.inject();
+
+            for fault in generated.faults() {
+                // the whole domain is taken out together
+                assert_eq!(fault.groups, topology.groups_in("az", &fault.domain));
+
+                bach::time::sleep_until(start + fault.start + 1.ms()).await;
+                assert!(fault.groups.iter().all(Group::is_paused), "{generated}");
+            }
+
+            *plan.lock().unwrap() = Some(generated);
+        }
+        .primary()
+        .spawn();
+    });
+
+    let plan = plan.lock().unwrap().take().unwrap();
+    plan
+}
+
+#[test]
+fn correlated_faults() {
+    let plan = run(1);
+    assert_eq!(plan.level(), "az");
+    assert_eq!(plan.faults().len(), 4);
+    assert!(plan.faults().windows(2).all(|w| w[0].start <= w[1].start));
+
+    for fault in plan.faults() {
+        assert!(fault.start <= 10.s());
+        assert!((1.s()..=3.s()).contains(&fault.duration));
+    }
+}
+
+#[test]
+fn reproducible() {
+    for seed in 0..5 {
+        assert_eq!(run(seed), run(seed));
+    }
+}
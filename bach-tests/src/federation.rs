@@ -0,0 +1,136 @@
+use bach::{
+    environment::{default::Runtime, federation::Federation},
+    ext::*,
+    time::Instant,
+};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn request_response() {
+    let mut federation = Federation::new();
+    let clients = federation.add(Runtime::new().with_seed(1));
+    let servers = federation.add(Runtime::new().with_seed(2));
+
+    let (requests, incoming) = federation.bridge::<u32>(servers, 5.ms()..=5.ms());
+    let (responses, replies) = federation.bridge::<u32>(clients, 10.ms()..=10.ms());
+
+    let received = Arc::new(Mutex::new(vec![]));
+
+    federation.enter(servers, || {
+        async move {
+            while let Ok(request) = incoming.recv().await {
+                // simulate some processing time on the server
+                1.ms().sleep().await;
+                responses.send(request * 2);
+            }
+        }
+        .daemon()
+        .spawn_named("server");
+    });
+
+    federation.enter(clients, || {
+        let received = received.clone();
+        async move {
+            for request in 0..3 {
+                requests.send(request);
+                let reply = replies.recv().await.unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push((reply, Instant::now().elapsed_since_start()));
+                3.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn_named("client");
+    });
+
+    federation.run();
+
+    assert_eq!(
+        *received.lock().unwrap(),
+        [(0, 16.ms()), (2, 35.ms()), (4, 54.ms())]
+    );
+
+    // the clocks stay in lockstep
+    let clients = federation.runtime(clients).elapsed();
+    let servers = federation.runtime(servers).elapsed();
+    assert_eq!(clients, servers);
+
+    // runtimes without anything scheduled can still be advanced
+    let target = federation.now().unwrap() + 1.s();
+    federation.advance_to(target);
+    assert_eq!(federation.now(), Some(target));
+}
+
+#[test]
+fn long_timer() {
+    let mut federation = Federation::new();
+    let clients = federation.add(Runtime::new().with_seed(1));
+    let servers = federation.add(Runtime::new().with_seed(2));
+
+    let (requests, incoming) = federation.bridge::<u32>(servers, 5.ms()..=5.ms());
+    let (responses, replies) = federation.bridge::<u32>(clients, 10.ms()..=10.ms());
+
+    let received = Arc::new(Mutex::new(vec![]));
+
+    federation.enter(servers, || {
+        // a timer far in the future shouldn't let the round skip past the ready tasks
+        async {
+            3600.s().sleep().await;
+        }
+        .daemon()
+        .spawn_named("maintenance");
+
+        async move {
+            while let Ok(request) = incoming.recv().await {
+                responses.send(request * 2);
+            }
+        }
+        .daemon()
+        .spawn_named("server");
+    });
+
+    federation.enter(clients, || {
+        let received = received.clone();
+        async move {
+            for request in 0..3 {
+                requests.send(request);
+                let reply = replies.recv().await.unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push((reply, Instant::now().elapsed_since_start()));
+            }
+        }
+        .primary()
+        .spawn_named("client");
+    });
+
+    federation.run();
+
+    // each round trip takes exactly the latency of both bridges
+    assert_eq!(
+        *received.lock().unwrap(),
+        [(0, 15.ms()), (2, 30.ms()), (4, 45.ms())]
+    );
+}
+
+#[test]
+#[should_panic(expected = "the federation stalled with primary tasks still pending")]
+fn stalled() {
+    let mut federation = Federation::new();
+    let servers = federation.add(Runtime::new());
+    let (_requests, incoming) = federation.bridge::<u32>(servers, 1.ms()..=1.ms());
+
+    federation.enter(servers, || {
+        async move {
+            // nothing is ever sent over the bridge
+            let _ = incoming.recv().await;
+        }
+        .primary()
+        .spawn();
+    });
+
+    federation.run();
+}
@@ -0,0 +1,236 @@
+use bach::{
+    environment::default::Runtime,
+    ext::*,
+    group::{Group, Local},
+    time::{Duration, Instant},
+};
+use std::sync::{Arc, Mutex};
+
+fn heartbeats(group: Group, beats: Arc<Mutex<Vec<Instant>>>) {
+    async move {
+        loop {
+            beats.lock().unwrap().push(Instant::now());
+            100.ms().sleep().await;
+        }
+    }
+    .group(&group.name())
+    .spawn_named("heartbeat");
+}
+
+fn max_gap(beats: &[Instant]) -> Duration {
+    beats
+        .windows(2)
+        .map(|w| w[1].elapsed_since_start() - w[0].elapsed_since_start())
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn pause_freezes_group() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let node = Arc::new(Mutex::new(vec![]));
+    let other = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let group = Group::new("node");
+        heartbeats(group, node.clone());
+        heartbeats(Group::new("other"), other.clone());
+
+        group.pause_at(Instant::now() + 1050.ms(), 550.ms());
+
+        async move {
+            1100.ms().sleep().await;
+            assert!(group.is_paused());
+            2.s().sleep().await;
+            assert!(!group.is_paused());
+        }
+        .primary()
+        .spawn();
+    });
+
+    let node = node.lock().unwrap();
+    let other = other.lock().unwrap();
+
+    // the paused group misses its heartbeats while the other group is unaffected
+    assert_eq!(max_gap(&node), 600.ms());
+    assert_eq!(max_gap(&other), 100.ms());
+    assert_eq!(other.len() - node.len(), 5);
+}
+
+#[test]
+fn injected_pauses() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let beats = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let group = Group::new("node");
+        heartbeats(group, beats.clone());
+
+        group.inject_pauses(|| 1050.ms(), || 250.ms());
+
+        async move {
+            5.s().sleep().await;
+        }
+        .primary()
+        .spawn();
+    });
+
+    let beats = beats.lock().unwrap();
+    assert_eq!(max_gap(&beats), 300.ms());
+}
+
+#[test]
+fn task_counts() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    rt.run(|| {
+        for id in 0..3 {
+            async move {
+                (id * 10).ms().sleep().await;
+            }
+            .group("workers")
+            .spawn();
+        }
+
+        async {
+            1.ms().sleep().await;
+
+            let workers = Group::find("workers").unwrap();
+            assert!(bach::group::list().contains(&workers));
+            assert!(Group::find("unknown").is_none());
+
+            // the first worker has already completed
+            assert_eq!(workers.task_count(), 2);
+
+            // tasks spawned from within a group inherit it, unless they have their own group
+            async {
+                async { 5.ms().sleep().await }.spawn();
+                async { 5.ms().sleep().await }.group("nested").spawn();
+            }
+            .group("workers")
+            .spawn();
+
+            1.ms().sleep().await;
+            assert_eq!(workers.task_count(), 3);
+            assert_eq!(Group::find("nested").unwrap().task_count(), 1);
+
+            30.ms().sleep().await;
+            assert_eq!(workers.task_count(), 0);
+        }
+        .group("orchestrator")
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn unaligned_pause() {
+    let mut rt = Runtime::new();
+
+    rt.run(|| {
+        async {
+            let group = Group::new("node");
+            // shorter than a tick, so it can't be slept exactly
+            group.pause(Duration::from_nanos(1500));
+            assert!(group.is_paused());
+            group.pause_at(Instant::now() + 5.us(), Duration::from_nanos(700));
+
+            10.us().sleep().await;
+            assert!(!group.is_paused());
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn paused_outer_task_count() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    rt.run(|| {
+        async {
+            async { 10.ms().sleep().await }.group("inner").spawn();
+        }
+        .group("outer")
+        .spawn();
+
+        async {
+            1.ms().sleep().await;
+            let outer = Group::find("outer").unwrap();
+            let inner = Group::find("inner").unwrap();
+            assert_eq!(inner.task_count(), 1);
+
+            // the task is woken while the outer group is paused but still counts towards the inner
+            outer.pause(20.ms());
+            15.ms().sleep().await;
+            assert_eq!(inner.task_count(), 1);
+            assert_eq!(outer.task_count(), 0);
+
+            10.ms().sleep().await;
+            assert_eq!(inner.task_count(), 0);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn list_order() {
+    let names = ["zeta", "alpha", "mid"];
+    let created: Vec<_> = names.iter().map(|name| Group::new(name)).collect();
+
+    // recreating a group keeps its original position
+    Group::new("alpha");
+
+    let listed: Vec<_> = bach::group::list()
+        .into_iter()
+        .filter(|group| names.contains(&group.name().as_str()))
+        .collect();
+    assert_eq!(listed, created);
+
+    let mut sorted = created.clone();
+    sorted.reverse();
+    sorted.sort();
+    assert_eq!(sorted, created);
+}
+
+#[test]
+fn foreign_access() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let checks = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let (sender, receiver) = bach::sync::channel::unbounded();
+
+        let checks_a = checks.clone();
+        async move {
+            let state = Local::new(vec![1, 2, 3]);
+            checks_a.lock().unwrap().push(("a", state.is_foreign()));
+            // the state should have been copied instead of sent to another host
+            sender.send(state).await.unwrap();
+        }
+        .group("a")
+        .primary()
+        .spawn();
+
+        let checks_b = checks.clone();
+        async move {
+            let state = receiver.recv().await.unwrap();
+            assert_eq!(state.group(), Group::find("a").unwrap());
+            checks_b.lock().unwrap().push(("b", state.is_foreign()));
+        }
+        .group("b")
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(*checks.lock().unwrap(), [("a", false), ("b", true)]);
+}
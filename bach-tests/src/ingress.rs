@@ -0,0 +1,69 @@
+use bach::{environment::default::Runtime, ext::*, time::Instant};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+};
+
+#[test]
+fn host_threads() {
+    let mut rt = Runtime::new();
+    let (first, first_rx) = rt.ingress::<u32>();
+    let (second, second_rx) = rt.ingress::<u32>();
+    assert_eq!((first.id(), second.id()), (0, 1));
+
+    let received = Arc::new(Mutex::new(vec![]));
+
+    rt.enter(|| {
+        for (name, rx) in [("first", first_rx), ("second", second_rx)] {
+            let received = received.clone();
+            async move {
+                while let Ok(event) = rx.recv().await {
+                    let now = Instant::now().elapsed_since_start();
+                    received.lock().unwrap().push((now, event));
+                }
+                received
+                    .lock()
+                    .unwrap()
+                    .push((Instant::now().elapsed_since_start(), 0));
+            }
+            .daemon()
+            .spawn_named(name);
+        }
+    });
+
+    // the second ingress sends first, but the first ingress is still delivered first
+    thread::scope(|s| {
+        s.spawn(|| {
+            second.send(10);
+            second.send(11);
+        });
+    });
+    thread::scope(|s| {
+        s.spawn(|| {
+            first.send(1);
+            first.send(2);
+        });
+    });
+
+    let now = rt.time_driver().now();
+    rt.advance_to(now + 1.ms());
+    assert_eq!(
+        *received.lock().unwrap(),
+        [(0.ms(), 1), (0.ms(), 2), (0.ms(), 10), (0.ms(), 11)]
+    );
+    received.lock().unwrap().clear();
+
+    // dropping an ingress closes its receiver after the remaining events are delivered
+    thread::spawn(move || {
+        first.send(3);
+        drop(first);
+    })
+    .join()
+    .unwrap();
+
+    let now = rt.time_driver().now();
+    rt.advance_to(now + 1.ms());
+    assert_eq!(*received.lock().unwrap(), [(1.ms(), 3), (1.ms(), 0)]);
+
+    drop(second);
+}
@@ -0,0 +1,150 @@
+use bach::{environment::default::Runtime, ext::*, group::Group, sync::Lease, time::Duration};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn failover() {
+    let expirations = Arc::new(Mutex::new(vec![]));
+    let writes = Arc::new(Mutex::new(vec![]));
+
+    Runtime::new().run(|| {
+        let lease = {
+            let expirations = expirations.clone();
+            Lease::builder(100.ms())
+                .with_on_expire(move |expired| {
+                    expirations
+                        .lock()
+                        .unwrap()
+                        .push((expired.group.name(), expired.epoch));
+                })
+                .build()
+        };
+
+        for name in ["a", "b"] {
+            let lease = lease.clone();
+            let writes = writes.clone();
+            async move {
+                let mut grant = lease.acquire().await;
+                while grant.check().is_ok() && grant.renew().await.is_ok() {
+                    30.ms().sleep().await;
+
+                    // the storage layer checks the fencing token before applying the write
+                    let res = lease.fence(grant.epoch());
+                    writes
+                        .lock()
+                        .unwrap()
+                        .push((name, grant.epoch(), res.is_ok()));
+                    if res.is_err() {
+                        return;
+                    }
+
+                    20.ms().sleep().await;
+                }
+            }
+            .group(name)
+            .spawn();
+        }
+
+        async move {
+            // pause the leader in between checking its grant and writing
+            120.ms().sleep().await;
+            Group::new("a").pause(300.ms());
+
+            1.s().sleep().await;
+            assert_eq!(lease.holder(), Some(Group::new("b")));
+            assert_eq!(lease.epoch(), 2);
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(*expirations.lock().unwrap(), [("a".to_string(), 1)]);
+
+    let writes = writes.lock().unwrap();
+    let stale: Vec<_> = writes.iter().filter(|(_, _, ok)| !ok).collect();
+    assert_eq!(stale, [&("a", 1, false)]);
+    assert!(writes
+        .iter()
+        .filter(|(name, _, _)| *name == "b")
+        .all(|(_, epoch, ok)| *epoch == 2 && *ok));
+}
+
+#[test]
+fn slow_renewal() {
+    Runtime::new().run(|| {
+        let mut delays = [1.ms(), 1.ms(), 150.ms()].into_iter();
+        let lease = Lease::builder(100.ms())
+            .with_renewal_delay(move || delays.next().unwrap_or_default())
+            .build();
+
+        async move {
+            let mut grant = lease.try_acquire().unwrap();
+            assert!(lease.try_acquire().is_none());
+
+            grant.renew().await.unwrap();
+            50.ms().sleep().await;
+            grant.renew().await.unwrap();
+            50.ms().sleep().await;
+
+            // the last renewal reaches the authority after the grant expired
+            let expired = grant.renew().await.unwrap_err();
+            assert_eq!(expired.epoch, 1);
+            assert!(!grant.is_held());
+            assert!(grant.check().is_err());
+
+            let next = lease.try_acquire().unwrap();
+            assert_eq!(next.epoch(), 2);
+            next.release();
+            assert_eq!(lease.holder(), None);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn unaligned_ttl() {
+    Runtime::new().run(|| {
+        let lease = Lease::new(Duration::from_nanos(1500));
+
+        async move {
+            let grant = lease.try_acquire().unwrap();
+            assert!(grant.is_held());
+
+            // the watch task expires the grant even though the TTL isn't a whole tick
+            lease.acquire().await;
+            assert_eq!(lease.epoch(), 2);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn takeover_expires_previous_holder() {
+    let expirations = Arc::new(Mutex::new(vec![]));
+
+    Runtime::new().run(|| {
+        let lease = {
+            let expirations = expirations.clone();
+            Lease::builder(10.us())
+                .with_on_expire(move |expired| {
+                    expirations.lock().unwrap().push(expired.epoch);
+                })
+                .build()
+        };
+
+        async move {
+            for _ in 0..10 {
+                let grant = lease.try_acquire().unwrap();
+
+                // take over the lease in the same tick that the watch task wakes up, which may be
+                // before it has a chance to expire the grant
+                bach::time::sleep_until(grant.expires()).await;
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(*expirations.lock().unwrap(), (1..=10).collect::<Vec<_>>());
+}
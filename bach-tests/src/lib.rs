@@ -1,9 +1,59 @@
 #[global_allocator]
 static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
+#[cfg(test)]
+mod autoscale;
+#[cfg(test)]
+mod broadcast;
+#[cfg(test)]
+mod condvar;
 #[cfg(test)]
 mod coop;
 #[cfg(test)]
+mod deadline;
+#[cfg(test)]
+mod examples;
+#[cfg(test)]
+mod executor;
+#[cfg(test)]
+mod failure_detector;
+#[cfg(test)]
+mod fairness;
+#[cfg(test)]
+mod faults;
+#[cfg(test)]
+mod federation;
+#[cfg(test)]
+mod group;
+#[cfg(test)]
+mod ingress;
+#[cfg(test)]
+mod lease;
+#[cfg(test)]
+mod output;
+#[cfg(test)]
+mod phaser;
+#[cfg(test)]
+mod process;
+#[cfg(test)]
 mod queue;
 #[cfg(test)]
+mod rand;
+#[cfg(test)]
+mod realtime;
+#[cfg(test)]
+mod scheduler;
+#[cfg(test)]
+mod services;
+#[cfg(test)]
+mod stats;
+#[cfg(test)]
+mod stream;
+#[cfg(test)]
 mod testing;
+#[cfg(test)]
+mod time;
+#[cfg(test)]
+mod watchdog;
+#[cfg(test)]
+mod workload;
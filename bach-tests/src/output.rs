@@ -0,0 +1,109 @@
+use bach::{
+    environment::default::Runtime,
+    ext::*,
+    output::{self, log_every, println, Stream},
+};
+
+#[test]
+fn capture() {
+    crate::testing::init_tracing();
+    output::set_echo(false);
+
+    let mut rt = Runtime::new();
+    rt.run(|| {
+        for id in 0..2u64 {
+            async move {
+                id.s().sleep().await;
+                println!("hello from {id}");
+            }
+            .group(&format!("node{id}"))
+            .primary()
+            .spawn_named("printer");
+        }
+    });
+
+    let records = output::take();
+    assert_eq!(records.len(), 2);
+
+    for (id, record) in records.iter().enumerate() {
+        assert_eq!(record.stream, Stream::Stdout);
+        assert_eq!(record.message, format!("hello from {id}\n"));
+        assert_eq!(record.group.unwrap().name(), format!("node{id}"));
+        assert_eq!(record.task.as_ref().unwrap().name(), Some("printer"));
+        assert_eq!(record.time.unwrap().elapsed_since_start(), (id as u64).s());
+    }
+
+    assert!(output::take().is_empty());
+}
+
+#[test]
+fn rate_limited() {
+    output::set_echo(false);
+
+    fn busy_loop() {
+        Runtime::new().run(|| {
+            async {
+                for i in 0..30 {
+                    log_every!(1.s(), "tick {i}");
+                    100.ms().sleep().await;
+                }
+            }
+            .primary()
+            .spawn();
+        });
+    }
+
+    busy_loop();
+    let messages: Vec<_> = output::take()
+        .into_iter()
+        .map(|record| (record.time.unwrap().elapsed_since_start(), record.message))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (0.s(), "tick 0\n".to_string()),
+            (1.s(), "tick 10 (9 suppressed)\n".to_string()),
+            (2.s(), "tick 20 (9 suppressed)\n".to_string()),
+        ]
+    );
+
+    // the call site starts over in a new run
+    busy_loop();
+    assert_eq!(output::take()[0].message, "tick 0\n");
+}
+
+#[test]
+fn rate_limited_per_run() {
+    output::set_echo(false);
+
+    fn run(ticks: u64) {
+        Runtime::new().run(|| {
+            async move {
+                for i in 0..ticks {
+                    log_every!(1.s(), "tick {i}");
+                }
+            }
+            .primary()
+            .spawn();
+        });
+    }
+
+    fn messages() -> Vec<String> {
+        output::take()
+            .into_iter()
+            .map(|record| record.message)
+            .collect()
+    }
+
+    run(3);
+    assert_eq!(messages(), ["tick 0\n"]);
+
+    // the second run logs at the same simulated time as the first
+    run(1);
+    assert_eq!(messages(), ["tick 0\n"]);
+
+    // records that weren't taken are cleared by the next run
+    run(3);
+    run(3);
+    assert_eq!(messages(), ["tick 0\n"]);
+}
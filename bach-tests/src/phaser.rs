@@ -0,0 +1,66 @@
+use bach::{environment::default::Runtime, ext::*, group::Group, testing::Phaser, time::Instant};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn phases() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let log = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let phaser = Phaser::new().with_timeout(10.s());
+
+        for node in 0..3u64 {
+            let group = Group::new(&format!("node{node}"));
+            phaser.register(group);
+
+            let phaser = phaser.clone();
+            let log = log.clone();
+            async move {
+                // each node takes a different amount of time to start
+                (node + 1).s().sleep().await;
+                assert_eq!(phaser.arrive("started").await, 0);
+                log.lock().unwrap().push((node, "started", Instant::now()));
+
+                assert_eq!(phaser.arrive("verify").await, 1);
+                log.lock().unwrap().push((node, "verify", Instant::now()));
+            }
+            .group(&group.name())
+            .primary()
+            .spawn();
+        }
+    });
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 6);
+    for (_, phase, time) in log.iter() {
+        let expected = match *phase {
+            "started" | "verify" => 3.s(),
+            _ => unreachable!(),
+        };
+        assert_eq!(time.elapsed_since_start(), expected);
+    }
+}
+
+#[test]
+#[should_panic(expected = "phase \"started\" timed out after 5s waiting for: node1")]
+fn phase_timeout() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new().with_rand(None);
+
+    rt.run(|| {
+        let phaser = Phaser::new().with_timeout(5.s());
+
+        let group = Group::new("node0");
+        phaser.register(group);
+        phaser.register(Group::new("node1"));
+
+        async move {
+            phaser.arrive("started").await;
+        }
+        .group(&group.name())
+        .primary()
+        .spawn();
+    });
+}
@@ -0,0 +1,97 @@
+use bach::{
+    environment::default::Runtime,
+    ext::*,
+    process::{self, ExitStatus, Process},
+    time::Instant,
+};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+fn run(f: impl FnOnce()) {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+    rt.run(f);
+}
+
+#[test]
+fn restart_loop() {
+    run(|| {
+        async {
+            let process = Process::exec("daemon", |io| async move {
+                io.stdout.write("starting\n");
+                1.s().sleep().await;
+                io.stderr.write("crashed\n");
+                1
+            });
+
+            let stdout = process.stdout();
+
+            // restart the daemon a couple of times, similar to a systemd unit
+            for _ in 0..3 {
+                let status = process.wait().await;
+                assert_eq!(status, ExitStatus::Code(1));
+                assert!(!status.success());
+                100.ms().sleep().await;
+                process.restart();
+            }
+
+            // let the last run write its output before killing it
+            10.ms().sleep().await;
+            process.kill();
+            assert_eq!(process.wait().await, ExitStatus::Killed);
+            assert_eq!(process.starts(), 4);
+
+            let mut lines = 0;
+            while let Ok(chunk) = stdout.try_pop() {
+                assert_eq!(chunk, b"starting\n");
+                lines += 1;
+            }
+            assert_eq!(lines, 4);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn kill_tasks() {
+    let ticks = Arc::new(AtomicU64::new(0));
+    let counter = ticks.clone();
+
+    run(move || {
+        async move {
+            let process = Process::exec("server", move |_io| {
+                let counter = counter.clone();
+                async move {
+                    process::spawn(async move {
+                        loop {
+                            100.ms().sleep().await;
+                            counter.fetch_add(1, Ordering::Relaxed);
+                        }
+                    });
+
+                    // the main task never exits on its own
+                    loop {
+                        1.s().sleep().await;
+                    }
+                }
+            });
+
+            let killed_at = Instant::now() + 1.s();
+            bach::time::sleep_until(killed_at).await;
+            process.kill();
+            assert_eq!(process.status(), Some(ExitStatus::Killed));
+
+            // make sure the background task stopped with the process
+            2.s().sleep().await;
+        }
+        .primary()
+        .spawn();
+    });
+
+    // the background task ticked during the first second only
+    assert!(ticks.load(Ordering::Relaxed) <= 10);
+    assert!(ticks.load(Ordering::Relaxed) >= 9);
+}
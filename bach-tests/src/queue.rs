@@ -104,3 +104,612 @@ fn latent_queue() {
     assert_eq!(elapsed, 20.ms());
     assert_eq!(RECV_COUNT.load(Ordering::Relaxed), COUNT);
 }
+
+#[test]
+fn per_destination_latency() {
+    use bach::sync::queue::latent::{self, LatencyExt as _};
+
+    static ARRIVALS: [AtomicDuration; 3] = [
+        AtomicDuration::new(Duration::ZERO),
+        AtomicDuration::new(Duration::ZERO),
+        AtomicDuration::new(Duration::ZERO),
+    ];
+
+    run(|| {
+        // (destination, size)
+        let latency = latent::per_key(|(dest, _): &(usize, usize)| *dest, 5.ms())
+            .with(1, 20.ms())
+            .with(2, 50.ms())
+            .plus(latent::transmission(
+                1_000_000,
+                |(_, size): &(usize, usize)| *size,
+            ));
+
+        let (sender, receiver) = Queue::default().latent(latency).channel();
+
+        async move {
+            for dest in 0..3 {
+                sender.send((dest, 1000)).await.unwrap();
+            }
+        }
+        .primary()
+        .spawn_named("client");
+
+        async move {
+            while let Ok((dest, _size)) = receiver.pop().await {
+                ARRIVALS[dest].store(Instant::now().elapsed_since_start(), Ordering::Relaxed);
+            }
+        }
+        .primary()
+        .spawn_named("server");
+    });
+
+    // each message also takes 1ms to transmit
+    let arrivals: Vec<_> = ARRIVALS
+        .iter()
+        .map(|arrival| arrival.load(Ordering::Relaxed))
+        .collect();
+    assert_eq!(arrivals, [6.ms(), 21.ms(), 51.ms()]);
+}
+
+#[test]
+fn throughput_history() {
+    use bach::sync::queue::throughput::Interval;
+    use std::sync::{Arc, Mutex};
+
+    let history = Arc::new(Mutex::new(vec![]));
+
+    run({
+        let history = history.clone();
+        move || {
+            let queue = Arc::new(
+                Queue::<Vec<u8>>::default()
+                    .throughput("link", 10.ms())
+                    .with_size(Vec::len),
+            );
+            let (sender, receiver) = queue.clone().channel();
+
+            async move {
+                // 2 items per interval, then a quiet interval, then 1 item per interval
+                for delay in [0, 5, 5, 5, 15, 10] {
+                    delay.ms().sleep().await;
+                    sender.send(vec![0; 100]).await.unwrap();
+                }
+            }
+            .primary()
+            .spawn_named("client");
+
+            async move {
+                while receiver.pop().await.is_ok() {}
+                *history.lock().unwrap() = queue.history();
+            }
+            .primary()
+            .spawn_named("server");
+        }
+    });
+
+    let interval = |start: u64, items: u64| Interval {
+        start: start.ms(),
+        items,
+        bytes: items * 100,
+    };
+
+    assert_eq!(
+        *history.lock().unwrap(),
+        [
+            interval(0, 2),
+            interval(10, 2),
+            interval(20, 0),
+            interval(30, 1)
+        ]
+    );
+}
+
+#[test]
+fn pending_snapshot() {
+    use bach::sync::queue::{Queue as _, Snapshot as _};
+    use std::sync::Arc;
+
+    run(|| {
+        let queue = Arc::new(Queue::default().sojourn());
+        let (sender, receiver) = queue.clone().channel();
+
+        async move {
+            for id in 0..10u32 {
+                1.ms().sleep().await;
+                sender.send(id).await.unwrap();
+            }
+        }
+        .primary()
+        .spawn_named("client");
+
+        async move {
+            while receiver.recv().await.is_ok() {
+                3.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn_named("server");
+
+        async move {
+            5.ms().sleep().await;
+            insta::assert_debug_snapshot!(queue.snapshot());
+            5.ms().sleep().await;
+            let expected = Queue::default();
+            for id in queue.snapshot() {
+                expected.push(id).unwrap();
+            }
+            assert_eq!(queue.digest(), expected.digest());
+        }
+        .primary()
+        .spawn_named("observer");
+    });
+}
+
+#[test]
+fn jitter_whole_ticks() {
+    use bach::sync::queue::latent::{Latency as _, LatencyExt};
+
+    run(|| {
+        async {
+            let latency = LatencyExt::<()>::with_jitter(10.ms(), 5.ms());
+            for _ in 0..100 {
+                let latency = latency.for_value(&());
+                assert!((10.ms()..=15.ms()).contains(&latency), "{latency:?}");
+                assert_eq!(latency.subsec_nanos() % 1000, 0, "{latency:?}");
+            }
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn latent_snapshot() {
+    use bach::sync::queue::{latent, Queue as _, Snapshot as _};
+
+    run(|| {
+        async {
+            let queue = Queue::default().latent(latent::from_fn(|delay: &u64| delay.ms()));
+            for delay in [30, 10, 20, 10] {
+                queue.push(delay).unwrap();
+            }
+
+            // items are listed in the order they become ready
+            assert_eq!(queue.snapshot(), [10, 10, 20, 30]);
+
+            15.ms().sleep().await;
+            assert_eq!(queue.pop(), Ok(10));
+            assert_eq!(queue.pop(), Ok(10));
+            assert_eq!(queue.snapshot(), [20, 30]);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn bounded_channels() {
+    use bach::sync::{channel, queue::vec_deque::Overflow};
+
+    run(|| {
+        let (sender, receiver) = channel::bounded(2);
+
+        async move {
+            for id in 0..4u32 {
+                sender.send(id).await.unwrap();
+            }
+            // the sender had to wait for the receiver to make room
+            assert_eq!(Instant::now().elapsed_since_start(), 10.ms());
+        }
+        .primary()
+        .spawn();
+
+        async move {
+            10.ms().sleep().await;
+            while receiver.recv().await.is_ok() {}
+        }
+        .primary()
+        .spawn();
+
+        let prev = channel::set_default_overflow(Overflow::PreferRecent);
+        let (sender, receiver) = channel::bounded(2);
+        channel::set_default_overflow(prev);
+
+        async move {
+            for id in 0..4u32 {
+                sender.send(id).await.unwrap();
+            }
+            drop(sender);
+
+            let mut received = vec![];
+            while let Ok(id) = receiver.recv().await {
+                received.push(id);
+            }
+            // the oldest messages were dropped to make room
+            assert_eq!(received, [2, 3]);
+        }
+        .primary()
+        .spawn();
+
+        let (sender, receiver) = channel::unbounded();
+        async move {
+            for id in 0..100u32 {
+                sender.send(id).await.unwrap();
+            }
+            assert_eq!(receiver.len(), 100);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn drain_unprocessed_work() {
+    use bach::sync::channel;
+    use std::sync::{Arc, Mutex};
+
+    let unreceived = Arc::new(Mutex::new(vec![]));
+    let unsent = Arc::new(Mutex::new(vec![]));
+
+    run(|| {
+        let (sender, receiver) = channel::unbounded();
+        let (idle_sender, idle_receiver) = channel::unbounded();
+
+        let unsent = unsent.clone();
+        async move {
+            // keep the idle channel open without ever reading from it
+            let _idle_receiver = idle_receiver;
+            for id in 0..6u32 {
+                sender.send(id).await.unwrap();
+                idle_sender.send(id).await.unwrap();
+            }
+            *unsent.lock().unwrap() = idle_sender.finish();
+        }
+        .primary()
+        .spawn();
+
+        // the worker only gets through the first few requests before the scenario ends
+        let unreceived = unreceived.clone();
+        async move {
+            for _ in 0..3 {
+                receiver.recv().await.unwrap();
+                10.ms().sleep().await;
+            }
+            *unreceived.lock().unwrap() = receiver.drain_remaining();
+            assert!(receiver.recv().await.is_err());
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(*unreceived.lock().unwrap(), [3, 4, 5]);
+    assert_eq!(*unsent.lock().unwrap(), [0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn congestion_feedback() {
+    use bach::sync::queue::{congestion::Signal, vec_deque::Overflow};
+    use std::sync::{Arc, Mutex};
+
+    let signals = Arc::new(Mutex::new(vec![]));
+
+    run({
+        let signals = signals.clone();
+        move || {
+            let queue = Arc::new(
+                vec_deque::Queue::builder()
+                    .with_capacity(Some(2))
+                    .with_overflow(Overflow::PreferRecent)
+                    .build()
+                    .congestion(),
+            );
+            let feedback = queue.subscribe();
+            let (sender, receiver) = queue.channel();
+
+            async move {
+                for i in 0..3 {
+                    sender.send(i).await.unwrap();
+                }
+                // give the receiver a chance to drain the queue before closing it
+                2.ms().sleep().await;
+            }
+            .primary()
+            .spawn_named("sender");
+
+            async move {
+                1.ms().sleep().await;
+                while receiver.try_pop().is_ok() {}
+                // let the transport observe the signals before the simulation ends
+                1.ms().sleep().await;
+            }
+            .primary()
+            .spawn_named("receiver");
+
+            async move {
+                while let Ok(signal) = feedback.recv().await {
+                    signals.lock().unwrap().push(signal);
+                }
+            }
+            .spawn_named("transport");
+        }
+    });
+
+    let occupancy = |len| Signal::Occupancy {
+        len,
+        capacity: Some(2),
+    };
+    let dropped = Signal::Dropped {
+        len: 2,
+        capacity: Some(2),
+    };
+
+    let signals = signals.lock().unwrap();
+    assert_eq!(
+        *signals,
+        [
+            occupancy(1),
+            occupancy(2),
+            dropped,
+            occupancy(1),
+            occupancy(0)
+        ]
+    );
+    assert_eq!(signals[1].utilization(), 1.0);
+}
+
+#[test]
+fn littles_law() {
+    use bach::{rand::*, sync::queue::occupancy::Stats};
+    use std::sync::{Arc, Mutex};
+
+    let stats = Arc::new(Mutex::new(Stats::default()));
+
+    run({
+        let stats = stats.clone();
+        move || {
+            let queue = Arc::new(Queue::<(Instant, u32)>::default().occupancy());
+            let (sender, receiver) = queue.clone().channel();
+
+            async move {
+                for i in 0..100 {
+                    (1..10u64).any().ms().sleep().await;
+                    sender.send(i).await.unwrap();
+                }
+            }
+            .primary()
+            .spawn_named("arrivals");
+
+            async move {
+                while receiver.pop().await.is_ok() {
+                    (1..8u64).any().ms().sleep().await;
+                }
+                *stats.lock().unwrap() = queue.stats();
+            }
+            .primary()
+            .spawn_named("service");
+        }
+    });
+
+    let stats = *stats.lock().unwrap();
+    assert_eq!(stats.arrivals, 100);
+    assert_eq!(stats.departures, 100);
+    assert!(stats.mean_len > 0.0, "{stats}");
+    // the queue is drained by the end of the run so the law holds exactly
+    stats.assert_littles_law(1e-9);
+}
+
+#[test]
+fn blocking_host_thread() {
+    use bach::sync::channel;
+    use std::thread;
+
+    let (requests, incoming) = channel::bounded::<u32>(1);
+    let (responses, replies) = channel::unbounded();
+
+    let host = thread::spawn(move || {
+        let mut replies_received = vec![];
+        for request in 0..3 {
+            requests.blocking_send(request).unwrap();
+            replies_received.push(replies.blocking_recv().unwrap());
+        }
+        drop(requests);
+        // the simulation closes its side once it sees the requests end
+        assert!(replies.blocking_recv().is_err());
+        replies_received
+    });
+
+    let mut rt = Runtime::new();
+    rt.enter(|| {
+        async move {
+            while let Ok(request) = incoming.recv().await {
+                responses.send(request * 10).await.unwrap();
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    // step the simulation until the host thread is done with it
+    while !host.is_finished() {
+        let now = rt.time_driver().now();
+        rt.advance_to(now + 1.ms());
+        thread::yield_now();
+    }
+
+    assert_eq!(host.join().unwrap(), [0, 10, 20]);
+}
+
+#[test]
+fn random_loss() {
+    use std::sync::{Arc, Mutex};
+
+    let received = |seed| {
+        let received = Arc::new(Mutex::new(vec![]));
+        let mut rt = Runtime::new().with_seed(seed);
+        rt.run(|| {
+            let (sender, receiver) = vec_deque::Queue::default()
+                .loss(0.1)
+                .latent(10.ms())
+                .channel();
+
+            async move {
+                for i in 0..2_000 {
+                    sender.send(i).await.unwrap();
+                }
+            }
+            .primary()
+            .spawn_named("sender");
+
+            let received = received.clone();
+            async move {
+                while let Ok(i) = receiver.recv().await {
+                    received.lock().unwrap().push(i);
+                }
+            }
+            .primary()
+            .spawn_named("receiver");
+        });
+        let received = received.lock().unwrap().clone();
+        received
+    };
+
+    let first = received(1);
+    assert!((1_700..=1_900).contains(&first.len()), "{}", first.len());
+
+    // the same seed loses the same items
+    assert_eq!(received(1), first);
+    assert_ne!(received(2), first);
+}
+
+#[test]
+fn cancel_safe_recv() {
+    use bach::sync::channel;
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::{Arc, Mutex},
+        task::Poll,
+    };
+
+    let received = Arc::new(Mutex::new(vec![]));
+
+    run({
+        let received = received.clone();
+        move || {
+            let (fast, fast_rx) = channel::unbounded();
+            let (slow, slow_rx) = channel::unbounded();
+
+            async move {
+                for i in 0..20u64 {
+                    1.ms().sleep().await;
+                    fast.send(i).await.unwrap();
+                    if i % 5 == 0 {
+                        slow.send(100 + i).await.unwrap();
+                    }
+                }
+            }
+            .primary()
+            .spawn_named("sender");
+
+            async move {
+                loop {
+                    // whichever future loses the race is dropped without losing its message
+                    let mut fast = pin!(fast_rx.recv());
+                    let mut slow = pin!(slow_rx.recv());
+                    let message = std::future::poll_fn(|cx| {
+                        if let Poll::Ready(message) = fast.as_mut().poll(cx) {
+                            return Poll::Ready(message);
+                        }
+                        slow.as_mut().poll(cx)
+                    })
+                    .await;
+
+                    let Ok(message) = message else {
+                        break;
+                    };
+                    received.lock().unwrap().push(message);
+                }
+            }
+            .primary()
+            .spawn_named("receiver");
+        }
+    });
+
+    let mut received = received.lock().unwrap().clone();
+    received.sort();
+    let expected: Vec<_> = (0..20).chain([100, 105, 110, 115]).collect();
+    assert_eq!(received, expected);
+}
+
+#[test]
+fn multiplexed_poll_recv() {
+    use bach::sync::channel;
+    use std::{
+        pin::pin,
+        sync::{Arc, Mutex},
+        task::Poll,
+    };
+
+    let received = Arc::new(Mutex::new(vec![]));
+
+    run({
+        let received = received.clone();
+        move || {
+            let (a, a_rx) = channel::unbounded();
+            let (b, b_rx) = channel::unbounded();
+
+            async move {
+                for i in 0..3 {
+                    a.send(("a", i)).await.unwrap();
+                    1.ms().sleep().await;
+                    b.send(("b", i)).await.unwrap();
+                    1.ms().sleep().await;
+                }
+            }
+            .primary()
+            .spawn_named("sender");
+
+            async move {
+                let mut receivers = [pin!(a_rx), pin!(b_rx)];
+                let mut open = [true, true];
+                // a hand-written state machine that drains both channels until they close
+                std::future::poll_fn(|cx| {
+                    for (receiver, open) in receivers.iter_mut().zip(open.iter_mut()) {
+                        while *open {
+                            match receiver.as_mut().poll_recv(cx) {
+                                Poll::Ready(Ok(message)) => {
+                                    let now = Instant::now().elapsed_since_start();
+                                    received.lock().unwrap().push((now, message));
+                                }
+                                Poll::Ready(Err(_)) => *open = false,
+                                Poll::Pending => break,
+                            }
+                        }
+                    }
+                    if open.iter().any(|open| *open) {
+                        Poll::Pending
+                    } else {
+                        Poll::Ready(())
+                    }
+                })
+                .await;
+            }
+            .primary()
+            .spawn_named("receiver");
+        }
+    });
+
+    let received = received.lock().unwrap().clone();
+    assert_eq!(
+        received,
+        [
+            (0.ms(), ("a", 0)),
+            (1.ms(), ("b", 0)),
+            (2.ms(), ("a", 1)),
+            (3.ms(), ("b", 1)),
+            (4.ms(), ("a", 2)),
+            (5.ms(), ("b", 2)),
+        ]
+    );
+}
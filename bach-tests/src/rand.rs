@@ -0,0 +1,70 @@
+use bach::{environment::default::Runtime, ext::*, rand};
+use std::sync::{Arc, Mutex};
+
+fn generate(seed: u64) -> (Vec<u32>, Option<u32>, Vec<u32>) {
+    let mut rt = Runtime::new().with_seed(seed);
+    let output = Arc::new(Mutex::new(None));
+
+    rt.run(|| {
+        let output = output.clone();
+        async move {
+            let values: Vec<u32> = (0..20).collect();
+            let shuffled = values.shuffled();
+            let chosen = rand::choose(&values).copied();
+            let sampled = rand::sample(&values, 5);
+            *output.lock().unwrap() = Some((shuffled, chosen, sampled));
+        }
+        .primary()
+        .spawn();
+    });
+
+    let output = output.lock().unwrap().take().unwrap();
+    output
+}
+
+#[test]
+fn reproducible_shuffles() {
+    crate::testing::init_tracing();
+
+    let (shuffled, chosen, sampled) = generate(123);
+
+    let mut sorted = shuffled.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+    assert!(chosen.is_some());
+
+    let mut sampled_sorted = sampled.clone();
+    sampled_sorted.sort();
+    sampled_sorted.dedup();
+    assert_eq!(sampled_sorted.len(), 5);
+
+    // the same seed produces the same values
+    assert_eq!(generate(123), (shuffled, chosen, sampled));
+    assert_ne!(generate(123).0, generate(456).0);
+
+    assert_eq!(rand::choose::<u8>(&[]), None);
+}
+
+#[test]
+fn weighted() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+    let counts = Arc::new(Mutex::new([0u32; 3]));
+
+    rt.run(|| {
+        let counts = counts.clone();
+        async move {
+            let targets = rand::Weighted::new([(0, 80), (1, 20), (2, 0)]);
+            for _ in 0..1000 {
+                counts.lock().unwrap()[*targets.pick()] += 1;
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    let [hot, cold, never] = *counts.lock().unwrap();
+    assert!((750..850).contains(&hot), "{hot}");
+    assert_eq!(hot + cold, 1000);
+    assert_eq!(never, 0);
+}
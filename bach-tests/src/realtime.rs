@@ -0,0 +1,71 @@
+use bach::{
+    environment::{default::Runtime, realtime::Realtime},
+    ext::*,
+    sync::channel,
+    time::Instant,
+};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+#[test]
+fn paced() {
+    let mut rt = Runtime::new();
+    rt.enter(|| {
+        async {
+            for _ in 0..10 {
+                100.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    let start = std::time::Instant::now();
+    // 1s of simulated time takes 10ms
+    Realtime::new(&mut rt).with_scale(100.0).run();
+
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert_eq!(rt.elapsed(), 1.s());
+}
+
+#[test]
+fn host_service() {
+    let mut rt = Runtime::new();
+    let (responses, replies) = rt.ingress::<u32>();
+    let (requests, outgoing) = channel::unbounded::<u32>();
+
+    // a "real" service that takes 5ms of wall-clock time to respond
+    let service = thread::spawn(move || {
+        while let Ok(request) = outgoing.blocking_recv() {
+            thread::sleep(Duration::from_millis(5));
+            responses.send(request + 1);
+        }
+    });
+
+    let latencies = Arc::new(Mutex::new(vec![]));
+    rt.enter(|| {
+        let latencies = latencies.clone();
+        async move {
+            for request in 0..3 {
+                let start = Instant::now();
+                requests.send(request).await.unwrap();
+                assert_eq!(replies.recv().await.unwrap(), request + 1);
+                latencies.lock().unwrap().push(start.elapsed());
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    Realtime::new(&mut rt).with_scale(10.0).run();
+    service.join().unwrap();
+
+    // 5ms of wall-clock time is 50ms of simulated time, minus up to one poll interval for the
+    // reply to be picked up
+    for latency in latencies.lock().unwrap().iter() {
+        assert!(*latency >= 40.ms(), "{latency:?}");
+    }
+}
@@ -0,0 +1,159 @@
+//! Scheduler benchmarks that double as stress tests for the coop scheduler
+//!
+//! Each scenario is explored exhaustively, recording the number of interleavings and the
+//! wall-clock time spent on each one. The bounds on the time per interleaving are loose enough for
+//! unoptimized builds on slow machines but catch accidental quadratic behavior in the scheduler.
+
+use bach::{environment::default::Runtime, ext::*, sync::channel};
+use std::{
+    cell::Cell,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+struct Bench {
+    interleavings: usize,
+    elapsed: Duration,
+}
+
+impl Bench {
+    fn per_interleaving(&self) -> Duration {
+        self.elapsed / self.interleavings as u32
+    }
+}
+
+/// Explores all of the interleavings of the simulation set up by `f`
+fn bench(f: impl Fn() + std::panic::RefUnwindSafe) -> Bench {
+    // tests run in parallel so keep the count local to the thread
+    thread_local! {
+        static ITERATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+    ITERATIONS.with(|count| count.set(0));
+
+    let start = Instant::now();
+    bolero::check!().exhaustive().run(|| {
+        ITERATIONS.with(|count| count.set(count.get() + 1));
+        let mut rt = Runtime::new().with_coop(true).with_rand(None);
+        rt.run(&f);
+    });
+
+    Bench {
+        interleavings: ITERATIONS.with(|count| count.get()),
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Passes `tokens` tokens around a ring of `tasks` tasks, each going around `rounds` times
+fn token_ring(tasks: usize, tokens: usize, rounds: usize, hops: Arc<Mutex<usize>>) {
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..tasks).map(|_| channel::bounded(1)).unzip();
+    let finished = Arc::new(Mutex::new(0));
+
+    for (id, receiver) in receivers.into_iter().enumerate() {
+        let next = senders[(id + 1) % tasks].clone();
+        let hops = hops.clone();
+        let finished = finished.clone();
+        async move {
+            // spread the tokens out evenly
+            if id % (tasks / tokens) == 0 && id / (tasks / tokens) < tokens {
+                next.send(0).await.unwrap();
+            }
+            while let Ok(hop) = receiver.recv().await {
+                *hops.lock().unwrap() += 1;
+                if hop + 1 < tasks * rounds {
+                    next.send(hop + 1).await.unwrap();
+                    continue;
+                }
+
+                let mut finished = finished.lock().unwrap();
+                *finished += 1;
+                // closing the channel shuts down the rest of the ring
+                if *finished == tokens {
+                    return;
+                }
+            }
+        }
+        .primary()
+        .spawn_named(format!("ring_{id}"));
+    }
+}
+
+/// Sends `messages` messages from each of `tasks` tasks to all of the others
+fn mesh(tasks: usize, messages: usize, received: Arc<Mutex<Vec<usize>>>) {
+    *received.lock().unwrap() = vec![0; tasks];
+
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..tasks).map(|_| channel::unbounded()).unzip();
+
+    for (id, receiver) in receivers.into_iter().enumerate() {
+        let peers: Vec<_> = senders
+            .iter()
+            .enumerate()
+            .filter(|(peer, _)| *peer != id)
+            .map(|(_, sender)| sender.clone())
+            .collect();
+
+        async move {
+            for message in 0..messages {
+                for peer in &peers {
+                    peer.send(message).await.unwrap();
+                }
+            }
+        }
+        .primary()
+        .spawn_named(format!("mesh_{id}_send"));
+
+        let received = received.clone();
+        let expected = (tasks - 1) * messages;
+        async move {
+            for _ in 0..expected {
+                receiver.recv().await.unwrap();
+                received.lock().unwrap()[id] += 1;
+            }
+        }
+        .primary()
+        .spawn_named(format!("mesh_{id}_recv"));
+    }
+}
+
+#[test]
+fn token_ring_bench() {
+    for (tasks, tokens) in [(2, 1), (8, 1), (8, 4), (64, 16)] {
+        let hops = Arc::new(Mutex::new(0));
+        let bench = {
+            let hops = hops.clone();
+            bench(move || token_ring(tasks, tokens, 10, hops.clone()))
+        };
+
+        // each channel only has a single sender and receiver so there's nothing to reorder, no
+        // matter how many tokens are in flight
+        assert_eq!(bench.interleavings, 1);
+        assert_eq!(*hops.lock().unwrap(), tasks * tokens * 10);
+        assert!(
+            bench.per_interleaving() < Duration::from_secs(2),
+            "{tasks} tasks with {tokens} tokens took {:?}",
+            bench.per_interleaving()
+        );
+    }
+}
+
+#[test]
+fn mesh_bench() {
+    for (tasks, messages, interleavings) in [(2, 1, 1), (3, 1, 4), (3, 2, 16), (4, 1, 72)] {
+        let received = Arc::new(Mutex::new(vec![]));
+        let bench = {
+            let received = received.clone();
+            bench(move || mesh(tasks, messages, received.clone()))
+        };
+
+        // the senders of each channel are only interleaved with each other
+        assert_eq!(bench.interleavings, interleavings);
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![(tasks - 1) * messages; tasks]
+        );
+        assert!(
+            bench.per_interleaving() < Duration::from_millis(100),
+            "{tasks} tasks with {messages} messages took {:?}",
+            bench.per_interleaving()
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use bach::{environment::default::Runtime, ext::*, services, sync::channel};
+use std::sync::{Arc, Mutex};
+
+/// A simulated key-value store that other components discover by name
+#[derive(Clone)]
+struct Store {
+    requests: channel::Sender<(String, u64)>,
+}
+
+fn start_store(totals: Arc<Mutex<Vec<(String, u64)>>>) {
+    let (requests, receiver) = channel::unbounded();
+
+    async move {
+        while let Ok(request) = receiver.recv().await {
+            totals.lock().unwrap().push(request);
+        }
+    }
+    .group("store")
+    .spawn();
+
+    services::register("store", Store { requests });
+}
+
+#[test]
+fn discovery() {
+    let totals = Arc::new(Mutex::new(vec![]));
+
+    Runtime::new().run(|| {
+        // the client starts before the store is registered
+        async {
+            let store: Store = services::wait("store").await;
+            store.requests.send(("client".into(), 1)).await.unwrap();
+        }
+        .group("client")
+        .primary()
+        .spawn();
+
+        let totals = totals.clone();
+        async move {
+            10.ms().sleep().await;
+            start_store(totals);
+
+            let store = services::get::<Store>("store").unwrap();
+            store.requests.send(("starter".into(), 2)).await.unwrap();
+
+            assert_eq!(services::names(), ["store"]);
+            assert!(services::get::<Store>("unknown").is_none());
+
+            // give the store a chance to process the requests
+            1.ms().sleep().await;
+        }
+        .primary()
+        .spawn();
+    });
+
+    let mut totals = totals.lock().unwrap().clone();
+    totals.sort();
+    assert_eq!(
+        totals,
+        [("client".to_string(), 1), ("starter".to_string(), 2)]
+    );
+
+    // each runtime has its own registry
+    Runtime::new().run(|| {
+        async {
+            assert!(services::names().is_empty());
+            services::register("store", 42u64);
+            assert!(services::remove("store"));
+            assert!(!services::remove("store"));
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+/// A service that looks at the registry when it's dropped
+#[derive(Clone)]
+struct Observer(Arc<Mutex<Option<Vec<String>>>>);
+
+impl Drop for Observer {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap() = Some(services::names());
+    }
+}
+
+#[test]
+fn drop_outside_of_lock() {
+    let observed = Arc::new(Mutex::new(None));
+
+    Runtime::new().run(|| {
+        let observed = observed.clone();
+        async move {
+            services::register("tracked", Observer(observed));
+            services::register("other", 1u64);
+            assert!(services::remove("tracked"));
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(*observed.lock().unwrap(), Some(vec!["other".to_string()]));
+}
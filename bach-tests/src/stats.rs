@@ -0,0 +1,38 @@
+use bach::{environment::default::Runtime, ext::*, testing::Estimate, time::Instant};
+use std::sync::{Arc, Mutex};
+
+/// Returns the average time it took to serve 100 requests with a random service time
+fn mean_service_time(seed: u64) -> f64 {
+    let mut rt = Runtime::new().with_seed(seed);
+    let mean = Arc::new(Mutex::new(0.0));
+
+    rt.run(|| {
+        let mean = mean.clone();
+        async move {
+            let start = Instant::now();
+            for _ in 0..100 {
+                let service_ms = (0..=10u64).any();
+                service_ms.ms().sleep().await;
+            }
+            *mean.lock().unwrap() = start.elapsed().as_secs_f64() * 1000.0 / 100.0;
+        }
+        .primary()
+        .spawn();
+    });
+
+    let mean = *mean.lock().unwrap();
+    mean
+}
+
+#[test]
+fn multi_seed_estimate() {
+    crate::testing::init_tracing();
+
+    let estimate = Estimate::new((0..20).map(mean_service_time));
+    assert_eq!(estimate.count(), 20);
+
+    // service times are uniform over 0..=10ms
+    let interval = estimate.confidence_interval(0.99);
+    assert!(interval.contains(&5.0), "{estimate}");
+    assert!(estimate.half_width(0.99) < 1.0, "{estimate}");
+}
@@ -0,0 +1,246 @@
+use bach::{
+    environment::default::Runtime,
+    ext::*,
+    stream::{join, FuturesUnordered, Stream},
+    sync::{channel::Receiver, queue::vec_deque::Queue},
+    time::{Duration, Instant},
+};
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+#[test]
+fn futures_unordered_completion_order() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    rt.run(|| {
+        async move {
+            let mut futures: FuturesUnordered<_> = [3u64, 1, 2]
+                .into_iter()
+                .map(|delay| async move {
+                    delay.ms().sleep().await;
+                    delay
+                })
+                .collect();
+
+            let mut outputs = vec![];
+            while let Some(output) = futures.next().await {
+                outputs.push(output);
+            }
+            assert_eq!(outputs, [1, 2, 3]);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn futures_unordered_coop_interleavings() {
+    crate::testing::init_tracing();
+    static ORDERS: Mutex<BTreeSet<Vec<u8>>> = Mutex::new(BTreeSet::new());
+
+    bolero::check!().exhaustive().run(|| {
+        let mut rt = Runtime::new().with_coop(true).with_rand(None);
+        rt.run(|| {
+            async move {
+                let mut futures: FuturesUnordered<_> =
+                    (0..3u8).map(|id| async move { id }).collect();
+
+                let mut outputs = vec![];
+                while let Some(output) = futures.next().await {
+                    outputs.push(output);
+                }
+                ORDERS.lock().unwrap().insert(outputs);
+            }
+            .primary()
+            .spawn();
+        });
+    });
+
+    // every completion order is explored
+    assert_eq!(ORDERS.lock().unwrap().len(), 6);
+}
+
+#[test]
+fn join_all_spawned_tasks() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    rt.run(|| {
+        async move {
+            let start = Instant::now();
+            let handles = [30u64, 10, 20].map(|delay| {
+                async move {
+                    delay.ms().sleep().await;
+                    delay
+                }
+                .spawn()
+            });
+
+            let outputs = join::join_all("scatter", handles).await;
+            assert_eq!(outputs, [30, 10, 20]);
+            // the branches run concurrently so the slowest one determines the latency
+            assert_eq!(start.elapsed(), 30.ms());
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn try_join_all_first_error() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    rt.run(|| {
+        async move {
+            let start = Instant::now();
+            let branches = [(30u64, true), (10, false), (20, true)].map(|(delay, ok)| async move {
+                delay.ms().sleep().await;
+                if ok {
+                    Ok(delay)
+                } else {
+                    Err(delay)
+                }
+            });
+
+            let res = join::try_join_all("scatter", branches).await;
+            assert_eq!(res, Err(10));
+            // the remaining branches are dropped as soon as one fails
+            assert_eq!(start.elapsed(), 10.ms());
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+/// Sends `items` at the given offsets from the start of the simulation and returns the
+/// timestamped output of `adapter`
+fn timed<T, S>(
+    items: &'static [(u64, u8)],
+    adapter: impl 'static + FnOnce(Receiver<u8>) -> S + Send,
+) -> Vec<(Duration, T)>
+where
+    S: Stream<Item = T> + Send,
+    T: 'static + Send + core::fmt::Debug,
+{
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+    let outputs = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let (sender, receiver) = Queue::default().channel();
+
+        async move {
+            let start = Instant::now();
+            for (offset, item) in items {
+                bach::time::sleep_until(start + offset.ms()).await;
+                sender.push(*item).await.unwrap();
+            }
+            // keep the stream open for a bit after the last item
+            100.ms().sleep().await;
+        }
+        .spawn();
+
+        let outputs = outputs.clone();
+        async move {
+            let mut stream = core::pin::pin!(adapter(receiver));
+            while let Some(item) = core::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+                let now = Instant::now().elapsed_since_start();
+                outputs.lock().unwrap().push((now, item));
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    Arc::try_unwrap(outputs).unwrap().into_inner().unwrap()
+}
+
+const ITEMS: &[(u64, u8)] = &[(0, 0), (10, 1), (20, 2), (90, 3), (250, 4), (260, 5)];
+
+#[test]
+fn throttle() {
+    let outputs = timed(ITEMS, |s| s.throttle(50.ms()));
+    assert_eq!(
+        outputs,
+        [
+            (0.ms(), 0),
+            (50.ms(), 1),
+            (100.ms(), 2),
+            (150.ms(), 3),
+            (250.ms(), 4),
+            (300.ms(), 5),
+        ]
+    );
+}
+
+#[test]
+fn debounce() {
+    let outputs = timed(ITEMS, |s| s.debounce(50.ms()));
+    assert_eq!(outputs, [(70.ms(), 2), (140.ms(), 3), (310.ms(), 5)]);
+}
+
+#[test]
+fn buffer_timeout() {
+    let outputs = timed(ITEMS, |s| s.buffer_timeout(2, 50.ms()));
+    assert_eq!(
+        outputs,
+        [
+            (10.ms(), vec![0, 1]),
+            (70.ms(), vec![2]),
+            (140.ms(), vec![3]),
+            (260.ms(), vec![4, 5]),
+        ]
+    );
+}
+
+#[test]
+fn sample() {
+    let outputs = timed(ITEMS, |s| s.sample(100.ms()));
+    assert_eq!(outputs, [(100.ms(), 3), (300.ms(), 5)]);
+}
+
+#[test]
+fn sample_flushes_on_end() {
+    // the stream ends at 360ms, before the period at 400ms
+    let outputs = timed(ITEMS, |s| s.sample(200.ms()));
+    assert_eq!(outputs, [(200.ms(), 3), (360.ms(), 5)]);
+}
+
+#[test]
+fn sample_slow_consumer() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+    let outputs = Arc::new(Mutex::new(vec![]));
+
+    rt.run(|| {
+        let (sender, receiver) = Queue::default().channel();
+
+        async move {
+            for item in 0..6u8 {
+                sender.push(item).await.unwrap();
+                45.ms().sleep().await;
+            }
+        }
+        .spawn();
+
+        let outputs = outputs.clone();
+        async move {
+            let mut stream = core::pin::pin!(receiver.sample(100.ms()));
+            while let Some(item) = core::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+                let now = Instant::now().elapsed_since_start();
+                outputs.lock().unwrap().push((now, item));
+                // a slow consumer doesn't shift the following periods
+                30.ms().sleep().await;
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    let outputs = outputs.lock().unwrap();
+    assert_eq!(*outputs, [(100.ms(), 2), (200.ms(), 4), (270.ms(), 5)]);
+}
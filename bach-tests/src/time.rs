@@ -0,0 +1,117 @@
+use bach::{environment::default::Runtime, ext::*, sync::queue::vec_deque::Queue, time::Instant};
+
+#[test]
+fn time_driver_post_at() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+    let driver = rt.time_driver();
+
+    let (sender, receiver) = Queue::default().channel();
+
+    let target = driver.now() + 5.s();
+    std::thread::spawn(move || {
+        driver.post_at(target, move || {
+            sender.try_push(Instant::now()).unwrap();
+        });
+    })
+    .join()
+    .unwrap();
+
+    rt.run(|| {
+        async move {
+            let fired = receiver.pop().await.unwrap();
+            assert_eq!(fired, target);
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(rt.elapsed(), 5.s());
+}
+
+#[test]
+fn timer_coalescing() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new().with_timer_coalescing(1.ms());
+
+    let wakes = std::sync::Arc::new(std::sync::Mutex::new(std::collections::BTreeSet::new()));
+
+    rt.run(|| {
+        for i in 1..=100u64 {
+            let wakes = wakes.clone();
+            async move {
+                (i * 30).us().sleep().await;
+                wakes
+                    .lock()
+                    .unwrap()
+                    .insert(Instant::now().elapsed_since_start());
+            }
+            .primary()
+            .spawn();
+        }
+    });
+
+    // 100 timers spread over 3ms fire on 3 ticks
+    let wakes = wakes.lock().unwrap();
+    assert_eq!(
+        wakes.iter().copied().collect::<Vec<_>>(),
+        [1.ms(), 2.ms(), 3.ms()]
+    );
+}
+
+#[test]
+fn step_through_deadlines() {
+    crate::testing::init_tracing();
+    let mut rt = Runtime::new();
+
+    let wakes = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+
+    let start = rt.run(|| {
+        for delay in [10.ms(), 250.ms(), 3.s()] {
+            let wakes = wakes.clone();
+            async move {
+                delay.sleep().await;
+                wakes.lock().unwrap().push(delay);
+            }
+            .spawn();
+        }
+        Instant::now()
+    });
+
+    let deadline = rt.next_deadline().unwrap();
+    assert_eq!(deadline, start + 10.ms());
+    rt.advance_to(deadline);
+    assert_eq!(rt.elapsed(), 10.ms());
+    assert_eq!(*wakes.lock().unwrap(), [10.ms()]);
+
+    // advancing past a deadline wakes the timer without stopping at it
+    rt.advance_to(start + 1.s());
+    assert_eq!(rt.elapsed(), 1.s());
+    assert_eq!(*wakes.lock().unwrap(), [10.ms(), 250.ms()]);
+
+    assert_eq!(rt.next_deadline(), Some(start + 3.s()));
+    rt.advance_to(start + 3.s());
+    assert_eq!(rt.next_deadline(), None);
+    assert_eq!(*wakes.lock().unwrap(), [10.ms(), 250.ms(), 3.s()]);
+}
+
+#[test]
+fn advance_to_with_coalescing() {
+    let mut rt = Runtime::new().with_timer_coalescing(10.ms());
+
+    let start = rt.run(|| {
+        async {
+            25.ms().sleep().await;
+        }
+        .spawn();
+        Instant::now()
+    });
+
+    // the coalesced timers don't move the clock past the target
+    for offset in [3.ms(), 25.ms(), 31.ms()] {
+        let target = start + offset;
+        rt.advance_to(target);
+        assert_eq!(rt.time_driver().now(), target);
+    }
+    assert_eq!(rt.next_deadline(), None);
+}
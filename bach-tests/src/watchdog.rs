@@ -0,0 +1,78 @@
+use bach::{environment::default::Runtime, ext::*, testing::Watchdog, time::Instant};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn throughput_collapse() {
+    let watchdog = Watchdog::new("requests", 50.0).with_grace(2.s());
+    let stalls = Arc::new(Mutex::new(vec![]));
+
+    Runtime::new().run(|| {
+        // serves 100 requests per second, then slows down to 10 after 5s
+        let progress = watchdog.clone();
+        async move {
+            loop {
+                let delay = if Instant::now().elapsed_since_start() < 5.s() {
+                    10.ms()
+                } else {
+                    100.ms()
+                };
+                delay.sleep().await;
+                progress.increment();
+            }
+        }
+        .daemon()
+        .spawn_named("server");
+
+        let watchdog = watchdog.clone();
+        let stalls = stalls.clone();
+        async move {
+            for _ in 0..8 {
+                if let Err(stall) = watchdog.sample() {
+                    stalls.lock().unwrap().push(stall);
+                }
+                1.s().sleep().await;
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    let stalls = stalls.lock().unwrap();
+    let at: Vec<_> = stalls
+        .iter()
+        .map(|s| s.at().elapsed_since_start())
+        .collect();
+    assert_eq!(at, [6.s(), 7.s()]);
+
+    let stall = &stalls[0];
+    assert_eq!(stall.name(), "requests");
+    assert!(stall.progress() < 50, "{stall}");
+    assert_eq!(stall.history().last(), Some(&stall.progress()));
+    assert!(stall.history()[..4].iter().all(|p| *p >= 99), "{stall}");
+    assert!(stall.to_string().starts_with("`requests` advanced by"));
+}
+
+#[test]
+fn grace_period() {
+    let watchdog = Watchdog::new("startup", 10.0)
+        .with_window(100.ms())
+        .with_grace(1.s());
+
+    Runtime::new().run(|| {
+        watchdog.spawn();
+
+        // nothing happens until the system warms up
+        let progress = watchdog.clone();
+        async move {
+            1.s().sleep().await;
+            for _ in 0..100 {
+                10.ms().sleep().await;
+                progress.increment();
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    assert_eq!(watchdog.total(), 100);
+}
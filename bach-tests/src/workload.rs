@@ -0,0 +1,135 @@
+use bach::{
+    environment::default::Runtime,
+    ext::*,
+    time::Instant,
+    workload::{Arrivals, Constant, Pattern, Sinusoid, Trace, HOUR},
+};
+use std::sync::{Arc, Mutex};
+
+/// Counts the arrivals in each `bucket` for `buckets` buckets
+fn arrivals<P>(seed: u64, pattern: P, bucket: u64, buckets: usize) -> Vec<u64>
+where
+    P: 'static + Pattern + Send,
+{
+    let counts = Arc::new(Mutex::new(vec![0u64; buckets]));
+    let end = bucket.s() * buckets as u32;
+
+    Runtime::new().with_seed(seed).run(|| {
+        let counts = counts.clone();
+        async move {
+            let mut arrivals = Arrivals::new(pattern);
+            while let Some(at) = arrivals.tick().await {
+                let elapsed = at.elapsed_since_start();
+                if elapsed >= end {
+                    break;
+                }
+                assert_eq!(at, Instant::now());
+                let idx = (elapsed.as_secs() / bucket) as usize;
+                counts.lock().unwrap()[idx] += 1;
+            }
+        }
+        .primary()
+        .spawn();
+    });
+
+    let counts = counts.lock().unwrap().clone();
+    counts
+}
+
+#[test]
+fn constant() {
+    let counts = arrivals(1, Constant(10.0), 10, 6);
+    let total: u64 = counts.iter().sum();
+    assert!((540..=660).contains(&total), "{counts:?}");
+}
+
+#[test]
+fn sinusoid() {
+    // the busiest half of each minute is centered on 15s
+    let pattern = Sinusoid::new(10.0, 10.0, 60.s()).with_peak_at(15.s());
+    assert_eq!(pattern.rate(15.s()), 20.0);
+    assert!(pattern.rate(45.s()).abs() < 1e-9);
+
+    let counts = arrivals(2, pattern, 30, 4);
+    for minute in counts.chunks(2) {
+        // the expected counts are ~491 and ~109
+        assert!(minute[0] > minute[1] * 3, "{counts:?}");
+    }
+}
+
+#[test]
+fn trace() {
+    let trace = Trace::new([(10.s(), 4.0), (20.s(), 8.0)]);
+    assert_eq!(trace.rate(0.s()), 4.0);
+    assert_eq!(trace.rate(15.s()), 6.0);
+    assert_eq!(trace.rate(30.s()), 8.0);
+    assert_eq!(trace.peak(), 8.0);
+
+    let trace = trace.with_period(30.s());
+    // wraps around from the last point back to the first
+    assert_eq!(trace.rate(25.s()), 7.0);
+    assert_eq!(trace.rate(35.s()), 5.0);
+    assert_eq!(trace.rate(45.s()), 6.0);
+
+    let hourly = Trace::hourly([0.0, 2.0, 4.0]);
+    assert_eq!(hourly.rate(HOUR / 2), 1.0);
+    assert_eq!(hourly.rate(HOUR * 5 / 2), 2.0);
+    assert_eq!(hourly.rate(HOUR * 3), 0.0);
+}
+
+#[test]
+fn quiet_periods() {
+    // traffic only flows during the second and fourth windows
+    let trace = Trace::new([
+        (0.s(), 0.0),
+        (10.s(), 0.0),
+        (10.s(), 5.0),
+        (20.s(), 5.0),
+        (20.s(), 0.0),
+    ])
+    .with_period(20.s());
+
+    let counts = arrivals(3, trace, 10, 4);
+    assert_eq!(counts[0], 0, "{counts:?}");
+    assert_eq!(counts[2], 0, "{counts:?}");
+    assert!(counts[1] > 20 && counts[3] > 20, "{counts:?}");
+}
+
+#[test]
+fn no_arrivals() {
+    Runtime::new().run(|| {
+        async {
+            let mut arrivals = Arrivals::new(Constant(0.0));
+            assert_eq!(arrivals.tick().await, None);
+        }
+        .primary()
+        .spawn();
+    });
+}
+
+#[test]
+fn trace_end() {
+    // traffic stops for good after the first hour
+    let trace = Trace::new([(0.s(), 10.0), (HOUR, 0.0)]);
+    assert_eq!(trace.end(), Some(HOUR));
+
+    let mut rt = Runtime::new();
+    let count = rt.run(|| {
+        let count = Arc::new(Mutex::new(0u64));
+        let total = count.clone();
+        async move {
+            let mut arrivals = Arrivals::new(trace);
+            while arrivals.tick().await.is_some() {
+                *count.lock().unwrap() += 1;
+            }
+        }
+        .primary()
+        .spawn();
+        total
+    });
+
+    // the rate averages 5/s over the hour
+    let count = *count.lock().unwrap();
+    assert!((17_000..19_000).contains(&count), "{count}");
+    assert!(rt.elapsed() <= HOUR);
+}
@@ -0,0 +1,311 @@
+//! A controller that scales a pool of workers based on an observed metric
+//!
+//! The [`Pool`] periodically samples a metric, like the depth of a work queue or a recent
+//! latency percentile, and asks a policy how many workers should be running. Changes only take
+//! effect after a reaction delay, which models the time it takes to notice the change and
+//! provision a new instance, so autoscaling policies can be evaluated against realistic load
+//! before they're deployed.
+//!
+//! ```no_run
+//! # use bach::{autoscale, environment::default::Runtime, ext::*, sync::channel};
+//! # struct Job;
+//! # impl Job {
+//! #     async fn run(self) {}
+//! # }
+//! # Runtime::new().run(|| {
+//! let (sender, receiver) = channel::unbounded::<Job>();
+//!
+//! let pool = autoscale::Pool::builder(move |_id| {
+//!     let receiver = receiver.clone();
+//!     async move {
+//!         while let Ok(job) = receiver.recv().await {
+//!             job.run().await;
+//!         }
+//!     }
+//! })
+//! .with_max(16)
+//! .with_metric({
+//!     let sender = sender.clone();
+//!     move || sender.len() as f64
+//! })
+//! .with_target(10.0)
+//! .with_reaction_delay(30.s())
+//! .spawn();
+//! # });
+//! ```
+
+use crate::{
+    executor::JoinHandle,
+    time::{sleep, Duration, Instant},
+};
+use alloc::sync::Arc;
+use core::{fmt, future::Future};
+use std::sync::Mutex;
+
+/// Samples the metric that the pool is scaled on
+pub type Metric = Box<dyn FnMut() -> f64 + Send>;
+
+/// Returns the number of workers that should be running for an observation
+pub type Policy = Box<dyn FnMut(&Observation) -> usize + Send>;
+
+/// A sample of the metric along with the size of the pool at the time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Observation {
+    pub at: Instant,
+    pub metric: f64,
+    pub workers: usize,
+}
+
+/// A change in the number of running workers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub at: Instant,
+    pub workers: usize,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} worker(s)", self.at, self.workers)
+    }
+}
+
+pub struct Builder<W> {
+    worker: W,
+    min: usize,
+    max: usize,
+    interval: Duration,
+    reaction_delay: Duration,
+    metric: Option<Metric>,
+    policy: Option<Policy>,
+}
+
+impl<W, F> Builder<W>
+where
+    W: 'static + FnMut(u64) -> F + Send,
+    F: 'static + Future<Output = ()> + Send,
+{
+    /// Sets the minimum number of workers, which defaults to `1`
+    pub fn with_min(mut self, min: usize) -> Self {
+        self.min = min;
+        self
+    }
+
+    /// Sets the maximum number of workers, which defaults to `8`
+    pub fn with_max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets how often the metric is sampled, which defaults to 1s
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how long it takes for a decision to take effect, which defaults to zero
+    ///
+    /// No other decisions are made while a change is pending.
+    pub fn with_reaction_delay(mut self, delay: Duration) -> Self {
+        self.reaction_delay = delay;
+        self
+    }
+
+    /// Sets the metric that the pool is scaled on
+    pub fn with_metric<M>(mut self, metric: M) -> Self
+    where
+        M: 'static + FnMut() -> f64 + Send,
+    {
+        self.metric = Some(Box::new(metric));
+        self
+    }
+
+    /// Scales the pool so each worker accounts for `target` of the metric, rounding up
+    ///
+    /// For example, a target of `10.0` on a queue depth of `45` asks for 5 workers. This is the
+    /// default policy, with a target of `1.0`.
+    pub fn with_target(self, target: f64) -> Self {
+        assert!(target > 0.0, "target must be greater than 0");
+        self.with_policy(move |observation| (observation.metric / target).ceil() as usize)
+    }
+
+    /// Sets a custom policy
+    ///
+    /// The returned number of workers is clamped to the configured minimum and maximum.
+    pub fn with_policy<P>(mut self, policy: P) -> Self
+    where
+        P: 'static + FnMut(&Observation) -> usize + Send,
+    {
+        self.policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Starts the minimum number of workers and the controller in the current group
+    ///
+    /// # Panics
+    ///
+    /// Panics if no metric was provided or the minimum is greater than the maximum.
+    pub fn spawn(self) -> Pool {
+        let Self {
+            mut worker,
+            min,
+            max,
+            interval,
+            reaction_delay,
+            metric,
+            policy,
+        } = self;
+
+        let mut metric = metric.expect("a metric is required to scale the pool");
+        assert!(min <= max, "the minimum is greater than the maximum");
+        let mut policy = policy.unwrap_or_else(|| {
+            Box::new(|observation: &Observation| observation.metric.ceil() as usize)
+        });
+
+        let pool = Pool(Arc::new(Mutex::new(State {
+            worker: Box::new(move |id| Box::pin(worker(id)) as _),
+            next_id: 0,
+            workers: vec![],
+            history: vec![],
+            controller: None,
+        })));
+
+        pool.resize(min);
+
+        let controller = {
+            let pool = pool.clone();
+            async move {
+                loop {
+                    sleep(interval).await;
+
+                    let workers = pool.workers();
+                    let observation = Observation {
+                        at: Instant::now(),
+                        metric: metric(),
+                        workers,
+                    };
+                    let desired = policy(&observation).clamp(min, max);
+                    if desired == workers {
+                        continue;
+                    }
+
+                    if !reaction_delay.is_zero() {
+                        sleep(reaction_delay).await;
+                    }
+
+                    pool.resize(desired);
+                }
+            }
+        };
+
+        let controller = crate::task::daemon::spawn_named(controller, "autoscaler");
+        pool.0.lock().unwrap().controller = Some(controller);
+
+        pool
+    }
+}
+
+type Worker = Box<dyn FnMut(u64) -> core::pin::Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// A pool of workers that is resized by a controller task
+#[derive(Clone)]
+pub struct Pool(Arc<Mutex<State>>);
+
+struct State {
+    worker: Worker,
+    next_id: u64,
+    workers: Vec<JoinHandle<()>>,
+    history: Vec<Change>,
+    controller: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("workers", &self.workers())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pool {
+    /// Returns a builder for a pool that runs `worker` for each worker it starts
+    ///
+    /// Each worker is passed a unique id. Workers that are scaled down are cancelled at their
+    /// next await point, like a terminated instance, so any work they were in the middle of is
+    /// lost.
+    pub fn builder<W, F>(worker: W) -> Builder<W>
+    where
+        W: 'static + FnMut(u64) -> F + Send,
+        F: 'static + Future<Output = ()> + Send,
+    {
+        Builder {
+            worker,
+            min: 1,
+            max: 8,
+            interval: Duration::from_secs(1),
+            reaction_delay: Duration::ZERO,
+            metric: None,
+            policy: None,
+        }
+    }
+
+    /// Returns the number of running workers
+    pub fn workers(&self) -> usize {
+        self.0.lock().unwrap().workers.len()
+    }
+
+    /// Returns every change in the number of workers, including the initial workers
+    pub fn history(&self) -> Vec<Change> {
+        self.0.lock().unwrap().history.clone()
+    }
+
+    /// Stops the controller and cancels all of the workers
+    pub fn stop(&self) {
+        let mut state = self.0.lock().unwrap();
+        let controller = state.controller.take();
+        let workers = core::mem::take(&mut state.workers);
+        drop(state);
+
+        if let Some(controller) = controller {
+            controller.cancel();
+        }
+        for worker in workers {
+            worker.cancel();
+        }
+    }
+
+    fn resize(&self, desired: usize) {
+        let mut state = self.0.lock().unwrap();
+        let current = state.workers.len();
+
+        if desired > current {
+            count!("autoscale", (desired - current) as u64, "direction" = "up");
+        } else if desired < current {
+            count!(
+                "autoscale",
+                (current - desired) as u64,
+                "direction" = "down"
+            );
+        }
+
+        while state.workers.len() < desired {
+            let id = state.next_id;
+            state.next_id += 1;
+            let worker = (state.worker)(id);
+            let worker = crate::task::daemon::spawn_named(worker, format!("worker_{id}"));
+            state.workers.push(worker);
+        }
+
+        // the most recently started workers are the first to go
+        let cancelled = state.workers.split_off(desired);
+
+        state.history.push(Change {
+            at: Instant::now(),
+            workers: desired,
+        });
+        drop(state);
+
+        for worker in cancelled {
+            worker.cancel();
+        }
+    }
+}
@@ -1,14 +1,28 @@
 use crate::{define, ext::*};
+use pin_project_lite::pin_project;
 use std::{
-    collections::{BTreeMap, VecDeque},
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
     future::Future,
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     task::{Context, Poll, Waker},
 };
 
 define!(scope, Coop);
 
+/// Set once the truncation warning has been printed, since exhaustive checks create a new
+/// scheduler for every iteration
+static TRUNCATION_WARNED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static IS_ATOMIC: Cell<bool> = const { Cell::new(false) };
+}
+
 #[derive(Clone, Default)]
 pub struct Coop(Arc<Mutex<State>>);
 
@@ -17,17 +31,95 @@ struct State {
     id: u64,
     operations: BTreeMap<Operation, VecDeque<Task>>,
     moves: Vec<usize>,
+    /// Operations whose acquirers can run in any order without changing the outcome
+    commutative: BTreeSet<Operation>,
+    /// Links an operation to another that it conflicts with
+    conflicts: BTreeMap<Operation, Operation>,
+    /// The shuffle decisions that have been made so far
+    decisions: Vec<usize>,
+    /// Decisions to use instead of generating new ones
+    replay: VecDeque<usize>,
+    /// The maximum number of shuffle decisions made per round
+    max_depth: Option<usize>,
+    /// The number of rounds that were limited by `max_depth`
+    truncated: u64,
+    stats: Stats,
 }
 
 impl State {
+    /// Returns the representative operation for the set of conflicting operations
+    fn root(&self, mut operation: Operation) -> Operation {
+        while let Some(parent) = self.conflicts.get(&operation) {
+            operation = *parent;
+        }
+        operation
+    }
+
+    fn conflict(&mut self, a: Operation, b: Operation) {
+        let a = self.root(a);
+        let b = self.root(b);
+        if a != b {
+            self.conflicts.insert(a.max(b), a.min(b));
+        }
+    }
+
+    /// Removes an operation from its set of conflicting operations, keeping the rest of the set
+    /// linked together
+    fn unlink(&mut self, operation: Operation) {
+        let mut parent = self.conflicts.remove(&operation);
+        let children: Vec<_> = self
+            .conflicts
+            .iter()
+            .filter(|(_, p)| **p == operation)
+            .map(|(child, _)| *child)
+            .collect();
+
+        // parents are always lower than their children so the invariant is kept
+        for child in children {
+            if let Some(parent) = parent {
+                self.conflicts.insert(child, parent);
+            } else {
+                // the operation was the root so the lowest child takes its place
+                self.conflicts.remove(&child);
+                parent = Some(child);
+            }
+        }
+    }
+
     fn schedule(&mut self) -> usize {
         let mut woken_tasks = 0;
         let mut max_len = 0;
 
+        // Merge the tasks of conflicting operations so they are interleaved with each other.
+        // A merged set is only considered commutative if all of its operations are.
+        let mut queues = BTreeMap::<Operation, (bool, VecDeque<Task>)>::new();
+        for (operation, mut tasks) in core::mem::take(&mut self.operations) {
+            let is_commutative = self.commutative.contains(&operation);
+            let (queue_commutative, queue) = queues
+                .entry(self.root(operation))
+                .or_insert_with(|| (true, VecDeque::new()));
+            *queue_commutative &= is_commutative;
+            queue.append(&mut tasks);
+        }
+
         // First look at all of the pending tasks and find the `max_len`
-        for tasks in self.operations.values() {
+        for (is_commutative, tasks) in queues.values() {
             woken_tasks += tasks.len();
-            max_len = max_len.max(tasks.len());
+            // commutative operations aren't reordered so they don't contribute to the search
+            if !is_commutative {
+                max_len = max_len.max(tasks.len());
+
+                if tasks.len() > 1 {
+                    let operations: BTreeSet<_> = tasks.iter().map(|task| task.operation).collect();
+                    for operation in operations {
+                        self.stats.entry(operation).contended_rounds += 1;
+                    }
+                }
+            }
+        }
+
+        if woken_tasks > 0 {
+            self.stats.rounds += 1;
         }
 
         // Generate a set of interleavings from the `max_len` value
@@ -38,20 +130,47 @@ impl State {
         // See: https://en.wikipedia.org/wiki/Partial_order_reduction
         self.moves.clear();
         let max_dst = max_len.saturating_sub(1);
-        for src in 0..max_dst {
-            let dst = (src..=max_dst).any();
+
+        // Bound the number of decisions so large rounds don't explode the search space. Tasks
+        // past the bound keep their arrival order.
+        let mut depth = max_dst;
+        if let Some(max_depth) = self.max_depth {
+            if depth > max_depth {
+                count!("coop_truncated");
+                if !TRUNCATION_WARNED.swap(true, Ordering::Relaxed) {
+                    eprintln!(
+                        "warning: coop interleavings truncated from {depth} to {max_depth} decisions; exploration is incomplete"
+                    );
+                }
+                self.truncated += 1;
+                depth = max_depth;
+            }
+        }
+
+        for src in 0..depth {
+            let dst = if let Some(offset) = self.replay.pop_front() {
+                (src + offset).min(max_dst)
+            } else {
+                (src..=max_dst).any()
+            };
+            self.decisions.push(dst - src);
             self.moves.push(dst);
         }
 
-        self.operations.retain(|_operation, tasks| {
+        for (is_commutative, mut tasks) in queues.into_values() {
             for (src, dst) in self.moves.iter().copied().enumerate() {
                 // make sure the src applies to this set of tasks
-                if src == tasks.len() {
+                if is_commutative || src == tasks.len() {
                     break;
                 }
 
                 // if dst is in-bounds, then swap it with src. otherwise, leave it in place
                 if dst < tasks.len() {
+                    if src != dst {
+                        self.stats.reorders += 1;
+                        self.stats.entry(tasks[src].operation).reorders += 1;
+                        self.stats.entry(tasks[dst].operation).reorders += 1;
+                    }
                     tasks.swap(src, dst);
                 }
             }
@@ -60,10 +179,7 @@ impl State {
                 // dropping it wakes it up
                 drop(task)
             }
-
-            // clear out everything
-            false
-        });
+        }
 
         woken_tasks
     }
@@ -78,6 +194,37 @@ impl Coop {
         self.0.lock().unwrap().schedule()
     }
 
+    /// Limits the number of shuffle decisions made in each scheduling round
+    ///
+    /// This trades completeness for runtime when exploring large models exhaustively. A warning
+    /// is printed the first time a round is truncated in the process, and every truncated round
+    /// is counted by the `coop_truncated` metric.
+    pub fn set_max_depth(&self, max_depth: Option<usize>) {
+        self.0.lock().unwrap().max_depth = max_depth;
+    }
+
+    /// Returns the number of scheduling rounds that were limited by the max depth
+    pub fn truncated_rounds(&self) -> u64 {
+        self.0.lock().unwrap().truncated
+    }
+
+    /// Returns the statistics for the interleavings that have been explored so far
+    pub fn stats(&self) -> Stats {
+        self.0.lock().unwrap().stats.clone()
+    }
+
+    /// Returns the shuffle decisions that have been made so far
+    pub fn decisions(&self) -> Schedule {
+        Schedule(self.0.lock().unwrap().decisions.clone())
+    }
+
+    /// Forces the scheduler to follow the given decisions, rather than generating them
+    ///
+    /// Once the decisions are exhausted, the scheduler goes back to generating them.
+    pub fn replay(&self, schedule: &Schedule) {
+        self.0.lock().unwrap().replay = schedule.0.iter().copied().collect();
+    }
+
     fn resource(&mut self) -> Operation {
         let mut state = self.0.lock().unwrap();
         let id = state.id;
@@ -89,13 +236,14 @@ impl Coop {
         let handle = Arc::new(());
 
         let task = Task {
+            operation: *resource,
             waker: cx.waker().clone(),
             handle: handle.clone(),
         };
 
-        self.0
-            .lock()
-            .unwrap()
+        let mut state = self.0.lock().unwrap();
+        state.stats.entry(*resource).acquisitions += 1;
+        state
             .operations
             .entry(*resource)
             .or_default()
@@ -105,39 +253,296 @@ impl Coop {
     }
 }
 
+/// Returns the coop statistics for the current runtime
+///
+/// Returns `None` if coop scheduling isn't enabled. This is useful for meta-tests that check a
+/// critical operation is actually being explored.
+pub fn stats() -> Option<Stats> {
+    if cfg!(not(feature = "coop")) {
+        return None;
+    }
+
+    scope::try_borrow_with(|coop| coop.as_ref().map(Coop::stats))
+}
+
+/// A summary of the interleavings explored by the coop scheduler in a run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of scheduling rounds that woke at least one task
+    pub rounds: u64,
+    /// The number of times two tasks were swapped from their arrival order
+    pub reorders: u64,
+    /// The statistics for each operation that has been acquired
+    pub operations: BTreeMap<Operation, OperationStats>,
+}
+
+impl Stats {
+    /// Returns the statistics for the given operation
+    pub fn operation(&self, operation: &Operation) -> OperationStats {
+        self.operations.get(operation).copied().unwrap_or_default()
+    }
+
+    fn entry(&mut self, operation: Operation) -> &mut OperationStats {
+        self.operations.entry(operation).or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OperationStats {
+    /// The number of times a task acquired the operation
+    pub acquisitions: u64,
+    /// The number of rounds where multiple tasks were waiting on the operation
+    pub contended_rounds: u64,
+    /// The number of times a task waiting on the operation was moved from its arrival order
+    pub reorders: u64,
+}
+
+/// A compact, replayable record of the interleavings chosen by the coop scheduler
+///
+/// Each decision is encoded as a single alphanumeric character, or as a decimal number wrapped
+/// in braces for larger values.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schedule(Vec<usize>);
+
+const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+impl Schedule {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn from_decisions(decisions: Vec<usize>) -> Self {
+        Self(decisions)
+    }
+
+    pub(crate) fn decisions(&self) -> &[usize] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for decision in self.0.iter().copied() {
+            if let Some(digit) = DIGITS.get(decision) {
+                write!(f, "{}", *digit as char)?;
+            } else {
+                write!(f, "{{{decision}}}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseScheduleError(String);
+
+impl fmt::Display for ParseScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid coop schedule: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseScheduleError {}
+
+impl core::str::FromStr for Schedule {
+    type Err = ParseScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut decisions = vec![];
+        let mut chars = s.trim().chars();
+
+        while let Some(c) = chars.next() {
+            if c == '{' {
+                let value: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let value = value
+                    .parse()
+                    .map_err(|_| ParseScheduleError(format!("invalid decision {{{value}}}")))?;
+                decisions.push(value);
+                continue;
+            }
+
+            let value = c
+                .is_ascii()
+                .then(|| DIGITS.iter().position(|d| *d == c as u8))
+                .flatten()
+                .ok_or_else(|| ParseScheduleError(format!("unexpected character {c:?}")))?;
+            decisions.push(value);
+        }
+
+        Ok(Self(decisions))
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Operation(u64);
 
 impl Operation {
+    /// The operation returned when registering outside of a coop scheduler
+    const UNREGISTERED: Self = Self(u64::MAX);
+
     pub fn register() -> Self {
         if cfg!(not(feature = "coop")) {
-            return Self(u64::MAX);
+            return Self::UNREGISTERED;
         }
 
         scope::try_borrow_mut_with(|coop| coop.as_mut().map(|coop| coop.resource()))
-            .unwrap_or(Operation(u64::MAX))
+            .unwrap_or(Self::UNREGISTERED)
+    }
+
+    /// Declares that tasks acquiring this operation can proceed in any order
+    ///
+    /// The coop scheduler won't explore the different orderings of a commutative operation,
+    /// which prunes the search space for resources that don't depend on acquisition order.
+    pub fn commutative(self) -> Self {
+        if cfg!(feature = "coop") {
+            scope::try_borrow_mut_with(|coop| {
+                if let Some(coop) = coop {
+                    coop.0.lock().unwrap().commutative.insert(self);
+                }
+            });
+        }
+        self
     }
 
-    pub async fn acquire(&self) {
+    /// Declares that this operation conflicts with `other`
+    ///
+    /// Tasks acquiring either operation in the same round are interleaved with each other,
+    /// rather than only with the tasks acquiring the same operation.
+    ///
+    /// Operations registered outside of a coop scheduler all share the same identity, so
+    /// conflicts involving them are ignored rather than linking unrelated operations together.
+    pub fn conflicts_with(&self, other: &Operation) {
         if cfg!(not(feature = "coop")) {
             return;
         }
 
-        let Some(future) = core::future::poll_fn(|cx| {
-            Poll::Ready(scope::try_borrow_mut_with(|coop| {
-                coop.as_mut().map(|coop| coop.acquire(cx, self))
-            }))
-        })
-        .await
-        else {
+        if *self == Self::UNREGISTERED || *other == Self::UNREGISTERED {
             return;
-        };
+        }
+
+        scope::try_borrow_mut_with(|coop| {
+            if let Some(coop) = coop {
+                coop.0.lock().unwrap().conflict(*self, *other);
+            }
+        });
+    }
+
+    /// Removes any conflicts declared for this operation
+    ///
+    /// This should be called once the operation is no longer acquired so the conflicts don't
+    /// accumulate over the course of a run.
+    pub fn clear_conflicts(&self) {
+        if cfg!(not(feature = "coop")) || *self == Self::UNREGISTERED {
+            return;
+        }
+
+        scope::try_borrow_mut_with(|coop| {
+            if let Some(coop) = coop {
+                coop.0.lock().unwrap().unlink(*self);
+            }
+        });
+    }
+
+    pub fn acquire(&self) -> Acquire {
+        Acquire {
+            operation: *self,
+            waiting: None,
+        }
+    }
+}
+
+/// A future returned by [`Operation::acquire`]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Acquire {
+    operation: Operation,
+    /// Set once the task has been queued with the coop scheduler
+    waiting: Option<Waiting>,
+}
+
+impl Future for Acquire {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.waiting.is_none() {
+            if cfg!(not(feature = "coop")) {
+                return Poll::Ready(());
+            }
+
+            if IS_ATOMIC.with(Cell::get) {
+                count!("coop_atomic");
+                return Poll::Ready(());
+            }
+
+            let operation = self.operation;
+            let Some(waiting) = scope::try_borrow_mut_with(|coop| {
+                coop.as_mut().map(|coop| coop.acquire(cx, &operation))
+            }) else {
+                return Poll::Ready(());
+            };
+
+            self.waiting = Some(waiting);
+        }
+
+        let waiting = self.waiting.as_mut().unwrap();
+        Pin::new(waiting).poll(cx)
+    }
+}
+
+/// Runs `future` as a single step of the coop scheduler
+///
+/// Operations acquired inside the block don't yield to the scheduler, so the block is never
+/// interleaved with other tasks at those points. This models work that's atomic in production,
+/// like a single syscall, and keeps the scheduler from exploring orderings that can't happen.
+///
+/// ```no_run
+/// # use bach::coop;
+/// # struct File;
+/// # impl File {
+/// #     async fn write(&self, _bytes: &[u8]) {}
+/// # }
+/// # async fn example(file: File, header: Vec<u8>, body: Vec<u8>) {
+/// coop::atomic(async {
+///     file.write(&header).await;
+///     file.write(&body).await;
+/// })
+/// .await;
+/// # }
+/// ```
+///
+/// The block still yields if it waits on something that isn't ready, like an empty channel, and
+/// other tasks are free to run until it's woken.
+pub fn atomic<F: Future>(future: F) -> Atomic<F> {
+    Atomic { inner: future }
+}
+
+pin_project! {
+    #[must_use = "futures do nothing unless polled"]
+    pub struct Atomic<F> {
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F: Future> Future for Atomic<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        struct Reset(bool);
+
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                IS_ATOMIC.with(|is_atomic| is_atomic.set(self.0));
+            }
+        }
 
-        future.await;
+        // restore the previous value so nested blocks don't end the outer one
+        let _reset = Reset(IS_ATOMIC.with(|is_atomic| is_atomic.replace(true)));
+        self.project().inner.poll(cx)
     }
 }
 
 pub struct Task {
+    operation: Operation,
     waker: Waker,
     #[allow(dead_code)] // this just holds the `Waiting` future open
     handle: Arc<()>,
@@ -0,0 +1,268 @@
+//! Per-request deadlines and cancellation
+//!
+//! A [`Deadline`] can be attached to a task with [`DeadlineExt::with_deadline`], after which it is
+//! available through [`current`] while the task is polled. Since it is `Clone + Send`, it can also
+//! be embedded in messages so the receiving side can continue working under the same deadline.
+//!
+//! ```no_run
+//! # use bach::{
+//! #     deadline::{self, Deadline},
+//! #     ext::*,
+//! #     sync::channel,
+//! # };
+//! # type Error = Box<dyn std::error::Error>;
+//! # struct Request;
+//! # async fn handle(_request: Request) {}
+//! # async fn example(
+//! #     sender: channel::Sender<(Deadline, Request)>,
+//! #     receiver: channel::Receiver<(Deadline, Request)>,
+//! #     request: Request,
+//! # ) -> Result<(), Error> {
+//! let deadline = Deadline::after(100.ms());
+//!
+//! sender.send((deadline.clone(), request)).await?;
+//!
+//! // on the server
+//! let (deadline, request) = receiver.recv().await?;
+//! async move {
+//!     let response = deadline::current().unwrap().enforce(handle(request)).await?;
+//!     Ok::<_, deadline::Error>(response)
+//! }
+//! .with_deadline(deadline)
+//! .spawn();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    define,
+    time::{scheduler::Timer, sleep_until, Duration, Instant},
+};
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+use event_listener_strategy::event_listener::{Event, EventListener};
+use pin_project_lite::pin_project;
+
+define!(scope, Deadline);
+
+/// Returns the deadline attached to the current task, if any
+pub fn current() -> Option<Deadline> {
+    scope::try_borrow_with(|deadline| deadline.clone())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The deadline passed before the work completed
+    Expired,
+    /// The request was cancelled before the work completed
+    Cancelled,
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Expired => write!(f, "deadline expired"),
+            Self::Cancelled => write!(f, "request cancelled"),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Deadline {
+    at: Option<Instant>,
+    cancel: Arc<Cancel>,
+}
+
+#[derive(Default)]
+struct Cancel {
+    is_cancelled: AtomicBool,
+    event: Event,
+}
+
+impl fmt::Debug for Deadline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Deadline")
+            .field("at", &self.at)
+            .field("is_cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl Deadline {
+    /// Creates a context without a deadline that can only be cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn at(at: Instant) -> Self {
+        Self {
+            at: Some(at),
+            ..Default::default()
+        }
+    }
+
+    pub fn after(duration: Duration) -> Self {
+        Self::at(Instant::now() + duration)
+    }
+
+    /// Derives a deadline that expires after `duration` or the current deadline, whichever is
+    /// sooner
+    ///
+    /// The derived deadline shares the cancellation of its parent.
+    pub fn with_timeout(&self, duration: Duration) -> Self {
+        let at = Instant::now() + duration;
+        Self {
+            at: Some(self.at.map_or(at, |current| current.min(at))),
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    pub fn instant(&self) -> Option<Instant> {
+        self.at
+    }
+
+    /// Returns the time left before the deadline expires, or `None` if there isn't one
+    pub fn remaining(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.at.map(|at| {
+            at.elapsed_since_start()
+                .saturating_sub(now.elapsed_since_start())
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.at.is_some_and(|at| at.has_elapsed())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled.load(Ordering::Acquire)
+    }
+
+    /// Cancels the request, along with any deadlines derived from it
+    pub fn cancel(&self) {
+        if !self.cancel.is_cancelled.swap(true, Ordering::AcqRel) {
+            count!("deadline_cancelled");
+            self.cancel.event.notify(usize::MAX);
+        }
+    }
+
+    /// Returns an error if the deadline has expired or the request was cancelled
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        if self.is_expired() {
+            return Err(Error::Expired);
+        }
+        Ok(())
+    }
+
+    /// Runs `future` under the deadline, returning an error if it expires or is cancelled first
+    pub fn enforce<F: Future>(&self, future: F) -> Enforce<F> {
+        Enforce {
+            inner: future,
+            deadline: self.clone(),
+            timer: None,
+            listener: None,
+        }
+    }
+}
+
+pub trait DeadlineExt: Sized {
+    /// Attaches the deadline to the future, making it available through [`current`]
+    ///
+    /// The deadline isn't enforced; see [`Deadline::enforce`] for that.
+    fn with_deadline(self, deadline: Deadline) -> WithDeadline<Self>;
+}
+
+impl<F: Future> DeadlineExt for F {
+    fn with_deadline(self, deadline: Deadline) -> WithDeadline<Self> {
+        WithDeadline {
+            inner: self,
+            deadline,
+        }
+    }
+}
+
+pin_project! {
+    #[must_use = "futures do nothing unless polled"]
+    pub struct WithDeadline<F> {
+        #[pin]
+        inner: F,
+        deadline: Deadline,
+    }
+}
+
+impl<F: Future> Future for WithDeadline<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        scope::with(this.deadline.clone(), || this.inner.poll(cx))
+    }
+}
+
+pin_project! {
+    #[must_use = "futures do nothing unless polled"]
+    pub struct Enforce<F> {
+        #[pin]
+        inner: F,
+        deadline: Deadline,
+        timer: Option<Timer>,
+        listener: Option<EventListener>,
+    }
+}
+
+impl<F: Future> Future for Enforce<F> {
+    type Output = Result<F::Output, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if let Err(err) = this.deadline.check() {
+            if err == Error::Expired {
+                count!("deadline_expired");
+            }
+            return Poll::Ready(Err(err));
+        }
+
+        if let Poll::Ready(output) = this.inner.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        loop {
+            let listener = this
+                .listener
+                .get_or_insert_with(|| this.deadline.cancel.event.listen());
+
+            // check again in case we were cancelled before listening
+            if this.deadline.is_cancelled() {
+                return Poll::Ready(Err(Error::Cancelled));
+            }
+
+            if Pin::new(listener).poll(cx).is_pending() {
+                break;
+            }
+
+            *this.listener = None;
+        }
+
+        if let Some(at) = this.deadline.at {
+            let timer = this.timer.get_or_insert_with(|| sleep_until(at));
+            if Pin::new(timer).poll(cx).is_ready() {
+                count!("deadline_expired");
+                return Poll::Ready(Err(Error::Expired));
+            }
+        }
+
+        Poll::Pending
+    }
+}
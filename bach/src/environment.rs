@@ -1,7 +1,10 @@
 use core::task::Poll;
 
 pub mod default;
+pub mod federation;
 mod macrostep;
+pub mod realtime;
+pub mod replay;
 pub use macrostep::Macrostep;
 
 pub trait Environment {
@@ -1,11 +1,16 @@
 use crate::{coop::Coop, environment::Environment as _, executor, rand, time::scheduler};
-use core::task::Poll;
-use std::time::Duration;
+use core::{cell::Cell, task::Poll};
+use std::{path::PathBuf, time::Duration};
 
 use super::{Macrostep, Runnable};
 
 pub struct Runtime {
     inner: executor::Executor<Environment>,
+    orphan_check: bool,
+    /// The seed passed to [`Runtime::with_seed`], if the RNG was created from one
+    seed: Option<u64>,
+    /// Where to record the replay if the runtime is dropped because of a panic
+    replay_file: Option<PathBuf>,
 }
 
 impl Default for Runtime {
@@ -19,7 +24,14 @@ impl Default for Runtime {
             coop_enabled: false,
         });
 
-        Self { inner }
+        start_run();
+
+        Self {
+            inner,
+            orphan_check: false,
+            seed: Some(0),
+            replay_file: None,
+        }
     }
 }
 
@@ -29,11 +41,14 @@ impl Runtime {
     }
 
     pub fn with_seed(self, seed: u64) -> Self {
-        self.with_rand(Some(rand::Scope::new(seed)))
+        let mut runtime = self.with_rand(Some(rand::Scope::new(seed)));
+        runtime.seed = Some(seed);
+        runtime
     }
 
     pub fn with_rand(mut self, rand: Option<rand::Scope>) -> Self {
         self.inner.environment().rand = rand;
+        self.seed = None;
         self
     }
 
@@ -42,14 +57,191 @@ impl Runtime {
         self
     }
 
+    /// Coalesces timer expirations within `granularity` to fire on the same tick
+    ///
+    /// See [`scheduler::Scheduler::set_coalescing`].
+    pub fn with_timer_coalescing(mut self, granularity: Duration) -> Self {
+        self.inner.environment().time.set_coalescing(granularity);
+        self
+    }
+
+    /// Limits the number of interleaving decisions the coop scheduler makes per round
+    ///
+    /// See [`crate::coop::Coop::set_max_depth`].
+    pub fn with_coop_max_depth(mut self, max_depth: usize) -> Self {
+        self.inner.environment().coop.set_max_depth(Some(max_depth));
+        self
+    }
+
+    /// Enables coop scheduling and forces it to follow a schedule printed by a failed run
+    ///
+    /// # Panics
+    ///
+    /// Panics if `schedule` is not a valid schedule string.
+    pub fn with_schedule(mut self, schedule: &str) -> Self {
+        let schedule: crate::coop::Schedule = schedule.parse().unwrap();
+        self.inner.environment().coop.replay(&schedule);
+        self.with_coop(true)
+    }
+
+    /// Records the run to a [replay file](super::replay) at `path` if it fails
+    ///
+    /// The file is only written when the runtime is dropped because of a panic, so passing runs
+    /// don't leave files behind. In a `bolero` loop, each iteration records to the same path and
+    /// the file ends up with the last iteration that failed. The seed and coop settings need to
+    /// be configured before calling this.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runtime was configured with a custom RNG through [`Self::with_rand`].
+    pub fn with_replay_recording<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        assert!(
+            self.seed.is_some() || self.inner.environment().rand.is_none(),
+            "runs with a custom RNG can't be recorded to a replay file"
+        );
+        self.replay_file = Some(path.into());
+        self
+    }
+
+    /// Replays the run recorded to the [replay file](super::replay) at `path`
+    ///
+    /// The seed and coop settings from the file replace the runtime's.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file can't be read or isn't a valid replay.
+    pub fn with_replay<P: AsRef<std::path::Path>>(self, path: P) -> Self {
+        let path = path.as_ref();
+        let replay = super::replay::Replay::read(path)
+            .unwrap_or_else(|err| panic!("invalid replay file {}: {err}", path.display()));
+
+        let mut runtime = match replay.seed {
+            Some(seed) => self.with_seed(seed),
+            None => {
+                let mut runtime = self.with_rand(None);
+                runtime.inner.environment().coop.replay(&replay.schedule);
+                runtime
+            }
+        };
+        runtime = runtime.with_coop(replay.coop);
+        runtime.replay_file = None;
+        runtime
+    }
+
+    /// Returns the statistics for the interleavings the coop scheduler has explored so far
+    pub fn coop_stats(&mut self) -> crate::coop::Stats {
+        self.inner.environment().coop.stats()
+    }
+
+    /// Returns the coop scheduling decisions made so far
+    ///
+    /// The returned schedule can be passed to [`Self::with_schedule`] to replay the run.
+    pub fn schedule(&mut self) -> crate::coop::Schedule {
+        self.inner.environment().coop.decisions()
+    }
+
+    /// Detects tasks that are woken from threads other than the one driving the runtime
+    ///
+    /// Wakes from other threads, like a real I/O reactor or a host thread, race with the
+    /// simulation and make runs nondeterministic. The woken task is held until the next
+    /// macrostep boundary and each wake is printed to stderr at that point, along with the
+    /// backtrace of the thread that issued it. The reports can also be collected with
+    /// [`Self::take_foreign_wakes`].
+    pub fn with_wake_audit(self, enabled: bool) -> Self {
+        self.inner.handle().set_wake_audit(enabled);
+        self
+    }
+
+    /// Tracks notifications that didn't reach any waiters and reports the tasks that were left
+    /// waiting for them when the runtime stalls
+    ///
+    /// See [`crate::sync::lost_wakeup`].
+    pub fn with_lost_wakeup_detection(self, enabled: bool) -> Self {
+        self.inner.handle().set_lost_wakeup_detection(enabled);
+        self
+    }
+
+    /// Returns the tasks that are waiting for a notification that was already sent
+    ///
+    /// This is only populated when [`Runtime::with_lost_wakeup_detection`] is enabled.
+    pub fn lost_wakeups(&mut self) -> Vec<crate::sync::lost_wakeup::LostWakeup> {
+        self.inner.handle().lost_wakeups()
+    }
+
+    /// Delays each task wakeup by an extra amount of simulated time returned by `noise`
+    ///
+    /// This models scheduling jitter from the OS or noisy neighbors, which is useful for
+    /// validating timeout margins. `noise` is called inside of the simulation so it can use
+    /// [`crate::rand`] to draw from a seeded distribution.
+    pub fn with_scheduler_noise<N>(self, noise: N) -> Self
+    where
+        N: 'static + Fn() -> Duration + Send + Sync,
+    {
+        self.inner
+            .handle()
+            .set_scheduler_noise(Some(alloc::sync::Arc::new(noise)));
+        self
+    }
+
+    /// Panics at the end of [`Self::run`] if any non-primary tasks are still pending
+    ///
+    /// Background loops that are supposed to run for the whole simulation, like heartbeats or
+    /// servers, need to be spawned with [`DaemonExt::daemon`](crate::ext::DaemonExt::daemon).
+    /// Anything else that is left over was likely leaked and is keeping resources alive.
+    pub fn with_orphan_check(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.inner.handle().track_orphans();
+        }
+        self.orphan_check = enabled;
+        self
+    }
+
     pub fn run<F: FnOnce() -> R, R>(&mut self, f: F) -> R {
-        let result = self.inner.environment().enter(f);
+        let result = self.enter(f);
 
         self.inner.block_on_primary();
 
+        if self.orphan_check {
+            self.check_orphans();
+        }
+
         result
     }
 
+    /// Calls `f` inside of the simulation without driving it
+    ///
+    /// Tasks spawned by `f` make progress the next time the runtime is driven, like with
+    /// [`Self::advance_to`].
+    pub fn enter<F: FnOnce() -> R, R>(&mut self, f: F) -> R {
+        self.inner.environment().enter(f)
+    }
+
+    pub(crate) fn has_primary(&self) -> bool {
+        self.inner.handle().primary_count() > 0
+    }
+
+    /// Returns `true` if there aren't any tasks that are ready to run
+    pub(crate) fn is_idle(&self) -> bool {
+        self.inner.is_idle()
+    }
+
+    fn check_orphans(&mut self) {
+        let orphans = self.inner.handle().orphans();
+        if orphans.is_empty() {
+            return;
+        }
+
+        let mut message = format!(
+            "{} task(s) were still pending after the primary tasks completed",
+            orphans.len()
+        );
+        for task in &orphans {
+            message.push_str(&format!("\n  {task}"));
+        }
+        message.push_str("\nspawn long-running background tasks with `.daemon()`");
+        panic!("{message}");
+    }
+
     pub fn block_on<F>(&mut self, f: F) -> F::Output
     where
         F: 'static + Send + core::future::Future,
@@ -64,11 +256,124 @@ impl Runtime {
             .time
             .enter(|| crate::time::Instant::now().elapsed_since_start())
     }
+
+    /// Returns the time of the next pending timer, or `None` if there aren't any
+    ///
+    /// See [`scheduler::Scheduler::next_expiration`].
+    pub fn next_deadline(&mut self) -> Option<crate::time::Instant> {
+        self.inner.environment().time.next_expiration()
+    }
+
+    /// Runs the simulation until the clock reaches `target`
+    ///
+    /// This allows embedding code to step through time explicitly rather than running until the
+    /// primary tasks complete. The clock is stepped from one timer to the next and never past
+    /// `target`, even when timers are coalesced. Tasks that are woken exactly at `target` are
+    /// polled once before this returns, but the tasks they wake may not have been.
+    pub fn advance_to(&mut self, target: crate::time::Instant) {
+        let stop = self.inner.environment().time.stop_at(target);
+
+        while !stop.take_expired() {
+            self.inner.macrostep();
+        }
+
+        // poll the tasks that were woken along with the stop without advancing the clock again
+        if !self.inner.is_idle() {
+            self.inner.macrostep();
+        }
+    }
+
+    /// Returns the wakes issued from foreign threads since the last call
+    ///
+    /// This is only populated when [`Runtime::with_wake_audit`] is enabled.
+    pub fn take_foreign_wakes(&mut self) -> Vec<executor::ForeignWake> {
+        self.inner.handle().take_foreign_wakes()
+    }
+
+    /// Returns a sender for events from a thread outside of the simulation
+    ///
+    /// Events are delivered to the receiver at the next macrostep boundary. See
+    /// [`executor::ingress`] for the order they're delivered in.
+    pub fn ingress<T>(&mut self) -> (executor::Ingress<T>, crate::sync::channel::Receiver<T>)
+    where
+        T: 'static + Send + Sync,
+    {
+        self.inner.handle().ingress()
+    }
+
+    /// Returns a handle that can schedule events into the simulation from other threads
+    pub fn time_driver(&mut self) -> crate::time::TimeDriver {
+        let env = self.inner.environment();
+        crate::time::TimeDriver::new(env.handle.clone(), env.time.handle())
+    }
 }
 
 impl Drop for Runtime {
     fn drop(&mut self) {
+        let env = self.inner.environment();
+        if std::thread::panicking() && cfg!(feature = "coop") && env.coop_enabled {
+            let schedule = env.coop.decisions();
+            if !schedule.is_empty() {
+                eprintln!("coop schedule: \"{schedule}\" (replay with `Runtime::with_schedule`)");
+            }
+        }
+
+        if let Some(path) = self.replay_file.take().filter(|_| std::thread::panicking()) {
+            let replay = super::replay::Replay {
+                seed: self.seed,
+                coop: env.coop_enabled,
+                schedule: env.coop.decisions(),
+            };
+            match replay.write(&path) {
+                Ok(()) => eprintln!("replay file written to {}", path.display()),
+                Err(err) => eprintln!("could not write replay file {}: {err}", path.display()),
+            }
+        }
+
         self.inner.close();
+
+        finish_run();
+    }
+}
+
+thread_local! {
+    static RUNTIMES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Called when a runtime is created
+///
+/// Runtimes that are alive on the same thread at the same time, like the members of a
+/// [`Federation`](super::federation::Federation), share a single run, so only the first one
+/// resets the per-run state.
+fn start_run() {
+    let runtimes = RUNTIMES.with(|runtimes| runtimes.replace(runtimes.get() + 1));
+    if runtimes == 0 {
+        crate::output::start_run();
+        crate::profile::start_run();
+
+        #[cfg(feature = "memory")]
+        crate::memory::start_run();
+
+        #[cfg(feature = "metrics")]
+        crate::testing::metrics::start_run();
+    }
+}
+
+/// Called when a runtime is dropped, which finishes the run once the last runtime is gone
+fn finish_run() {
+    let runtimes = RUNTIMES.with(|runtimes| {
+        let remaining = runtimes.get().saturating_sub(1);
+        runtimes.set(remaining);
+        remaining
+    });
+    if runtimes == 0 {
+        crate::profile::finish_run();
+
+        #[cfg(feature = "memory")]
+        crate::memory::finish_run();
+
+        #[cfg(feature = "metrics")]
+        crate::testing::metrics::finish_run();
     }
 }
 
@@ -174,7 +479,11 @@ impl super::Environment for Environment {
         // enough number that we won't get false positives but low enough that the number of
         // loops stays within reasonable ranges.
         if self.stalled_iterations > 100 {
-            panic!("the runtime stalled after 100 iterations");
+            let mut message = "the runtime stalled after 100 iterations".to_string();
+            for lost in self.handle.lost_wakeups() {
+                message.push_str(&format!("\n  lost wakeup: {lost}"));
+            }
+            panic!("{message}");
         }
 
         while let Some(ticks) = self.time.advance() {
@@ -0,0 +1,264 @@
+//! Multiple runtimes stepped in lockstep
+//!
+//! Very large scenarios are easier to structure as separate simulations, like one [`Runtime`]
+//! modeling a fleet of clients and another modeling a server cluster. A [`Federation`] owns the
+//! runtimes and keeps their clocks in sync, while bridges carry messages between them with their
+//! own latency.
+//!
+//! ```no_run
+//! # use bach::{
+//! #     environment::{
+//! #         default::Runtime,
+//! #         federation::{Federation, Sender},
+//! #     },
+//! #     ext::*,
+//! #     sync::channel,
+//! # };
+//! # struct Request;
+//! # async fn server(_incoming: channel::Receiver<Request>) {}
+//! # async fn client(_requests: Sender<Request>) {}
+//! let mut federation = Federation::new();
+//! let clients = federation.add(Runtime::new().with_seed(1));
+//! let servers = federation.add(Runtime::new().with_seed(2));
+//!
+//! let (requests, incoming) = federation.bridge::<Request>(servers, 5.ms()..=10.ms());
+//!
+//! federation.enter(servers, || server(incoming).daemon().spawn());
+//! federation.enter(clients, || client(requests).primary().spawn());
+//!
+//! federation.run();
+//! ```
+//!
+//! The runtimes are advanced in rounds no longer than the smallest bridge latency. A message sent
+//! during a round is always delivered after the round ends, so every runtime sees the messages
+//! from the others at the same simulated time that they would in a single runtime.
+
+use super::default::Runtime;
+use crate::{
+    rand::*,
+    sync::channel,
+    time::{
+        resolution::{duration_to_ticks, ticks_to_duration},
+        Duration, Instant, TimeDriver,
+    },
+};
+use alloc::sync::Arc;
+use core::ops::RangeInclusive;
+use std::{collections::VecDeque, sync::Mutex};
+
+/// Identifies a runtime in a [`Federation`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id(usize);
+
+#[derive(Default)]
+pub struct Federation {
+    // bridges are dropped before the runtimes they deliver into
+    bridges: Vec<Box<dyn Deliver>>,
+    runtimes: Vec<Runtime>,
+    lookahead: Option<Duration>,
+}
+
+impl Federation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a runtime to the federation
+    ///
+    /// The runtime's clock should not have been advanced before it's added.
+    pub fn add(&mut self, runtime: Runtime) -> Id {
+        let id = Id(self.runtimes.len());
+        self.runtimes.push(runtime);
+        id
+    }
+
+    /// Returns the runtime for `id`
+    pub fn runtime(&mut self, id: Id) -> &mut Runtime {
+        &mut self.runtimes[id.0]
+    }
+
+    /// Calls `f` inside of the runtime for `id`, which is used to spawn its tasks
+    pub fn enter<F: FnOnce() -> R, R>(&mut self, id: Id, f: F) -> R {
+        self.runtime(id).enter(f)
+    }
+
+    /// Creates a bridge that delivers messages into the runtime for `to`
+    ///
+    /// Each message is delayed by a latency picked from `latency` with the sending runtime's
+    /// RNG. The shortest latency of all of the bridges limits how far the runtimes can be
+    /// advanced in each round, so it should be as large as the scenario allows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of `latency` is shorter than the timer resolution.
+    pub fn bridge<T>(
+        &mut self,
+        to: Id,
+        latency: RangeInclusive<Duration>,
+    ) -> (Sender<T>, channel::Receiver<T>)
+    where
+        T: 'static + Send + Sync,
+    {
+        let min_latency = *latency.start();
+        assert!(
+            duration_to_ticks(min_latency) > 0,
+            "bridge latency must be at least {:?}",
+            ticks_to_duration(1)
+        );
+
+        self.lookahead = Some(match self.lookahead {
+            Some(lookahead) => lookahead.min(min_latency),
+            None => min_latency,
+        });
+
+        let (inbox, receiver) = channel::unbounded();
+        let outbox = Arc::new(Mutex::new(VecDeque::new()));
+
+        self.bridges.push(Box::new(Bridge {
+            to,
+            outbox: outbox.clone(),
+            inbox,
+        }));
+
+        let sender = Sender { latency, outbox };
+
+        (sender, receiver)
+    }
+
+    /// Runs the runtimes until all of their primary tasks complete
+    ///
+    /// # Panics
+    ///
+    /// Panics if primary tasks are still pending but none of the runtimes have any timers or
+    /// messages in flight.
+    pub fn run(&mut self) {
+        while self.runtimes.iter().any(|rt| rt.has_primary()) {
+            assert!(
+                self.round(None),
+                "the federation stalled with primary tasks still pending"
+            );
+        }
+    }
+
+    /// Runs the runtimes until their clocks reach `target`
+    pub fn advance_to(&mut self, target: Instant) {
+        while self.now().is_some_and(|now| now < target) {
+            self.round(Some(target));
+        }
+    }
+
+    /// Returns the current time of the runtimes, or `None` if the federation is empty
+    pub fn now(&mut self) -> Option<Instant> {
+        let runtime = self.runtimes.first_mut()?;
+        Some(runtime.time_driver().now())
+    }
+
+    /// Advances all of the runtimes by a single round and delivers the messages that were sent
+    ///
+    /// Returns `false` if none of the runtimes had anything to do.
+    fn round(&mut self, limit: Option<Instant>) -> bool {
+        let Some(now) = self.now() else {
+            return false;
+        };
+
+        let is_idle = self.runtimes.iter().all(|rt| rt.is_idle());
+        let earliest = self
+            .runtimes
+            .iter_mut()
+            .filter_map(|rt| rt.next_deadline())
+            .min();
+
+        let mut target = match earliest {
+            Some(earliest) => {
+                // nothing happens before the earliest timer, so an idle round can start there.
+                // Tasks that are ready send messages right away, which arrive a lookahead later.
+                let start = if is_idle { earliest.max(now) } else { now };
+                let target = start + self.lookahead.unwrap_or(Duration::ZERO);
+                target.max(now + ticks_to_duration(1))
+            }
+            // run the tasks that are ready without advancing the clock
+            None if !is_idle => now,
+            // nothing can happen until the limit, so the runtimes can jump straight to it
+            None => match limit {
+                Some(limit) => limit,
+                None => return false,
+            },
+        };
+
+        if let Some(limit) = limit {
+            target = target.min(limit);
+        }
+
+        for runtime in &mut self.runtimes {
+            runtime.advance_to(target);
+        }
+
+        for bridge in &mut self.bridges {
+            let to = bridge.to();
+            bridge.deliver(self.runtimes[to.0].time_driver());
+        }
+
+        true
+    }
+}
+
+trait Deliver {
+    fn to(&self) -> Id;
+
+    fn deliver(&mut self, driver: TimeDriver);
+}
+
+struct Bridge<T> {
+    to: Id,
+    outbox: Arc<Mutex<VecDeque<(Instant, T)>>>,
+    inbox: channel::Sender<T>,
+}
+
+impl<T: 'static + Send + Sync> Deliver for Bridge<T> {
+    fn to(&self) -> Id {
+        self.to
+    }
+
+    fn deliver(&mut self, driver: TimeDriver) {
+        let messages = core::mem::take(&mut *self.outbox.lock().unwrap());
+        for (target, message) in messages {
+            let inbox = self.inbox.clone();
+            driver.post_at(target, move || {
+                // the receiver may have been dropped
+                let _ = inbox.try_push(message);
+            });
+        }
+    }
+}
+
+/// The sending side of a bridge between two runtimes
+pub struct Sender<T> {
+    latency: RangeInclusive<Duration>,
+    outbox: Arc<Mutex<VecDeque<(Instant, T)>>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            latency: self.latency.clone(),
+            outbox: self.outbox.clone(),
+        }
+    }
+}
+
+impl<T: 'static + Send + Sync> Sender<T> {
+    /// Sends `message` to the receiving runtime, where it arrives after the bridge's latency
+    ///
+    /// This must be called from inside of one of the federation's runtimes. Messages that arrive
+    /// after the receiver was dropped are discarded.
+    pub fn send(&self, message: T) {
+        let start = duration_to_ticks(*self.latency.start());
+        let end = duration_to_ticks(*self.latency.end());
+        let latency = ticks_to_duration((start..=end.max(start)).any());
+
+        self.outbox
+            .lock()
+            .unwrap()
+            .push_back((Instant::now() + latency, message));
+    }
+}
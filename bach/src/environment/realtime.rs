@@ -0,0 +1,137 @@
+//! Pacing a simulation against the wall clock
+//!
+//! Simulations normally skip straight to the next timer. [`Realtime`] instead advances the
+//! simulated clock in step with the host's clock, optionally sped up or slowed down, so simulated
+//! tasks can interact with real services outside of the simulation. Requests are handed to host
+//! threads with [`Receiver::blocking_recv`](crate::sync::channel::Receiver::blocking_recv) and
+//! their responses come back through an [`Ingress`](crate::executor::Ingress).
+//!
+//! ```no_run
+//! # use bach::{
+//! #     environment::{default::Runtime, realtime::Realtime},
+//! #     ext::*,
+//! #     sync::channel,
+//! # };
+//! # struct Client;
+//! # impl Client {
+//! #     fn call(&self, request: u64) -> u64 {
+//! #         request
+//! #     }
+//! # }
+//! # async fn fleet(_requests: channel::Sender<u64>, _replies: channel::Receiver<u64>) {}
+//! # let client = Client;
+//! let mut rt = Runtime::new();
+//! let (responses, replies) = rt.ingress();
+//! let (requests, outgoing) = channel::unbounded();
+//!
+//! // forward requests to a real server from a host thread
+//! std::thread::spawn(move || {
+//!     while let Ok(request) = outgoing.blocking_recv() {
+//!         responses.send(client.call(request));
+//!     }
+//! });
+//!
+//! rt.enter(|| fleet(requests, replies).primary().spawn());
+//!
+//! Realtime::new(&mut rt).with_scale(10.0).run();
+//! ```
+//!
+//! This mode is experimental. Anything that touches the host clock or threads gives up the
+//! determinism of a regular run, so it's meant for bridging simulated workloads with integration
+//! tests rather than for reproducing bugs.
+
+use super::default::Runtime;
+use crate::time::Instant;
+use core::time::Duration;
+
+/// Drives a [`Runtime`] so simulated time follows the wall clock
+pub struct Realtime<'a> {
+    runtime: &'a mut Runtime,
+    scale: f64,
+    poll_interval: Duration,
+}
+
+impl<'a> Realtime<'a> {
+    pub fn new(runtime: &'a mut Runtime) -> Self {
+        Self {
+            runtime,
+            scale: 1.0,
+            poll_interval: Duration::from_millis(1),
+        }
+    }
+
+    /// Sets how many seconds of simulated time pass for each second of wall-clock time, which
+    /// defaults to `1.0`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` isn't positive.
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        assert!(
+            scale > 0.0 && scale.is_finite(),
+            "scale must be greater than 0"
+        );
+        self.scale = scale;
+        self
+    }
+
+    /// Sets the longest amount of wall-clock time between checks for events from outside of the
+    /// simulation, which defaults to 1ms
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Returns the current simulated time
+    pub fn now(&mut self) -> Instant {
+        self.runtime.time_driver().now()
+    }
+
+    /// Runs the simulation until all of its primary tasks complete
+    pub fn run(&mut self) {
+        self.run_while(None, |runtime| runtime.has_primary());
+    }
+
+    /// Runs the simulation for `duration` of simulated time
+    pub fn run_for(&mut self, duration: Duration) {
+        let end = self.runtime.time_driver().now() + duration;
+        self.run_while(Some(end), |runtime| runtime.time_driver().now() < end);
+    }
+
+    fn run_while<F>(&mut self, limit: Option<Instant>, mut condition: F)
+    where
+        F: FnMut(&mut Runtime) -> bool,
+    {
+        let wall_start = std::time::Instant::now();
+        let sim_start = self.runtime.time_driver().now();
+
+        while condition(self.runtime) {
+            let mut target = sim_start + wall_start.elapsed().mul_f64(self.scale);
+            // stop at each timer so the condition is checked right after it fires
+            if let Some(deadline) = self.runtime.next_deadline() {
+                target = target.min(deadline);
+            }
+            if let Some(limit) = limit {
+                target = target.min(limit);
+            }
+            let now = self.runtime.time_driver().now();
+            // the clock never moves backwards, but ready tasks still need to run
+            self.runtime.advance_to(target.max(now));
+
+            // sleep until the next timer, waking up periodically for outside events
+            let now = self.runtime.time_driver().now();
+            let mut wait = self.poll_interval;
+            if let Some(deadline) = self.runtime.next_deadline() {
+                wait = wait.min(self.wall_time(deadline.saturating_duration_since(now)));
+            }
+            if !wait.is_zero() && self.runtime.is_idle() {
+                std::thread::sleep(wait);
+            }
+        }
+    }
+
+    /// Converts an amount of simulated time to wall-clock time
+    fn wall_time(&self, duration: Duration) -> Duration {
+        duration.div_f64(self.scale)
+    }
+}
@@ -0,0 +1,135 @@
+//! Replay files for reproducing a run
+//!
+//! A run of the default runtime is fully determined by its seed and the decisions made by the
+//! coop scheduler; timers, wakeups, and [`crate::rand`] all follow from those. A [`Replay`]
+//! captures both in a small binary file. Failing runs are recorded with
+//! [`Runtime::with_replay_recording`](super::default::Runtime::with_replay_recording), and the
+//! file can be attached to a bug report and passed to
+//! [`Runtime::with_replay`](super::default::Runtime::with_replay) to re-run the same execution.
+//!
+//! Seeded runs regenerate the same coop decisions from the seed, so the recorded decisions are
+//! only forced on runs without an RNG, like the ones driven by `bolero`.
+//!
+//! The file starts with the `BACH` magic and a version byte, followed by a flags byte, the seed
+//! as a little-endian `u64`, and the number of coop decisions and the decisions themselves as
+//! LEB128 varints.
+
+use crate::coop::Schedule;
+use std::{fs, io, path::Path};
+
+const MAGIC: &[u8; 4] = b"BACH";
+const VERSION: u8 = 1;
+const COOP: u8 = 1;
+const SEEDED: u8 = 2;
+
+/// The information needed to reproduce a run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Replay {
+    /// The seed of the run, or `None` if it ran without an RNG
+    pub seed: Option<u64>,
+    /// Whether coop scheduling was enabled
+    pub coop: bool,
+    pub schedule: Schedule,
+}
+
+impl Replay {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let decisions = self.schedule.decisions();
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 10 + decisions.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+
+        let mut flags = 0;
+        if self.coop {
+            flags |= COOP;
+        }
+        if self.seed.is_some() {
+            flags |= SEEDED;
+        }
+        bytes.push(flags);
+        bytes.extend_from_slice(&self.seed.unwrap_or(0).to_le_bytes());
+        write_varint(&mut bytes, decisions.len() as u64);
+        for decision in decisions {
+            write_varint(&mut bytes, *decision as u64);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut bytes = bytes;
+
+        if take(&mut bytes, MAGIC.len())? != MAGIC {
+            return Err(invalid("missing replay file header"));
+        }
+
+        let version = take(&mut bytes, 1)?[0];
+        if version != VERSION {
+            return Err(invalid(format!("unsupported replay version {version}")));
+        }
+
+        let flags = take(&mut bytes, 1)?[0];
+        let seed = u64::from_le_bytes(take(&mut bytes, 8)?.try_into().unwrap());
+
+        let len = read_varint(&mut bytes)?;
+        let mut decisions = vec![];
+        for _ in 0..len {
+            decisions.push(read_varint(&mut bytes)? as usize);
+        }
+
+        if !bytes.is_empty() {
+            return Err(invalid("trailing bytes after replay"));
+        }
+
+        Ok(Self {
+            seed: (flags & SEEDED != 0).then_some(seed),
+            coop: flags & COOP != 0,
+            schedule: Schedule::from_decisions(decisions),
+        })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = take(bytes, 1)?[0];
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint is too long"))
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
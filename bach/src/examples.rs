@@ -0,0 +1,14 @@
+//! Runnable scenarios that show how the pieces of bach fit together
+//!
+//! Each scenario has a `Config` with the knobs worth experimenting with and returns a `Report`
+//! that summarizes the run. [`Config::run`](echo::Config::run) runs a scenario in its own
+//! runtime, while `scenario` returns a future that can be spawned into an existing simulation
+//! so the scenario can be embedded in a larger model. They're meant to be copied and adapted as a
+//! starting point for new simulations.
+//!
+//! The scenarios are exercised by the integration tests so they keep working as the crate
+//! changes.
+
+pub mod echo;
+pub mod failover;
+pub mod retry_storm;
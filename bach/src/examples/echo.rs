@@ -0,0 +1,150 @@
+//! Clients sending requests to an echo server over a link with a fixed latency
+//!
+//! The server handles one request at a time, so the round-trip latency seen by the clients grows
+//! as more of them share it.
+
+use crate::{
+    environment::default::Runtime,
+    ext::*,
+    sync::{channel, queue::vec_deque},
+    time::{Duration, Instant},
+};
+
+/// A payload along with where to send the echo
+type Request = (u64, channel::Sender<u64>);
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    clients: usize,
+    requests: usize,
+    latency: Duration,
+    processing: Duration,
+    seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clients: 4,
+            requests: 10,
+            latency: Duration::from_millis(5),
+            processing: Duration::from_millis(1),
+            seed: 0,
+        }
+    }
+}
+
+/// The outcome of an echo scenario
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The number of requests that were echoed back
+    pub completed: u64,
+    pub mean_latency: Duration,
+    pub max_latency: Duration,
+    /// The simulated time it took for all of the clients to finish
+    pub elapsed: Duration,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of clients, which defaults to `4`
+    pub fn with_clients(mut self, clients: usize) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    /// Sets the number of requests each client sends, which defaults to `10`
+    pub fn with_requests(mut self, requests: usize) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Sets the one-way latency of the link, which defaults to 5ms
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Sets how long the server takes to handle each request, which defaults to 1ms
+    pub fn with_processing(mut self, processing: Duration) -> Self {
+        self.processing = processing;
+        self
+    }
+
+    /// Sets the seed used by [`Self::run`], which defaults to `0`
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the scenario in a new runtime
+    pub fn run(&self) -> Report {
+        Runtime::new()
+            .with_seed(self.seed)
+            .block_on(self.clone().scenario())
+    }
+
+    /// Runs the scenario in the current simulation
+    pub async fn scenario(self) -> Report {
+        let start = Instant::now();
+
+        let (requests, incoming) = vec_deque::Queue::<(Instant, Request)>::default()
+            .latent(self.latency)
+            .channel();
+
+        let processing = self.processing;
+        async move {
+            while let Ok((payload, reply)) = incoming.recv().await {
+                processing.sleep().await;
+                let _ = reply.send(payload).await;
+            }
+        }
+        .daemon()
+        .spawn_named("server");
+
+        let clients: Vec<_> = (0..self.clients)
+            .map(|id| {
+                let requests = requests.clone();
+                let count = self.requests as u64;
+                let latency = self.latency;
+                async move {
+                    let (reply, replies) = vec_deque::Queue::default().latent(latency).channel();
+                    let mut latencies = vec![];
+                    for payload in 0..count {
+                        // wait a bit between requests so the clients don't move in lockstep
+                        (0..=10u64).any().ms().sleep().await;
+
+                        let sent = Instant::now();
+                        requests.send((payload, reply.clone())).await.unwrap();
+                        let echoed = replies.recv().await.unwrap();
+                        assert_eq!(echoed, payload);
+                        latencies.push(sent.elapsed());
+                    }
+                    latencies
+                }
+                .spawn_named(format!("client_{id}"))
+            })
+            .collect();
+
+        let mut latencies = vec![];
+        for client in clients {
+            latencies.extend(client.await);
+        }
+
+        let completed = latencies.len() as u64;
+        let total: Duration = latencies.iter().sum();
+        Report {
+            completed,
+            mean_latency: if completed > 0 {
+                total / completed as u32
+            } else {
+                Duration::ZERO
+            },
+            max_latency: latencies.into_iter().max().unwrap_or_default(),
+            elapsed: start.elapsed(),
+        }
+    }
+}
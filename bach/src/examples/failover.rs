@@ -0,0 +1,209 @@
+//! A backup replica taking over when the primary stops sending heartbeats
+//!
+//! The primary is paused part way through the run, which looks the same as a crash or a network
+//! partition from the backup's point of view. Once the backup's failure detector suspects the
+//! primary, the backup promotes itself and the client sends its requests there instead. Requests
+//! sent to the primary between the start of the fault and the promotion time out.
+
+use crate::{
+    deadline::Deadline,
+    environment::default::Runtime,
+    ext::*,
+    failure_detector::{Detector, Timeout},
+    group::Group,
+    sync::channel,
+    time::{Duration, Instant},
+};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+const REPLICAS: [&str; 2] = ["primary", "backup"];
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    requests: usize,
+    request_interval: Duration,
+    request_timeout: Duration,
+    heartbeat_interval: Duration,
+    failover_timeout: Duration,
+    fault_at: Duration,
+    fault_duration: Duration,
+    seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            requests: 100,
+            request_interval: Duration::from_millis(10),
+            request_timeout: Duration::from_millis(20),
+            heartbeat_interval: Duration::from_millis(10),
+            failover_timeout: Duration::from_millis(50),
+            fault_at: Duration::from_millis(300),
+            fault_duration: Duration::from_millis(500),
+            seed: 0,
+        }
+    }
+}
+
+/// The outcome of a failover scenario
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub succeeded: u64,
+    pub failed: u64,
+    /// When the backup promoted itself, relative to the start of the scenario
+    pub failover_at: Option<Duration>,
+    /// The number of requests served by the primary and the backup
+    pub served: [u64; 2],
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of requests the client sends, which defaults to `100`
+    pub fn with_requests(mut self, requests: usize) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Sets the time between requests, which defaults to 10ms
+    pub fn with_request_interval(mut self, request_interval: Duration) -> Self {
+        self.request_interval = request_interval;
+        self
+    }
+
+    /// Sets how long the client waits for each response, which defaults to 20ms
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Sets the time between heartbeats from the primary, which defaults to 10ms
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    /// Sets how long the backup waits without a heartbeat before promoting itself, which
+    /// defaults to 50ms
+    pub fn with_failover_timeout(mut self, failover_timeout: Duration) -> Self {
+        self.failover_timeout = failover_timeout;
+        self
+    }
+
+    /// Sets when the primary is paused, and for how long, which defaults to 500ms starting at
+    /// 300ms
+    pub fn with_fault(mut self, at: Duration, duration: Duration) -> Self {
+        self.fault_at = at;
+        self.fault_duration = duration;
+        self
+    }
+
+    /// Sets the seed used by [`Self::run`], which defaults to `0`
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the scenario in a new runtime
+    pub fn run(&self) -> Report {
+        Runtime::new()
+            .with_seed(self.seed)
+            .block_on(self.clone().scenario())
+    }
+
+    /// Runs the scenario in the current simulation
+    pub async fn scenario(self) -> Report {
+        let start = Instant::now();
+        let leader = Arc::new(AtomicUsize::new(0));
+        let served = Arc::new([AtomicU64::new(0), AtomicU64::new(0)]);
+        let failover_at = Arc::new(Mutex::new(None));
+
+        let mut replicas = vec![];
+        for (idx, name) in REPLICAS.into_iter().enumerate() {
+            let (sender, requests) = channel::unbounded::<channel::Sender<usize>>();
+            replicas.push(sender);
+
+            let served = served.clone();
+            async move {
+                while let Ok(reply) = requests.recv().await {
+                    1.ms().sleep().await;
+                    if reply.send(idx).await.is_ok() {
+                        served[idx].fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            .group(name)
+            .daemon()
+            .spawn_named(name);
+        }
+
+        let (heartbeat, heartbeats) = channel::unbounded();
+
+        let interval = self.heartbeat_interval;
+        async move {
+            loop {
+                if heartbeat.send(()).await.is_err() {
+                    return;
+                }
+                interval.sleep().await;
+            }
+        }
+        .group("primary")
+        .daemon()
+        .spawn_named("heartbeat");
+
+        {
+            let leader = leader.clone();
+            let failover_at = failover_at.clone();
+            let mut detector = Timeout::new(self.failover_timeout);
+            async move {
+                loop {
+                    // wake up periodically so a missing heartbeat is noticed
+                    match Deadline::after(interval).enforce(heartbeats.recv()).await {
+                        Ok(Ok(())) => detector.heartbeat(),
+                        Ok(Err(_)) => return,
+                        Err(_) => {}
+                    }
+
+                    if !detector.is_available() {
+                        leader.store(1, Ordering::Relaxed);
+                        *failover_at.lock().unwrap() = Some(start.elapsed());
+                        return;
+                    }
+                }
+            }
+            .group("backup")
+            .daemon()
+            .spawn_named("monitor");
+        }
+
+        Group::new("primary").pause_at(start + self.fault_at, self.fault_duration);
+
+        let mut report = Report::default();
+        for _ in 0..self.requests {
+            self.request_interval.sleep().await;
+
+            let (reply, response) = channel::bounded(1);
+            let replica = &replicas[leader.load(Ordering::Relaxed)];
+            replica.send(reply).await.unwrap();
+
+            let response = Deadline::after(self.request_timeout)
+                .enforce(response.recv())
+                .await;
+            if matches!(response, Ok(Ok(_))) {
+                report.succeeded += 1;
+            } else {
+                report.failed += 1;
+            }
+        }
+
+        report.failover_at = *failover_at.lock().unwrap();
+        report.served = [0, 1].map(|idx| served[idx].load(Ordering::Relaxed));
+        report
+    }
+}
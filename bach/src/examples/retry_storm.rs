@@ -0,0 +1,223 @@
+//! Clients retrying timed out requests against an overloaded server
+//!
+//! A burst of requests arrives at a server that can only handle one at a time. Requests that wait
+//! in its queue longer than the client timeout are retried, and the server still spends time on
+//! the ones nobody is waiting for anymore. Without backoff, the retries pile up behind each other
+//! and the server does much more work than the original burst required.
+//!
+//! Backing off spreads the retries out, and shedding requests whose deadline already passed keeps
+//! the server from spending its capacity on them.
+
+use crate::{
+    deadline::Deadline,
+    environment::default::Runtime,
+    ext::*,
+    sync::{channel, queue::vec_deque},
+    time::{Duration, Instant},
+};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// The client's deadline along with where to send the response
+type Request = (Deadline, channel::Sender<()>);
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    clients: usize,
+    service_time: Duration,
+    timeout: Duration,
+    max_attempts: u32,
+    backoff: Option<Duration>,
+    shed_expired: bool,
+    seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clients: 50,
+            service_time: Duration::from_millis(10),
+            timeout: Duration::from_millis(100),
+            max_attempts: 5,
+            backoff: None,
+            shed_expired: false,
+            seed: 0,
+        }
+    }
+}
+
+/// The outcome of a retry storm scenario
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The number of requests the clients wanted to make
+    pub requests: u64,
+    /// The number of requests sent, including retries
+    pub attempts: u64,
+    pub succeeded: u64,
+    /// The number of requests that ran out of attempts
+    pub failed: u64,
+    /// The number of requests the server handled after their client stopped waiting
+    pub wasted: u64,
+    /// The number of requests the server skipped because they had already expired
+    pub shed: u64,
+    /// The simulated time it took for all of the clients to finish
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// Returns the number of requests sent for each request the clients wanted to make
+    pub fn amplification(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.attempts as f64 / self.requests as f64
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of clients in the burst, which defaults to `50`
+    pub fn with_clients(mut self, clients: usize) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    /// Sets how long the server takes to handle each request, which defaults to 10ms
+    pub fn with_service_time(mut self, service_time: Duration) -> Self {
+        self.service_time = service_time;
+        self
+    }
+
+    /// Sets how long a client waits for a response before retrying, which defaults to 100ms
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a client sends a request before giving up, which defaults to `5`
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Enables exponential backoff with full jitter, starting at `base`
+    ///
+    /// Clients retry immediately by default.
+    pub fn with_backoff(mut self, base: Duration) -> Self {
+        self.backoff = Some(base);
+        self
+    }
+
+    /// Makes the server skip requests that expired while they were queued
+    ///
+    /// The server handles every request by default.
+    pub fn with_shed_expired(mut self, shed_expired: bool) -> Self {
+        self.shed_expired = shed_expired;
+        self
+    }
+
+    /// Sets the seed used by [`Self::run`], which defaults to `0`
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the scenario in a new runtime
+    pub fn run(&self) -> Report {
+        Runtime::new()
+            .with_seed(self.seed)
+            .block_on(self.clone().scenario())
+    }
+
+    /// Runs the scenario in the current simulation
+    pub async fn scenario(self) -> Report {
+        let start = Instant::now();
+        let wasted = Arc::new(AtomicU64::new(0));
+        let shed = Arc::new(AtomicU64::new(0));
+
+        let (requests, incoming) = vec_deque::Queue::<Request>::default().channel();
+
+        let server = {
+            let service_time = self.service_time;
+            let shed_expired = self.shed_expired;
+            let wasted = wasted.clone();
+            let shed = shed.clone();
+            async move {
+                while let Ok((deadline, reply)) = incoming.recv().await {
+                    if shed_expired && deadline.is_expired() {
+                        shed.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+
+                    // the server doesn't know the client gave up until it's done with the request
+                    service_time.sleep().await;
+                    if deadline.is_expired() {
+                        wasted.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    let _ = reply.send(()).await;
+                }
+            }
+            .spawn_named("server")
+        };
+
+        let clients: Vec<_> = (0..self.clients)
+            .map(|id| {
+                let requests = requests.clone();
+                let config = self.clone();
+                async move {
+                    let mut attempts = 0;
+                    let mut succeeded = false;
+                    while attempts < config.max_attempts {
+                        if attempts > 0 {
+                            if let Some(base) = config.backoff {
+                                let ceiling = base * 2u32.pow(attempts - 1);
+                                let jitter = (0..=ceiling.as_micros() as u64).any();
+                                jitter.us().sleep().await;
+                            }
+                        }
+
+                        attempts += 1;
+                        let deadline = Deadline::after(config.timeout);
+                        let (reply, response) = channel::bounded(1);
+                        requests.send((deadline.clone(), reply)).await.unwrap();
+                        if deadline.enforce(response.recv()).await.is_ok() {
+                            succeeded = true;
+                            break;
+                        }
+                    }
+                    (attempts, succeeded)
+                }
+                .spawn_named(format!("client_{id}"))
+            })
+            .collect();
+        // the server shuts down once the clients are done
+        drop(requests);
+
+        let mut report = Report {
+            requests: self.clients as u64,
+            ..Default::default()
+        };
+        for client in clients {
+            let (attempts, succeeded) = client.await;
+            report.attempts += attempts as u64;
+            if succeeded {
+                report.succeeded += 1;
+            } else {
+                report.failed += 1;
+            }
+        }
+        report.elapsed = start.elapsed();
+
+        // let the server work through the requests left in its queue
+        server.await;
+        report.wasted = wasted.load(Ordering::Relaxed);
+        report.shed = shed.load(Ordering::Relaxed);
+        report
+    }
+}
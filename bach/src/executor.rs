@@ -11,6 +11,12 @@ use core::{
     task::{Context, Poll, Waker},
 };
 
+mod audit;
+pub mod ingress;
+mod noise;
+pub use audit::ForeignWake;
+pub use ingress::Ingress;
+
 pub struct JoinHandle<Output>(Option<Task<Output>>);
 
 impl<Output> JoinHandle<Output> {
@@ -20,6 +26,11 @@ impl<Output> JoinHandle<Output> {
         }
     }
 
+    /// Returns `true` if the task has completed or was cancelled
+    pub fn is_finished(&self) -> bool {
+        self.0.as_ref().map_or(true, |task| task.is_finished())
+    }
+
     pub async fn stop(mut self) -> Option<Output> {
         if let Some(task) = self.0.take() {
             task.cancel().await
@@ -67,6 +78,12 @@ impl<E: Environment> Executor<E> {
             sender: queue.clone(),
             primary_count: Default::default(),
             ids: Default::default(),
+            audit: Arc::new(audit::Audit::new()),
+            noise: Default::default(),
+            services: Default::default(),
+            live: Default::default(),
+            lost_wakeups: Default::default(),
+            ingress: Default::default(),
         };
 
         let environment = create_env(&handle);
@@ -90,6 +107,11 @@ impl<E: Environment> Executor<E> {
         &self.handle
     }
 
+    /// Returns `true` if there aren't any tasks in the queue
+    pub fn is_idle(&self) -> bool {
+        self.queue.len() == 0
+    }
+
     pub fn microstep(&mut self) -> Poll<usize> {
         let task_count = self.queue.len();
 
@@ -121,6 +143,15 @@ impl<E: Environment> Executor<E> {
     }
 
     pub fn macrostep(&mut self) -> Macrostep {
+        // deliver any wakes that were deferred from foreign threads
+        self.handle.audit.flush(&self.queue);
+
+        // deliver any events that were sent from outside of the simulation
+        if self.handle.ingress.has_sources() {
+            let ingress = self.handle.ingress.clone();
+            self.environment.enter(|| ingress.flush());
+        }
+
         loop {
             if let Poll::Ready(tasks) = self.microstep() {
                 let macrostep = Macrostep { tasks, ticks: 0 };
@@ -178,9 +209,16 @@ impl<E: Environment> Executor<E> {
     pub fn close(&mut self) {
         // drop the pending items in the queue first
         let queue = self.queue.clone();
+        let audit = self.handle.audit.clone();
+        let services = self.handle.services.clone();
+        let ingress = self.handle.ingress.clone();
         self.environment.close(move || {
+            ingress.close();
+            // dropping the services can wake tasks, so do it before the queue is closed
+            services.clear();
             let _ = queue.close();
             drop(queue.drain());
+            audit.close();
         });
     }
 }
@@ -196,6 +234,13 @@ pub struct Handle {
     sender: Queue,
     primary_count: Arc<AtomicU64>,
     ids: Arc<AtomicU64>,
+    audit: Arc<audit::Audit>,
+    noise: Arc<noise::Noise>,
+    services: Arc<crate::services::Registry>,
+    /// Initialized once the runtime starts checking for orphans
+    live: Arc<std::sync::OnceLock<Arc<crate::task::info::Live>>>,
+    lost_wakeups: Arc<crate::sync::lost_wakeup::Tracker>,
+    ingress: Arc<ingress::Registry>,
 }
 
 impl Handle {
@@ -208,6 +253,25 @@ impl Handle {
     }
 
     pub fn spawn_named<F, N, Output>(&self, future: F, name: N) -> JoinHandle<Output>
+    where
+        F: Future<Output = Output> + Send + 'static,
+        Output: Send + 'static,
+        N: core::fmt::Display,
+    {
+        self.spawn_task(future, name, false)
+    }
+
+    /// Spawns a task that is marked as a [daemon](crate::task::daemon) before its first poll
+    pub(crate) fn spawn_daemon_named<F, N, Output>(&self, future: F, name: N) -> JoinHandle<Output>
+    where
+        F: Future<Output = Output> + Send + 'static,
+        Output: Send + 'static,
+        N: core::fmt::Display,
+    {
+        self.spawn_task(future, name, true)
+    }
+
+    fn spawn_task<F, N, Output>(&self, future: F, name: N, daemon: bool) -> JoinHandle<Output>
     where
         F: Future<Output = Output> + Send + 'static,
         Output: Send + 'static,
@@ -216,11 +280,13 @@ impl Handle {
         count!("spawn");
 
         let sender = self.sender.clone();
+        let audit = self.audit.clone();
 
         let id = self.ids.fetch_add(1, Ordering::Relaxed);
         let name = Arc::from(name.to_string());
 
-        let future = crate::task::info::WithInfo::new(future, id, &name);
+        let future = noise::Noisy::new(future, self.noise.get());
+        let future = crate::task::info::WithInfo::new(future, id, &name, daemon, self.live.get());
 
         let (runnable, task) = async_task::spawn(future, move |runnable| {
             if name.is_empty() {
@@ -228,7 +294,9 @@ impl Handle {
             } else {
                 count!("wake", "target" = name.clone());
             }
-            let _ = sender.push(runnable);
+            if let Some(runnable) = audit.intercept(id, runnable) {
+                let _ = sender.push(runnable);
+            }
         });
 
         // queue the initial poll
@@ -245,7 +313,73 @@ impl Handle {
         crate::task::primary::Guard::new(self.primary_count.clone())
     }
 
-    fn primary_count(&self) -> u64 {
+    /// Enables detection of wakes that are issued from threads other than the one driving the
+    /// executor
+    ///
+    /// Foreign wakes are deferred until the next macrostep boundary and reported with the
+    /// backtrace of the offending call.
+    pub fn set_wake_audit(&self, enabled: bool) {
+        self.audit.set_enabled(enabled, &self.sender);
+    }
+
+    /// Delays each task wakeup by a duration sampled from `noise`
+    ///
+    /// Only applies to tasks spawned after the call.
+    pub(crate) fn set_scheduler_noise(&self, noise: Option<Arc<noise::Sample>>) {
+        self.noise.set(noise);
+    }
+
+    /// Returns all of the foreign wakes that have been reported since the last call
+    pub fn take_foreign_wakes(&self) -> Vec<ForeignWake> {
+        self.audit.take_reports()
+    }
+
+    /// Records notifications on stateless primitives that didn't reach any waiters
+    ///
+    /// See [`crate::sync::lost_wakeup`].
+    pub fn set_lost_wakeup_detection(&self, enabled: bool) {
+        self.lost_wakeups.set_enabled(enabled);
+    }
+
+    /// Returns the tasks that are waiting for a notification that was already sent
+    pub fn lost_wakeups(&self) -> Vec<crate::sync::lost_wakeup::LostWakeup> {
+        self.lost_wakeups.lost()
+    }
+
+    pub(crate) fn lost_wakeup_tracker(&self) -> &Arc<crate::sync::lost_wakeup::Tracker> {
+        &self.lost_wakeups
+    }
+
+    /// Returns a sender for events from threads outside of the simulation, along with the
+    /// receiver they're delivered to
+    ///
+    /// See [`ingress`].
+    pub fn ingress<T>(&self) -> (Ingress<T>, crate::sync::channel::Receiver<T>)
+    where
+        T: 'static + Send + Sync,
+    {
+        self.ingress.create()
+    }
+
+    /// Starts tracking spawned tasks so they can be returned by [`Self::orphans`]
+    pub(crate) fn track_orphans(&self) {
+        self.live.get_or_init(Default::default);
+    }
+
+    /// Returns the tasks that are still pending and weren't spawned as daemons
+    ///
+    /// Only tasks that were spawned after [`Runtime::with_orphan_check`] was enabled are tracked.
+    ///
+    /// [`Runtime::with_orphan_check`]: crate::environment::default::Runtime::with_orphan_check
+    pub fn orphans(&self) -> Vec<crate::task::Info> {
+        self.live.get().map_or_else(Vec::new, |live| live.orphans())
+    }
+
+    pub(crate) fn services(&self) -> &crate::services::Registry {
+        &self.services
+    }
+
+    pub(crate) fn primary_count(&self) -> u64 {
         self.primary_count.load(Ordering::SeqCst)
     }
 }
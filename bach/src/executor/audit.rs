@@ -0,0 +1,135 @@
+use super::Queue;
+use crate::sync::queue::Queue as _;
+use async_task::Runnable;
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::{
+    backtrace::Backtrace,
+    sync::Mutex,
+    thread::{self, ThreadId},
+};
+
+/// A wake that was issued from a thread other than the one driving the executor
+#[derive(Debug)]
+pub struct ForeignWake {
+    /// The id of the task that was woken
+    pub task: u64,
+    /// The name of the thread that issued the wake, if any
+    pub thread: Option<String>,
+    /// The call stack of the thread at the time of the wake
+    pub backtrace: Backtrace,
+}
+
+impl fmt::Display for ForeignWake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let thread = self.thread.as_deref().unwrap_or("<unnamed>");
+        writeln!(
+            f,
+            "task {} was woken from foreign thread '{thread}'",
+            self.task
+        )?;
+        write!(f, "{}", self.backtrace)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Audit {
+    enabled: AtomicBool,
+    /// Set while runnables are held, which can outlast the audit being enabled
+    holding: AtomicBool,
+    owner: Mutex<ThreadId>,
+    pending: Mutex<Vec<Runnable>>,
+    unreported: Mutex<Vec<ForeignWake>>,
+    reports: Mutex<Vec<ForeignWake>>,
+}
+
+impl Audit {
+    pub fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            holding: AtomicBool::new(false),
+            owner: Mutex::new(thread::current().id()),
+            pending: Default::default(),
+            unreported: Default::default(),
+            reports: Default::default(),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool, queue: &Queue) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+
+        // don't wait for the next macrostep to release anything that's still held
+        if !enabled {
+            self.release(queue);
+        }
+    }
+
+    /// Returns the runnable back if the wake was issued from the owning thread
+    ///
+    /// Otherwise, the runnable is held until the next macrostep boundary.
+    pub fn intercept(&self, task: u64, runnable: Runnable) -> Option<Runnable> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return Some(runnable);
+        }
+
+        let current = thread::current();
+        if *self.owner.lock().unwrap() == current.id() {
+            return Some(runnable);
+        }
+
+        count!("foreign_wake");
+
+        let report = ForeignWake {
+            task,
+            thread: current.name().map(String::from),
+            backtrace: Backtrace::force_capture(),
+        };
+
+        self.pending.lock().unwrap().push(runnable);
+        self.unreported.lock().unwrap().push(report);
+        self.holding.store(true, Ordering::Release);
+
+        None
+    }
+
+    /// Moves any held runnables into the queue, in the order they were woken
+    pub fn flush(&self, queue: &Queue) {
+        if self.enabled.load(Ordering::Relaxed) {
+            // the thread driving the executor is considered the owner
+            *self.owner.lock().unwrap() = thread::current().id();
+        }
+
+        self.release(queue);
+    }
+
+    fn release(&self, queue: &Queue) {
+        // wakes that raced with disabling the audit are still held so check before skipping
+        if !self.holding.swap(false, Ordering::Acquire) {
+            return;
+        }
+
+        // release the lock before pushing since the queue may run wakers
+        let pending = core::mem::take(&mut *self.pending.lock().unwrap());
+        for runnable in pending {
+            let _ = queue.push(runnable);
+        }
+
+        let mut reports = self.reports.lock().unwrap();
+        for report in self.unreported.lock().unwrap().drain(..) {
+            eprintln!("{report}");
+            reports.push(report);
+        }
+    }
+
+    pub fn close(&self) {
+        // release the lock before dropping the runnables since dropping may trigger more wakes
+        let pending = core::mem::take(&mut *self.pending.lock().unwrap());
+        drop(pending);
+    }
+
+    pub fn take_reports(&self) -> Vec<ForeignWake> {
+        core::mem::take(&mut *self.reports.lock().unwrap())
+    }
+}
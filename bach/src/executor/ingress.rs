@@ -0,0 +1,171 @@
+//! Events sent into the simulation from threads outside of it
+//!
+//! An [`Ingress`] can be moved to a real thread, like one reading from hardware or a terminal,
+//! and its events are held until the next macrostep boundary. At each boundary, the pending
+//! events are delivered to the simulation in a fixed order: ingresses are drained in the order
+//! they were created and each one delivers its events in the order they were sent. The order in
+//! which host threads happen to race each other doesn't affect what the simulation observes
+//! within a boundary.
+//!
+//! Events only make progress while the runtime is being driven, so a simulation that waits on
+//! host events without any timers of its own should be stepped with
+//! [`Runtime::advance_to`](crate::environment::default::Runtime::advance_to).
+
+use crate::sync::channel;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::{collections::VecDeque, sync::Mutex};
+
+trait Source: Send + Sync {
+    /// Delivers the pending events and returns `false` once the source has been dropped
+    fn flush(&self) -> bool;
+
+    fn close(&self);
+}
+
+#[derive(Default)]
+pub(crate) struct Registry {
+    sources: Mutex<Vec<Arc<dyn Source>>>,
+    has_sources: AtomicBool,
+    ids: AtomicU64,
+}
+
+impl Registry {
+    pub fn create<T>(&self) -> (Ingress<T>, channel::Receiver<T>)
+    where
+        T: 'static + Send + Sync,
+    {
+        let (sender, receiver) = channel::unbounded();
+
+        let pending = Arc::new(Pending {
+            id: self.ids.fetch_add(1, Ordering::Relaxed),
+            events: Mutex::new(VecDeque::new()),
+            sender: Mutex::new(Some(sender)),
+            is_dropped: AtomicBool::new(false),
+        });
+
+        self.sources.lock().unwrap().push(pending.clone());
+        self.has_sources.store(true, Ordering::Relaxed);
+
+        (Ingress { pending }, receiver)
+    }
+
+    pub fn has_sources(&self) -> bool {
+        self.has_sources.load(Ordering::Relaxed)
+    }
+
+    /// Delivers the events that were sent since the last boundary
+    pub fn flush(&self) {
+        // deliver outside of the lock so new ingresses can be created while waking the receivers
+        let sources = self.sources.lock().unwrap().clone();
+        let mut dropped = vec![];
+
+        for (idx, source) in sources.iter().enumerate() {
+            if !source.flush() {
+                dropped.push(idx);
+            }
+        }
+
+        if dropped.is_empty() {
+            return;
+        }
+
+        let mut sources = self.sources.lock().unwrap();
+        for idx in dropped.into_iter().rev() {
+            sources.remove(idx);
+        }
+        self.has_sources
+            .store(!sources.is_empty(), Ordering::Relaxed);
+    }
+
+    pub fn close(&self) {
+        let sources = core::mem::take(&mut *self.sources.lock().unwrap());
+        self.has_sources.store(false, Ordering::Relaxed);
+        for source in sources {
+            source.close();
+        }
+    }
+}
+
+struct Pending<T> {
+    id: u64,
+    events: Mutex<VecDeque<T>>,
+    sender: Mutex<Option<channel::Sender<T>>>,
+    is_dropped: AtomicBool,
+}
+
+impl<T: 'static + Send + Sync> Source for Pending<T> {
+    fn flush(&self) -> bool {
+        // check before draining so events sent right before the drop are still delivered
+        let is_dropped = self.is_dropped.load(Ordering::Acquire);
+        let events = core::mem::take(&mut *self.events.lock().unwrap());
+
+        let mut sender = self.sender.lock().unwrap();
+
+        if !events.is_empty() {
+            count!(
+                "ingress",
+                events.len() as u64,
+                "source" = self.id.to_string()
+            );
+        }
+
+        if let Some(sender) = sender.as_ref() {
+            for event in events {
+                // the receiver may have been dropped
+                let _ = sender.try_push(event);
+            }
+        }
+
+        if is_dropped {
+            // closes the channel once the last events were delivered
+            sender.take();
+        }
+
+        !is_dropped
+    }
+
+    fn close(&self) {
+        let sender = self.sender.lock().unwrap().take();
+        let events = core::mem::take(&mut *self.events.lock().unwrap());
+        drop(sender);
+        drop(events);
+    }
+}
+
+/// Sends events into a running simulation from a thread outside of it
+///
+/// Returned by [`Runtime::ingress`](crate::environment::default::Runtime::ingress). The paired
+/// receiver is closed once the ingress is dropped and its remaining events are delivered.
+///
+/// An ingress isn't `Clone` since the order of events sent from multiple threads through the same
+/// ingress would depend on how the threads were scheduled. Each host thread should create its
+/// own.
+pub struct Ingress<T> {
+    pending: Arc<Pending<T>>,
+}
+
+impl<T> Ingress<T> {
+    /// Returns the position of the ingress in the delivery order
+    pub fn id(&self) -> u64 {
+        self.pending.id
+    }
+
+    /// Queues `event` for delivery at the next macrostep boundary
+    ///
+    /// Events sent after the runtime was closed are discarded.
+    pub fn send(&self, event: T) {
+        let sender = self.pending.sender.lock().unwrap();
+        if sender.is_none() {
+            return;
+        }
+        self.pending.events.lock().unwrap().push_back(event);
+        drop(sender);
+    }
+}
+
+impl<T> Drop for Ingress<T> {
+    fn drop(&mut self) {
+        self.pending.is_dropped.store(true, Ordering::Release);
+    }
+}
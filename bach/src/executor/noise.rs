@@ -0,0 +1,79 @@
+use crate::time::{scheduler::Timer, Duration};
+use alloc::sync::Arc;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+use std::sync::Mutex;
+
+/// Samples the extra delay applied to a task wakeup
+pub(crate) type Sample = dyn Fn() -> Duration + Send + Sync;
+
+#[derive(Default)]
+pub(crate) struct Noise(Mutex<Option<Arc<Sample>>>);
+
+impl Noise {
+    pub fn set(&self, sample: Option<Arc<Sample>>) {
+        *self.0.lock().unwrap() = sample;
+    }
+
+    pub fn get(&self) -> Option<Arc<Sample>> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+pin_project! {
+    /// Delays each poll following a wakeup by a sampled amount of simulated time
+    pub(crate) struct Noisy<F> {
+        #[pin]
+        inner: F,
+        sample: Option<Arc<Sample>>,
+        delay: Option<Timer>,
+        is_first: bool,
+    }
+}
+
+impl<F> Noisy<F> {
+    pub fn new(inner: F, sample: Option<Arc<Sample>>) -> Self {
+        Self {
+            inner,
+            sample,
+            delay: None,
+            is_first: true,
+        }
+    }
+}
+
+impl<F: Future> Future for Noisy<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if let Some(sample) = this.sample.as_ref() {
+            // the initial poll is dispatched as soon as the task is spawned
+            if core::mem::take(this.is_first) {
+                return this.inner.poll(cx);
+            }
+
+            if this.delay.is_none() {
+                let delay = sample();
+                if !delay.is_zero() {
+                    measure!("scheduler_noise", delay);
+                    *this.delay = Some(crate::time::sleep(delay));
+                }
+            }
+
+            if let Some(delay) = this.delay.as_mut() {
+                if Pin::new(delay).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                *this.delay = None;
+            }
+        }
+
+        this.inner.poll(cx)
+    }
+}
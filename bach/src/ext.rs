@@ -1,8 +1,10 @@
 use core::time::Duration;
 
 pub use crate::{
+    deadline::DeadlineExt,
     group::GroupExt,
-    rand::{gen, Any, AnySliceExt, AnySliceMutExt},
+    rand::{gen, Any, AnySliceExt, AnySliceMutExt, ShuffleExt},
+    stream::StreamTimeExt,
     sync::queue::{InstantQueueExt, QueueExt},
 };
 
@@ -85,6 +87,42 @@ where
     }
 }
 
+pub trait DaemonExt {
+    type Output;
+
+    /// Marks the task as a background task that is allowed to outlive the primary tasks
+    fn daemon(self) -> Self::Output;
+}
+
+impl<F> DaemonExt for F
+where
+    F: core::future::Future,
+{
+    type Output = crate::task::daemon::Wrapped<F>;
+
+    fn daemon(self) -> Self::Output {
+        crate::task::daemon::create(self)
+    }
+}
+
+pub trait CatchUnwindExt {
+    type Output;
+
+    /// Completes with the panic payload if the future panics, rather than ending the simulation
+    fn catch_unwind(self) -> Self::Output;
+}
+
+impl<F> CatchUnwindExt for F
+where
+    F: core::future::Future,
+{
+    type Output = crate::task::catch_unwind::Wrapped<F>;
+
+    fn catch_unwind(self) -> Self::Output {
+        crate::task::catch_unwind::create(self)
+    }
+}
+
 pub trait SeedExt {
     type Output;
 
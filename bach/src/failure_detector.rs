@@ -0,0 +1,333 @@
+//! Failure detectors driven by simulated heartbeats
+//!
+//! Cluster membership protocols decide when a peer is down based on the heartbeats they receive
+//! from it. A [`Detector`] turns the arrival times of those heartbeats into a suspicion level, and
+//! a [`Monitor`] tracks a detector for each peer.
+//!
+//! ```no_run
+//! # use bach::{
+//! #     failure_detector::{Monitor, PhiAccrual},
+//! #     sync::channel,
+//! # };
+//! # struct Heartbeat {
+//! #     from: u64,
+//! # }
+//! # struct Membership;
+//! # impl Membership {
+//! #     fn remove(&mut self, _peer: u64) {}
+//! # }
+//! # async fn example(incoming: channel::Receiver<Heartbeat>, mut membership: Membership) {
+//! let mut monitor = Monitor::new(|| PhiAccrual::new().with_threshold(8.0));
+//!
+//! while let Ok(Heartbeat { from }) = incoming.recv().await {
+//!     monitor.heartbeat(from);
+//!
+//!     for peer in monitor.suspected() {
+//!         membership.remove(peer);
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! The detectors only look at the simulated clock, so the same seed always suspects the same
+//! peers at the same time.
+
+use crate::time::{Duration, Instant};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Estimates how likely it is that a peer has failed from the heartbeats it sent
+pub trait Detector {
+    /// Records a heartbeat that arrived at `at`
+    fn heartbeat_at(&mut self, at: Instant);
+
+    /// Returns the suspicion level at `at`
+    ///
+    /// The level is `0.0` before the first heartbeat.
+    fn suspicion_at(&self, at: Instant) -> f64;
+
+    /// Returns the suspicion level at which the peer is considered unavailable
+    fn threshold(&self) -> f64;
+
+    /// Records a heartbeat that arrived now
+    fn heartbeat(&mut self) {
+        self.heartbeat_at(Instant::now())
+    }
+
+    /// Returns the current suspicion level
+    fn suspicion(&self) -> f64 {
+        self.suspicion_at(Instant::now())
+    }
+
+    /// Returns `true` if the current suspicion level is below the threshold
+    fn is_available(&self) -> bool {
+        self.suspicion() < self.threshold()
+    }
+}
+
+/// Suspects a peer once no heartbeats have arrived for a fixed amount of time
+///
+/// The suspicion level is the time since the last heartbeat as a fraction of the timeout, so the
+/// peer is unavailable once it reaches `1.0`.
+#[derive(Clone, Debug)]
+pub struct Timeout {
+    timeout: Duration,
+    last: Option<Instant>,
+}
+
+impl Timeout {
+    /// # Panics
+    ///
+    /// Panics if `timeout` is zero.
+    pub fn new(timeout: Duration) -> Self {
+        assert!(!timeout.is_zero(), "timeout must be greater than 0");
+        Self {
+            timeout,
+            last: None,
+        }
+    }
+}
+
+impl Detector for Timeout {
+    fn heartbeat_at(&mut self, at: Instant) {
+        self.last = Some(self.last.map_or(at, |last| last.max(at)));
+    }
+
+    fn suspicion_at(&self, at: Instant) -> f64 {
+        let Some(last) = self.last else {
+            return 0.0;
+        };
+        at.saturating_duration_since(last).as_secs_f64() / self.timeout.as_secs_f64()
+    }
+
+    fn threshold(&self) -> f64 {
+        1.0
+    }
+}
+
+/// The phi accrual failure detector from Hayashibara et al.
+///
+/// The intervals between heartbeats are assumed to be normally distributed. The suspicion level,
+/// phi, is `-log10` of the probability that a heartbeat would still be on its way after the time
+/// that has passed since the last one, so a phi of `8.0` means the detector would be wrong about
+/// 1 in 10^8 times. Peers with irregular heartbeats are given more leeway than ones that are
+/// always on time.
+#[derive(Clone, Debug)]
+pub struct PhiAccrual {
+    threshold: f64,
+    max_samples: usize,
+    min_std_dev: Duration,
+    acceptable_pause: Duration,
+    first_estimate: Duration,
+    intervals: VecDeque<f64>,
+    sum: f64,
+    squared_sum: f64,
+    last: Option<Instant>,
+}
+
+impl Default for PhiAccrual {
+    fn default() -> Self {
+        Self {
+            threshold: 8.0,
+            max_samples: 1000,
+            min_std_dev: Duration::from_millis(100),
+            acceptable_pause: Duration::ZERO,
+            first_estimate: Duration::from_secs(1),
+            intervals: VecDeque::new(),
+            sum: 0.0,
+            squared_sum: 0.0,
+            last: None,
+        }
+    }
+}
+
+impl PhiAccrual {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the phi at which the peer is considered unavailable, which defaults to `8.0`
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the number of intervals that the distribution is estimated from, which defaults to
+    /// `1000`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_samples` is `0`.
+    pub fn with_max_samples(mut self, max_samples: usize) -> Self {
+        assert!(max_samples > 0, "max_samples must be greater than 0");
+        self.max_samples = max_samples;
+        self
+    }
+
+    /// Sets the lower bound of the standard deviation, which defaults to 100ms
+    ///
+    /// Perfectly regular heartbeats would otherwise make phi jump to infinity as soon as one is
+    /// late.
+    pub fn with_min_std_dev(mut self, min_std_dev: Duration) -> Self {
+        self.min_std_dev = min_std_dev;
+        self
+    }
+
+    /// Sets how long a heartbeat can be late, on top of the expected interval, before phi starts
+    /// to rise, which defaults to zero
+    pub fn with_acceptable_pause(mut self, acceptable_pause: Duration) -> Self {
+        self.acceptable_pause = acceptable_pause;
+        self
+    }
+
+    /// Sets the expected interval before enough heartbeats have arrived to estimate it, which
+    /// defaults to 1s
+    pub fn with_first_estimate(mut self, first_estimate: Duration) -> Self {
+        self.first_estimate = first_estimate;
+        self
+    }
+
+    /// Returns the mean of the intervals between heartbeats, in seconds
+    pub fn mean(&self) -> f64 {
+        if self.intervals.is_empty() {
+            return self.first_estimate.as_secs_f64();
+        }
+        self.sum / self.intervals.len() as f64
+    }
+
+    /// Returns the standard deviation of the intervals between heartbeats, in seconds
+    pub fn std_dev(&self) -> f64 {
+        let std_dev = if self.intervals.is_empty() {
+            self.first_estimate.as_secs_f64() / 4.0
+        } else {
+            let mean = self.mean();
+            let variance = self.squared_sum / self.intervals.len() as f64 - mean * mean;
+            variance.max(0.0).sqrt()
+        };
+        std_dev.max(self.min_std_dev.as_secs_f64())
+    }
+
+    fn push(&mut self, interval: f64) {
+        if self.intervals.len() == self.max_samples {
+            if let Some(oldest) = self.intervals.pop_front() {
+                self.sum -= oldest;
+                self.squared_sum -= oldest * oldest;
+            }
+        }
+        self.intervals.push_back(interval);
+        self.sum += interval;
+        self.squared_sum += interval * interval;
+    }
+}
+
+impl Detector for PhiAccrual {
+    fn heartbeat_at(&mut self, at: Instant) {
+        if let Some(last) = self.last {
+            if at < last {
+                return;
+            }
+            self.push(at.saturating_duration_since(last).as_secs_f64());
+        }
+        self.last = Some(at);
+    }
+
+    fn suspicion_at(&self, at: Instant) -> f64 {
+        let Some(last) = self.last else {
+            return 0.0;
+        };
+
+        let elapsed = at.saturating_duration_since(last).as_secs_f64();
+        let mean = self.mean() + self.acceptable_pause.as_secs_f64();
+        let std_dev = self.std_dev();
+
+        // a logistic approximation of the normal CDF, which stays accurate far into the tail
+        let y = (elapsed - mean) / std_dev;
+        let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+        let phi = if elapsed > mean {
+            -(e / (1.0 + e)).log10()
+        } else {
+            -(1.0 - 1.0 / (1.0 + e)).log10()
+        };
+
+        phi.max(0.0)
+    }
+
+    fn threshold(&self) -> f64 {
+        self.threshold
+    }
+}
+
+/// Tracks a [`Detector`] for each peer
+pub struct Monitor<K, D> {
+    new_detector: Box<dyn FnMut() -> D + Send>,
+    peers: BTreeMap<K, Peer<D>>,
+}
+
+struct Peer<D> {
+    detector: D,
+    is_suspected: bool,
+}
+
+impl<K: Ord + Clone, D: Detector> Monitor<K, D> {
+    /// Returns a monitor that calls `new_detector` for each peer the first time it sends a
+    /// heartbeat
+    pub fn new<F>(new_detector: F) -> Self
+    where
+        F: 'static + FnMut() -> D + Send,
+    {
+        Self {
+            new_detector: Box::new(new_detector),
+            peers: BTreeMap::new(),
+        }
+    }
+
+    /// Records a heartbeat from `peer` that arrived now
+    pub fn heartbeat(&mut self, peer: K) {
+        let new_detector = &mut self.new_detector;
+        let peer = self.peers.entry(peer).or_insert_with(|| Peer {
+            detector: new_detector(),
+            is_suspected: false,
+        });
+        peer.detector.heartbeat();
+    }
+
+    /// Returns the current suspicion level of `peer`, or `None` if it never sent a heartbeat
+    pub fn suspicion(&self, peer: &K) -> Option<f64> {
+        Some(self.peers.get(peer)?.detector.suspicion())
+    }
+
+    /// Returns `true` if `peer` sent a heartbeat and isn't currently suspected
+    pub fn is_available(&self, peer: &K) -> bool {
+        self.peers
+            .get(peer)
+            .map_or(false, |peer| peer.detector.is_available())
+    }
+
+    /// Returns the peers that are currently suspected, in order
+    ///
+    /// Each change in whether a peer is suspected is counted in the `failure_detector` metric.
+    pub fn suspected(&mut self) -> Vec<K> {
+        let mut suspected = vec![];
+        for (key, peer) in self.peers.iter_mut() {
+            let is_suspected = !peer.detector.is_available();
+            if is_suspected != peer.is_suspected {
+                peer.is_suspected = is_suspected;
+                let event = if is_suspected { "suspect" } else { "recover" };
+                count!("failure_detector", "event" = event);
+            }
+            if is_suspected {
+                suspected.push(key.clone());
+            }
+        }
+        suspected
+    }
+
+    /// Stops tracking `peer`, like after it was removed from the cluster
+    pub fn remove(&mut self, peer: &K) -> Option<D> {
+        Some(self.peers.remove(peer)?.detector)
+    }
+
+    /// Returns the detector for `peer`
+    pub fn detector(&self, peer: &K) -> Option<&D> {
+        Some(&self.peers.get(peer)?.detector)
+    }
+}
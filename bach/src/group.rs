@@ -1,21 +1,41 @@
-use crate::tracing::info_span;
+pub mod local;
+
+pub use local::Local;
+
+use crate::{
+    executor::JoinHandle,
+    time::{Duration, Instant},
+    tracing::info_span,
+};
 use core::{
     fmt,
     future::Future,
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
 };
 use pin_project_lite::pin_project;
-use std::{cell::RefCell, collections::HashMap};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+};
 
 thread_local! {
     static GROUPS: RefCell<Groups> = RefCell::new(Groups::default());
+    // the innermost group of the task currently being polled
+    static INNERMOST: Cell<Option<Group>> = const { Cell::new(None) };
 }
 
 #[derive(Default)]
 struct Groups {
     name_to_id: HashMap<String, u64>,
-    id_to_name: HashMap<u64, String>,
+    id_to_name: BTreeMap<u64, String>,
+    paused: BTreeMap<u64, Pause>,
+    tasks: BTreeMap<u64, usize>,
+}
+
+struct Pause {
+    until: Instant,
+    wakers: Vec<Waker>,
 }
 
 impl Groups {
@@ -42,11 +62,26 @@ impl Groups {
 crate::scope::define!(scope, Group);
 crate::scope::define!(listener, fn(u64, &str));
 
+/// Returns all of the groups that have been created on the current thread, in creation order
+///
+/// The order is guaranteed to match the [`Ord`] implementation of [`Group`], so anything derived
+/// from the list, like diagnostics or snapshots, is stable across runs and platforms.
+pub fn list() -> Vec<Group> {
+    GROUPS.with(|groups| {
+        let groups = groups.borrow();
+        groups.id_to_name.keys().map(|&id| Group { id }).collect()
+    })
+}
+
 pub fn current() -> Group {
     scope::try_borrow_with(|scope| scope.unwrap_or_else(|| Group::new("main")))
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A named set of tasks, like the processes running on a simulated node
+///
+/// Groups are ordered by when they were first created on the current thread, rather than by
+/// name, so sorting them gives the same order as [`list`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Group {
     id: u64,
 }
@@ -67,9 +102,157 @@ impl Group {
         })
     }
 
+    /// Returns the group with the given name, if it has been created
+    pub fn find(name: &str) -> Option<Self> {
+        GROUPS.with(|groups| {
+            let id = groups.borrow().name_to_id.get(name).copied()?;
+            Some(Self { id })
+        })
+    }
+
     pub fn name(&self) -> String {
         self.to_string()
     }
+
+    /// Returns the number of tasks in the group that haven't completed yet
+    ///
+    /// Tasks are counted once they have been polled for the first time.
+    pub fn task_count(&self) -> usize {
+        GROUPS.with(|groups| groups.borrow().tasks.get(&self.id).copied().unwrap_or(0))
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns `true` if the group is currently frozen by [`Self::pause`]
+    pub fn is_paused(&self) -> bool {
+        GROUPS.with(|groups| groups.borrow().paused.contains_key(&self.id))
+    }
+
+    /// Freezes the group for `duration`, starting now
+    ///
+    /// None of the group's tasks are polled while it's paused, even if they are woken. This
+    /// models stop-the-world pauses, like garbage collection or compaction, on a simulated node.
+    /// Pausing a group that is already paused extends the pause if it would end later.
+    pub fn pause(&self, duration: Duration) {
+        // the resume task waits for the clock to reach `until`, which it can only do on a tick
+        let until = Instant::now() + crate::time::resolution::round_up(duration);
+
+        let is_new = GROUPS.with(|groups| {
+            let mut groups = groups.borrow_mut();
+            match groups.paused.get_mut(&self.id) {
+                Some(pause) => {
+                    pause.until = pause.until.max(until);
+                    false
+                }
+                None => {
+                    let wakers = vec![];
+                    groups.paused.insert(self.id, Pause { until, wakers });
+                    true
+                }
+            }
+        });
+
+        count!("pause", "group" = self.name());
+
+        if !is_new {
+            return;
+        }
+
+        let group = *self;
+        let resume = async move {
+            let _guard = PauseGuard(group);
+
+            loop {
+                let until =
+                    GROUPS.with(|groups| groups.borrow().paused.get(&group.id).map(|p| p.until));
+                let Some(until) = until else {
+                    return;
+                };
+
+                if until <= Instant::now() {
+                    break;
+                }
+
+                crate::time::sleep_until(until).await;
+            }
+
+            group.resume();
+        };
+
+        // the resume task is spawned outside of the group so it isn't frozen by its own pause
+        crate::task::scope::borrow_with(|handle| handle.spawn_daemon_named(resume, "group_resume"));
+    }
+
+    /// Freezes the group for `duration` once the simulation reaches `at`
+    pub fn pause_at(&self, at: Instant, duration: Duration) -> JoinHandle<()> {
+        let group = *self;
+        let pause = async move {
+            crate::time::sleep_until(at).await;
+            group.pause(duration);
+        };
+        crate::task::scope::borrow_with(|handle| handle.spawn_daemon_named(pause, "group_pause"))
+    }
+
+    /// Repeatedly freezes the group, waiting for `interval()` between the end of one pause and
+    /// the start of the next, and pausing for `duration()` each time
+    ///
+    /// Using [`crate::rand`] in the closures gives randomized pauses that are reproducible for a
+    /// given seed. The returned handle can be cancelled to stop injecting pauses.
+    pub fn inject_pauses<I, D>(&self, mut interval: I, mut duration: D) -> JoinHandle<()>
+    where
+        I: 'static + FnMut() -> Duration + Send,
+        D: 'static + FnMut() -> Duration + Send,
+    {
+        let group = *self;
+        let pauses = async move {
+            loop {
+                crate::time::sleep(interval()).await;
+                let duration = duration();
+                group.pause(duration);
+                crate::time::sleep(duration).await;
+            }
+        };
+        crate::task::scope::borrow_with(|handle| handle.spawn_daemon_named(pauses, "group_pause"))
+    }
+
+    /// Ends the pause on the group early, waking any of its tasks that were woken in the meantime
+    pub fn resume(&self) {
+        let pause = GROUPS.with(|groups| groups.borrow_mut().paused.remove(&self.id));
+
+        // wake the tasks outside of the borrow since they may be polled inline
+        if let Some(pause) = pause {
+            for waker in pause.wakers {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Returns `true` and holds onto the waker if the group is paused
+    fn park(&self, waker: &Waker) -> bool {
+        GROUPS.with(|groups| {
+            let mut groups = groups.borrow_mut();
+            let Some(pause) = groups.paused.get_mut(&self.id) else {
+                return false;
+            };
+            if !pause.wakers.iter().any(|w| w.will_wake(waker)) {
+                pause.wakers.push(waker.clone());
+            }
+            true
+        })
+    }
+}
+
+/// Clears the pause if the resume task is dropped before it completes, like when the runtime
+/// shuts down
+struct PauseGuard(Group);
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        let pause = GROUPS.with(|groups| groups.borrow_mut().paused.remove(&self.0.id));
+        drop(pause);
+    }
 }
 
 pub trait GroupExt: Sized {
@@ -86,18 +269,50 @@ where
     }
 }
 
+/// Counts a task towards its group until it's dropped
+struct TaskGuard(Group);
+
+impl TaskGuard {
+    fn new(group: Group) -> Self {
+        GROUPS.with(|groups| *groups.borrow_mut().tasks.entry(group.id).or_default() += 1);
+        Self(group)
+    }
+
+    fn set(&mut self, group: Group) {
+        if self.0 != group {
+            *self = Self::new(group);
+        }
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        // the thread may be shutting down, in which case there's nothing to update
+        let _ = GROUPS.try_with(|groups| {
+            if let Some(count) = groups.borrow_mut().tasks.get_mut(&self.0.id) {
+                *count -= 1;
+            }
+        });
+    }
+}
+
 pin_project! {
     #[must_use = "futures do nothing unless polled"]
     pub struct Grouped<Inner> {
         #[pin]
         inner: Inner,
         group: Group,
+        task: Option<TaskGuard>,
     }
 }
 
 impl<Inner> Grouped<Inner> {
     pub fn new(inner: Inner, group: Group) -> Self {
-        Self { inner, group }
+        Self {
+            inner,
+            group,
+            task: None,
+        }
     }
 }
 
@@ -111,7 +326,42 @@ where
         let this = self.project();
         let inner = this.inner;
         let group = this.group;
-        let span = info_span!("group", %group);
-        scope::with(*group, || span.in_scope(|| Future::poll(inner, cx)))
+
+        // Spawned tasks inherit the group of their parent, so a task can be wrapped in multiple
+        // groups. Only the outermost wrapper counts the task, using the innermost group.
+        let is_task = scope::try_borrow_with(|scope| scope.is_none());
+        INNERMOST.with(|innermost| innermost.set(Some(*group)));
+
+        let is_parked = group.park(cx.waker());
+        let res = if is_parked {
+            Poll::Pending
+        } else {
+            let span = info_span!("group", %group);
+            scope::with(*group, || {
+                span.in_scope(|| {
+                    #[cfg(feature = "memory")]
+                    let _attribution = crate::memory::Attribution::new(*group);
+                    Future::poll(inner, cx)
+                })
+            })
+        };
+
+        if is_task {
+            let innermost = INNERMOST
+                .with(|innermost| innermost.take())
+                .unwrap_or(*group);
+            if res.is_ready() {
+                *this.task = None;
+            } else if is_parked {
+                // the inner groups weren't polled, so the innermost one isn't known and the task
+                // keeps its current group
+            } else if let Some(task) = this.task.as_mut() {
+                task.set(innermost);
+            } else {
+                *this.task = Some(TaskGuard::new(innermost));
+            }
+        }
+
+        res
     }
 }
@@ -0,0 +1,110 @@
+//! Detects state that is accidentally shared between groups
+//!
+//! Groups usually model separate hosts, which should only communicate through channels or the
+//! network model. Wrapping per-host state in a [`Local`] tags it with the group that created it,
+//! and any access or drop from a task in a different group is reported with a warning and a
+//! `group_local_violation` metric.
+
+use super::{current, Group};
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+pub struct Local<T> {
+    value: T,
+    group: Group,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Local<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Local")
+            .field("value", &self.value)
+            .field("group", &self.group)
+            .finish()
+    }
+}
+
+impl<T> Local<T> {
+    /// Tags `value` with the current group
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            group: current(),
+        }
+    }
+
+    /// Returns the group that created the value
+    pub fn group(&self) -> Group {
+        self.group
+    }
+
+    /// Returns `true` if the current task belongs to a different group than the one that
+    /// created the value
+    ///
+    /// Code running outside of a task, like the runtime shutting down, is never considered
+    /// foreign.
+    pub fn is_foreign(&self) -> bool {
+        self.accessor().is_some()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.check("moved out");
+        let this = core::mem::ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped again
+        unsafe { core::ptr::read(&this.value) }
+    }
+
+    fn accessor(&self) -> Option<Group> {
+        let in_task = crate::task::info::scope::try_borrow_with(|info| info.is_some());
+        if !in_task {
+            return None;
+        }
+
+        let group = current();
+        (group != self.group).then_some(group)
+    }
+
+    fn check(&self, action: &'static str) {
+        let Some(accessor) = self.accessor() else {
+            return;
+        };
+
+        count!(
+            "group_local_violation",
+            "owner" = self.group.name(),
+            "accessor" = accessor.name(),
+            "action" = action
+        );
+        crate::tracing::warn!(
+            owner = %self.group,
+            accessor = %accessor,
+            "{} created in group {:?} was {action} from group {:?}",
+            core::any::type_name::<T>(),
+            self.group.name(),
+            accessor.name(),
+        );
+    }
+}
+
+impl<T> Deref for Local<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.check("used");
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Local<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.check("used");
+        &mut self.value
+    }
+}
+
+impl<T> Drop for Local<T> {
+    fn drop(&mut self) {
+        self.check("dropped");
+    }
+}
@@ -5,18 +5,32 @@ mod tracing;
 #[macro_use]
 pub mod metrics;
 
+pub mod autoscale;
 pub mod coop;
+pub mod deadline;
 pub mod environment;
+#[cfg(feature = "examples")]
+pub mod examples;
 pub mod executor;
 pub mod ext;
+pub mod failure_detector;
 pub mod group;
+#[cfg(feature = "memory")]
+pub mod memory;
 #[cfg(any(test, feature = "net"))]
 pub mod net;
+pub mod output;
+pub mod process;
+pub mod profile;
 pub mod rand;
 pub mod scope;
+pub mod services;
+pub mod stream;
 pub mod sync;
 pub mod task;
+pub mod testing;
 pub mod time;
+pub mod workload;
 
 /// Returns `true` if the caller is being executed in a `bach` environment
 pub fn is_active() -> bool {
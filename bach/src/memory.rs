@@ -0,0 +1,348 @@
+//! Per-group memory accounting
+//!
+//! Installing [`Allocator`] as the global allocator attributes each allocation made while a
+//! [`Grouped`](crate::group::Grouped) task is being polled to that group. This gives a rough
+//! memory profile of each simulated node.
+//!
+//! ```
+//! #[global_allocator]
+//! static ALLOCATOR: bach::memory::Allocator<std::alloc::System> =
+//!     bach::memory::Allocator::new(std::alloc::System);
+//! # fn main() {}
+//! ```
+//!
+//! Usage is tracked per thread, which matches how simulations are driven. Memory that is freed
+//! on a different thread than it was allocated on is subtracted from the freeing thread.
+//!
+//! Usage is reset at the start of each run. When the run finishes, the [`report`] is printed to
+//! stderr, and it's still available until the next run starts.
+
+use crate::group::{self, Group};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    fmt,
+};
+
+/// The maximum number of groups that can be tracked per thread
+///
+/// Allocations for groups beyond this limit are not attributed.
+pub const MAX_GROUPS: usize = 256;
+
+/// The sentinel used for allocations made outside of a group
+const UNTRACKED: usize = 0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of bytes currently allocated by the group
+    pub current: usize,
+    /// The maximum number of bytes the group had allocated at any point
+    pub peak: usize,
+    /// The total number of bytes the group has ever allocated
+    pub total: usize,
+    /// The total number of allocations the group has made
+    pub allocations: usize,
+}
+
+impl Usage {
+    const ZERO: Self = Self {
+        current: 0,
+        peak: 0,
+        total: 0,
+        allocations: 0,
+    };
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: Cell<Usage> = Cell::new(Usage::ZERO);
+
+thread_local! {
+    // the slot of the group currently being polled, offset by one
+    static CURRENT: Cell<usize> = const { Cell::new(UNTRACKED) };
+    static USAGE: [Cell<Usage>; MAX_GROUPS] = const { [EMPTY; MAX_GROUPS] };
+}
+
+/// Returns the memory usage for the given group on the current thread
+pub fn usage(group: Group) -> Usage {
+    let Some(slot) = slot(group.id()) else {
+        return Usage::default();
+    };
+
+    USAGE.with(|usage| usage[slot - 1].get())
+}
+
+/// Returns the memory usage for every group that has made an allocation on the current thread,
+/// in the order the groups were created
+pub fn report() -> Report {
+    // collect the groups first so the report doesn't include its own allocations
+    let groups = group::list();
+    let groups = groups
+        .into_iter()
+        .map(|group| (group, usage(group)))
+        .filter(|(_, usage)| usage.allocations > 0)
+        .collect();
+    Report { groups }
+}
+
+/// Resets the memory usage of all groups on the current thread
+pub fn reset() {
+    USAGE.with(|usage| {
+        for slot in usage.iter() {
+            slot.set(Usage::ZERO);
+        }
+    })
+}
+
+/// Called when a run starts on the current thread
+pub(crate) fn start_run() {
+    reset();
+}
+
+/// Called when a run finishes on the current thread
+pub(crate) fn finish_run() {
+    let report = report();
+    if !report.is_empty() {
+        eprintln!("memory usage by group:\n{report}");
+    }
+}
+
+/// A snapshot of the memory usage of each group, in the order the groups were created
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    groups: Vec<(Group, Usage)>,
+}
+
+impl Report {
+    /// Returns the memory usage for the given group
+    pub fn get(&self, group: Group) -> Option<&Usage> {
+        self.groups
+            .iter()
+            .find(|(g, _)| *g == group)
+            .map(|(_, usage)| usage)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Group, &Usage)> + '_ {
+        self.groups.iter().map(|(group, usage)| (*group, usage))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self.groups.iter().map(|(group, _)| group.name()).collect();
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        for (name, (_, usage)) in names.iter().zip(&self.groups) {
+            writeln!(
+                f,
+                "{name:width$}  current={} peak={} total={} allocations={}",
+                usage.current, usage.peak, usage.total, usage.allocations,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn slot(id: u64) -> Option<usize> {
+    let id = id as usize;
+    if id < MAX_GROUPS {
+        Some(id + 1)
+    } else {
+        None
+    }
+}
+
+/// Attributes allocations to a group for as long as the guard is held
+pub(crate) struct Attribution {
+    prev: usize,
+}
+
+impl Attribution {
+    pub(crate) fn new(group: Group) -> Self {
+        let slot = slot(group.id()).unwrap_or(UNTRACKED);
+        let prev = CURRENT.with(|current| current.replace(slot));
+        Self { prev }
+    }
+}
+
+impl Drop for Attribution {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.prev));
+    }
+}
+
+fn record_alloc(slot: usize, size: usize) {
+    if slot == UNTRACKED {
+        return;
+    }
+
+    let _ = USAGE.try_with(|usage| {
+        let cell = &usage[slot - 1];
+        let mut usage = cell.get();
+        usage.current += size;
+        usage.peak = usage.peak.max(usage.current);
+        usage.total += size;
+        usage.allocations += 1;
+        cell.set(usage);
+    });
+}
+
+fn record_dealloc(slot: usize, size: usize) {
+    if slot == UNTRACKED {
+        return;
+    }
+
+    let _ = USAGE.try_with(|usage| {
+        let cell = &usage[slot - 1];
+        let mut usage = cell.get();
+        usage.current = usage.current.saturating_sub(size);
+        cell.set(usage);
+    });
+}
+
+/// A global allocator that attributes allocations to the currently executing group
+///
+/// Each allocation is prefixed with a small header recording the owning group so it can be
+/// credited back when it's freed.
+pub struct Allocator<A> {
+    inner: A,
+}
+
+impl<A> Allocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    #[inline]
+    fn layout(layout: Layout) -> Option<(Layout, usize)> {
+        Layout::new::<usize>().extend(layout).ok()
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Allocator<A> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some((outer, offset)) = Self::layout(layout) else {
+            return core::ptr::null_mut();
+        };
+
+        let ptr = self.inner.alloc(outer);
+        if ptr.is_null() {
+            return ptr;
+        }
+
+        let slot = CURRENT
+            .try_with(|current| current.get())
+            .unwrap_or(UNTRACKED);
+        (ptr as *mut usize).write(slot);
+        record_alloc(slot, layout.size());
+
+        ptr.add(offset)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (outer, offset) = Self::layout(layout).unwrap();
+        let ptr = ptr.sub(offset);
+
+        let slot = (ptr as *const usize).read();
+        record_dealloc(slot, layout.size());
+
+        self.inner.dealloc(ptr, outer)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let (outer, offset) = Self::layout(layout).unwrap();
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return core::ptr::null_mut();
+        };
+        let Some((new_outer, _)) = Self::layout(new_layout) else {
+            return core::ptr::null_mut();
+        };
+
+        let ptr = ptr.sub(offset);
+        let new_ptr = self.inner.realloc(ptr, outer, new_outer.size());
+        if new_ptr.is_null() {
+            return new_ptr;
+        }
+
+        // the memory stays with the group that originally allocated it
+        let slot = (new_ptr as *const usize).read();
+        record_dealloc(slot, layout.size());
+        record_alloc(slot, new_size);
+
+        new_ptr.add(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::default::Runtime, executor::tests::executor, ext::*, group::Grouped};
+    use std::alloc::System;
+
+    #[global_allocator]
+    static ALLOCATOR: Allocator<System> = Allocator::new(System);
+
+    #[test]
+    fn group_attribution() {
+        let mut executor = executor();
+
+        let group = Group::new("memory_test");
+        let before = usage(group);
+
+        let task = executor.spawn(Grouped::new(async { vec![1u8; 1024] }, group));
+        executor.macrostep();
+
+        let after = usage(group);
+        assert!(after.current >= before.current + 1024);
+        assert!(after.peak >= 1024);
+        assert!(after.allocations > before.allocations);
+        assert!(report().get(group).is_some());
+
+        let value = executor.block_on(task);
+        assert_eq!(value.len(), 1024);
+        drop(value);
+
+        assert!(usage(group).current < after.current);
+    }
+
+    #[test]
+    fn per_run() {
+        let group = Group::new("memory_per_run");
+
+        let mut rt = Runtime::new();
+        rt.run(|| {
+            async {
+                let value = vec![1u8; 1024];
+                1.ms().sleep().await;
+                drop(value);
+            }
+            .group("memory_per_run")
+            .primary()
+            .spawn();
+        });
+        // the report that is printed at the end of the run stays available until the next run
+        drop(rt);
+        let report = report();
+        let recorded = report.get(group).unwrap();
+        assert!(recorded.peak >= 1024);
+        assert!(recorded.total >= 1024);
+
+        let line = report
+            .to_string()
+            .lines()
+            .find(|line| line.starts_with("memory_per_run"))
+            .unwrap()
+            .to_string();
+        assert!(line.contains(&format!("peak={}", recorded.peak)));
+        assert!(line.contains(&format!("allocations={}", recorded.allocations)));
+
+        // and is cleared once the next run starts
+        let _rt = Runtime::new();
+        assert_eq!(usage(group), Usage::default());
+    }
+}
@@ -2,6 +2,34 @@
 #[cfg(feature = "metrics")]
 pub mod macro_support {
     pub use ::metrics::*;
+
+    /// Labels of a metric, which are extended with the tags of the current task
+    ///
+    /// The labels stay on the stack until the metric key is built. Labels passed to the macro take
+    /// precedence over tags with the same key.
+    pub struct Labels<const N: usize>([Label; N]);
+
+    pub fn labels<const N: usize>(labels: [Label; N]) -> Labels<N> {
+        Labels(labels)
+    }
+
+    impl<const N: usize> IntoLabels for Labels<N> {
+        fn into_labels(self) -> Vec<Label> {
+            let mut labels = Vec::from(self.0);
+            crate::task::info::scope::try_borrow_with(|info| {
+                let Some(info) = info else {
+                    return;
+                };
+                // returns right away for tasks without any tags
+                info.for_each_tag(|key, value| {
+                    if labels[..N].iter().all(|label| label.key() != key) {
+                        labels.push(Label::new(key.to_string(), value.to_string()));
+                    }
+                });
+            });
+            labels
+        }
+    }
 }
 
 #[macro_export]
@@ -9,7 +37,13 @@ pub mod macro_support {
 macro_rules! measure {
     ($name:literal, $value:expr $(, $key:literal = $v:expr)* $(,)?) => {
         $crate::tracing::trace!(measure = %$name, value = ?$value $(, $key = %$v)*);
-        $crate::metrics::macro_support::histogram!($name $(, $key => $v)*).record($value);
+        $crate::metrics::macro_support::histogram!(
+            $name,
+            $crate::metrics::macro_support::labels([$(
+                $crate::metrics::macro_support::Label::new($key, $v)
+            ),*])
+        )
+        .record($value);
     };
 }
 
@@ -34,7 +68,13 @@ macro_rules! count {
     };
     ($name:literal, $value:expr $(, $key:literal = $v:expr)* $(,)?) => {
         $crate::tracing::trace!(count = %$name, value = %$value $(, $key = %$v)*);
-        $crate::metrics::macro_support::counter!($name $(, $key => $v)*).increment($value);
+        $crate::metrics::macro_support::counter!(
+            $name,
+            $crate::metrics::macro_support::labels([$(
+                $crate::metrics::macro_support::Label::new($key, $v)
+            ),*])
+        )
+        .increment($value);
     };
 }
 
@@ -0,0 +1,238 @@
+//! Capture of printed output
+//!
+//! The [`print!`](crate::output::print) family of macros records each message along with the
+//! emitting task, group and simulated timestamp so output can be inspected after a run. Records
+//! are cleared when the next run starts.
+//! [`log_every!`](crate::output::log_every) limits how often a busy call site prints, based on
+//! simulated time.
+
+use crate::{
+    group::Group,
+    task::Info,
+    time::{Duration, Instant},
+};
+use core::fmt;
+use std::cell::{Cell, RefCell};
+
+thread_local! {
+    static RECORDS: RefCell<Vec<Record>> = const { RefCell::new(Vec::new()) };
+    static ECHO: Cell<bool> = const { Cell::new(true) };
+    /// Incremented at the start of each run so per-run state can be reset lazily
+    static RUN: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A message printed by a task
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// The simulated time the message was printed, if in a simulation
+    pub time: Option<Instant>,
+    /// The group of the emitting task, if any
+    pub group: Option<Group>,
+    /// The emitting task, if any
+    pub task: Option<Info>,
+    pub stream: Stream,
+    pub message: String,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(time) = self.time {
+            write!(f, "{time} ")?;
+        }
+        if let Some(group) = self.group {
+            write!(f, "[{group}] ")?;
+        }
+        if let Some(task) = &self.task {
+            if let Some(name) = task.current_name() {
+                write!(f, "{name}: ")?;
+            } else {
+                write!(f, "task {}: ", task.id())?;
+            }
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Records a message and, if enabled, echoes it to the real stream
+#[doc(hidden)]
+pub fn write(stream: Stream, args: fmt::Arguments) {
+    let message = args.to_string();
+
+    if ECHO.with(|echo| echo.get()) {
+        match stream {
+            Stream::Stdout => std::print!("{message}"),
+            Stream::Stderr => std::eprint!("{message}"),
+        }
+    }
+
+    let record = Record {
+        time: Instant::try_now(),
+        group: crate::group::scope::try_borrow_with(|group| *group),
+        // the task may be renamed after the message was written
+        task: crate::task::info::scope::try_borrow_with(|task| {
+            task.as_ref().map(|task| task.snapshot())
+        }),
+        stream,
+        message,
+    };
+
+    RECORDS.with(|records| records.borrow_mut().push(record));
+}
+
+/// Takes all of the messages that have been recorded on the current thread
+pub fn take() -> Vec<Record> {
+    RECORDS.with(|records| core::mem::take(&mut *records.borrow_mut()))
+}
+
+/// Resets the per-run state at the start of a simulation
+pub(crate) fn start_run() {
+    RUN.with(|run| run.set(run.get() + 1));
+    RECORDS.with(|records| records.borrow_mut().clear());
+}
+
+/// Controls whether recorded messages are also written to the process' `stdout`/`stderr`
+///
+/// This is enabled by default.
+pub fn set_echo(enabled: bool) {
+    ECHO.with(|echo| echo.set(enabled));
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! output_print_ {
+    ($($arg:tt)*) => {
+        $crate::output::write($crate::output::Stream::Stdout, format_args!($($arg)*))
+    };
+}
+
+pub use crate::output_print_ as print;
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! output_println_ {
+    () => {
+        $crate::output::write($crate::output::Stream::Stdout, format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write(
+            $crate::output::Stream::Stdout,
+            format_args!("{}\n", format_args!($($arg)*)),
+        )
+    };
+}
+
+pub use crate::output_println_ as println;
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! output_eprint_ {
+    ($($arg:tt)*) => {
+        $crate::output::write($crate::output::Stream::Stderr, format_args!($($arg)*))
+    };
+}
+
+pub use crate::output_eprint_ as eprint;
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! output_eprintln_ {
+    () => {
+        $crate::output::write($crate::output::Stream::Stderr, format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write(
+            $crate::output::Stream::Stderr,
+            format_args!("{}\n", format_args!($($arg)*)),
+        )
+    };
+}
+
+pub use crate::output_eprintln_ as eprintln;
+
+/// The state of a single [`log_every!`](crate::output::log_every) call site
+#[doc(hidden)]
+pub struct Every {
+    run: Cell<u64>,
+    last: Cell<Option<Instant>>,
+    suppressed: Cell<u64>,
+}
+
+impl Default for Every {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Every {
+    pub const fn new() -> Self {
+        Self {
+            run: Cell::new(0),
+            last: Cell::new(None),
+            suppressed: Cell::new(0),
+        }
+    }
+
+    pub fn write(&self, period: Duration, args: fmt::Arguments) {
+        // each run starts over
+        let run = RUN.with(Cell::get);
+        if self.run.replace(run) != run {
+            self.last.set(None);
+            self.suppressed.set(0);
+        }
+
+        // messages outside of a simulation are never limited
+        if let Some(now) = Instant::try_now() {
+            if let Some(last) = self.last.get() {
+                if now.saturating_duration_since(last) < period {
+                    self.suppressed.set(self.suppressed.get() + 1);
+                    return;
+                }
+            }
+            self.last.set(Some(now));
+        }
+
+        match self.suppressed.replace(0) {
+            0 => write(Stream::Stdout, format_args!("{args}\n")),
+            suppressed => write(
+                Stream::Stdout,
+                format_args!("{args} ({suppressed} suppressed)\n"),
+            ),
+        }
+    }
+}
+
+/// Prints a line at most once per `period` of simulated time for each call site
+///
+/// Messages in between are dropped, and the next message that is printed includes how many were
+/// suppressed. This keeps busy loops from flooding the output while still showing progress during
+/// long runs.
+///
+/// ```no_run
+/// # use bach::{ext::*, sync::{channel, queue::PopError}};
+/// # fn handle(_request: u64) {}
+/// # async fn serve(queue: channel::Receiver<u64>) -> Result<(), PopError> {
+/// loop {
+///     let request = queue.recv().await?;
+///     bach::output::log_every!(1.s(), "queue depth: {}", queue.len());
+///     # handle(request);
+/// }
+/// # }
+/// ```
+#[macro_export]
+#[doc(hidden)]
+macro_rules! output_log_every_ {
+    ($period:expr, $($arg:tt)*) => {{
+        ::std::thread_local! {
+            static SITE: $crate::output::Every = const { $crate::output::Every::new() };
+        }
+        SITE.with(|site| site.write($period, format_args!($($arg)*)))
+    }};
+}
+
+pub use crate::output_log_every_ as log_every;
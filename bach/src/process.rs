@@ -0,0 +1,312 @@
+//! Simulated host processes
+//!
+//! A [`Process`] owns a [`Group`] and runs a program that can be killed and restarted from
+//! supervisor logic. Each process has an exit status and `stdout`/`stderr` byte channels.
+
+use crate::{
+    executor::JoinHandle,
+    group::{Group, Grouped},
+    sync::{
+        channel::{Receiver, Sender},
+        queue::{vec_deque, QueueExt as _},
+    },
+};
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use event_listener_strategy::event_listener::Event;
+use pin_project_lite::pin_project;
+use std::sync::Mutex;
+
+crate::scope::define!(scope, Process);
+
+type Program = dyn Fn(Io) -> Pin<Box<dyn Future<Output = i32> + Send>> + Send + Sync;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The program returned with the given exit code
+    Code(i32),
+    /// The process was killed before the program returned
+    Killed,
+}
+
+impl ExitStatus {
+    /// Returns the exit code, if the program returned one
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Self::Code(code) => Some(*code),
+            Self::Killed => None,
+        }
+    }
+
+    /// Returns `true` if the program returned with a `0` exit code
+    pub fn success(&self) -> bool {
+        matches!(self, Self::Code(0))
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Code(code) => write!(f, "exit code: {code}"),
+            Self::Killed => write!(f, "killed"),
+        }
+    }
+}
+
+/// The standard streams of a process
+#[derive(Clone, Debug)]
+pub struct Io {
+    pub stdout: Output,
+    pub stderr: Output,
+}
+
+/// A byte stream written by a process
+#[derive(Clone, Debug)]
+pub struct Output(Sender<Vec<u8>>);
+
+impl Output {
+    /// Writes a chunk of bytes to the stream
+    ///
+    /// Bytes written after the stream is closed are discarded.
+    pub fn write<B: Into<Vec<u8>>>(&self, bytes: B) {
+        let _ = self.0.try_push(bytes.into());
+    }
+}
+
+#[derive(Clone)]
+pub struct Process(Arc<Inner>);
+
+struct Inner {
+    group: Group,
+    program: Box<Program>,
+    io: Io,
+    stdout: Receiver<Vec<u8>>,
+    stderr: Receiver<Vec<u8>>,
+    exit: Event,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    main: Option<JoinHandle<()>>,
+    tasks: Vec<JoinHandle<()>>,
+    status: Option<ExitStatus>,
+    starts: u64,
+}
+
+impl fmt::Debug for Process {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Process")
+            .field("group", &self.0.group)
+            .field("status", &self.status())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Process {
+    /// Starts a new process running `program` in the group with the given name
+    pub fn exec<P, F>(name: &str, program: P) -> Self
+    where
+        P: 'static + Fn(Io) -> F + Send + Sync,
+        F: 'static + Future<Output = i32> + Send,
+    {
+        let (stdout_tx, stdout) = vec_deque::Queue::default().channel();
+        let (stderr_tx, stderr) = vec_deque::Queue::default().channel();
+
+        let io = Io {
+            stdout: Output(stdout_tx),
+            stderr: Output(stderr_tx),
+        };
+
+        let program: Box<Program> = Box::new(move |io| Box::pin(program(io)));
+
+        let process = Self(Arc::new(Inner {
+            group: Group::new(name),
+            program,
+            io,
+            stdout,
+            stderr,
+            exit: Event::new(),
+            state: Default::default(),
+        }));
+
+        process.start();
+
+        process
+    }
+
+    /// Returns the process that the current task belongs to, if any
+    pub fn current() -> Option<Self> {
+        scope::try_borrow_with(|process| process.clone())
+    }
+
+    /// Returns the group that the process runs in
+    pub fn group(&self) -> Group {
+        self.0.group
+    }
+
+    /// Returns the exit status of the most recent run, or `None` if it's still running
+    pub fn status(&self) -> Option<ExitStatus> {
+        self.0.state.lock().unwrap().status
+    }
+
+    /// Returns the number of times the program has been started
+    pub fn starts(&self) -> u64 {
+        self.0.state.lock().unwrap().starts
+    }
+
+    /// Returns the receiving side of the process' `stdout`
+    pub fn stdout(&self) -> Receiver<Vec<u8>> {
+        self.0.stdout.clone()
+    }
+
+    /// Returns the receiving side of the process' `stderr`
+    pub fn stderr(&self) -> Receiver<Vec<u8>> {
+        self.0.stderr.clone()
+    }
+
+    /// Kills the process along with every task it spawned
+    ///
+    /// Does nothing if the program has already exited.
+    pub fn kill(&self) {
+        let (main, tasks) = {
+            let mut state = self.0.state.lock().unwrap();
+            if state.status.is_some() {
+                return;
+            }
+            state.status = Some(ExitStatus::Killed);
+            (state.main.take(), core::mem::take(&mut state.tasks))
+        };
+
+        count!("kill", "process" = self.0.group.name());
+
+        // cancel the tasks outside of the lock since dropping them may interact with the process
+        if let Some(main) = main {
+            main.cancel();
+        }
+        for task in tasks {
+            task.cancel();
+        }
+
+        self.0.exit.notify(usize::MAX);
+    }
+
+    /// Kills the process, if running, and starts the program again
+    pub fn restart(&self) {
+        self.kill();
+        self.start();
+    }
+
+    /// Waits for the current run of the program to exit
+    pub async fn wait(&self) -> ExitStatus {
+        loop {
+            let listener = self.0.exit.listen();
+
+            if let Some(status) = self.status() {
+                return status;
+            }
+
+            listener.await;
+        }
+    }
+
+    fn start(&self) {
+        let generation = {
+            let mut state = self.0.state.lock().unwrap();
+            state.status = None;
+            state.starts += 1;
+            state.starts
+        };
+
+        count!("exec", "process" = self.0.group.name());
+
+        let program = (self.0.program)(self.0.io.clone());
+        let process = self.clone();
+        let main = async move {
+            let code = program.await;
+            process.exit(generation, ExitStatus::Code(code));
+        };
+
+        let main = self.spawn_scoped(main);
+        self.0.state.lock().unwrap().main = Some(main);
+    }
+
+    fn exit(&self, generation: u64, status: ExitStatus) {
+        let tasks = {
+            let mut state = self.0.state.lock().unwrap();
+            // a newer run has replaced this one
+            if state.starts != generation || state.status.is_some() {
+                return;
+            }
+            state.status = Some(status);
+            state.main = None;
+            core::mem::take(&mut state.tasks)
+        };
+
+        // the remaining tasks are torn down along with the process
+        for task in tasks {
+            task.cancel();
+        }
+
+        self.0.exit.notify(usize::MAX);
+    }
+
+    fn spawn_scoped<F>(&self, future: F) -> JoinHandle<()>
+    where
+        F: 'static + Future<Output = ()> + Send,
+    {
+        let future = Scoped {
+            inner: Grouped::new(future, self.0.group),
+            process: self.clone(),
+        };
+        crate::task::spawn_named(future, self.0.group)
+    }
+}
+
+/// Spawns a task that belongs to the current process
+///
+/// The task is cancelled when the process is killed or its program exits.
+///
+/// # Panics
+///
+/// Panics if called outside of a process.
+pub fn spawn<F>(future: F)
+where
+    F: 'static + Future<Output = ()> + Send,
+{
+    let process = Process::current().expect("missing process in thread scope");
+    let task = process.spawn_scoped(future);
+
+    let mut state = process.0.state.lock().unwrap();
+    if state.status.is_some() {
+        drop(state);
+        task.cancel();
+        return;
+    }
+    // only keep the handles of tasks that still need to be cancelled
+    state.tasks.retain(|task| !task.is_finished());
+    state.tasks.push(task);
+}
+
+pin_project! {
+    struct Scoped<F> {
+        #[pin]
+        inner: F,
+        process: Process,
+    }
+}
+
+impl<F: Future> Future for Scoped<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let inner = this.inner;
+        scope::with(this.process.clone(), || inner.poll(cx))
+    }
+}
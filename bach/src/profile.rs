@@ -0,0 +1,259 @@
+//! Breaks down where simulated time is spent across the phases of a protocol
+//!
+//! Each [`span`] guard measures the simulated time between its creation and when it is dropped,
+//! and adds it to the totals for its label. Since the clock only advances while tasks are
+//! waiting, the totals reflect the latency of each phase rather than the wall-clock time it took
+//! to simulate.
+//!
+//! ```no_run
+//! # struct Entry;
+//! # struct Log;
+//! # impl Log {
+//! #     async fn replicate(&self, _entry: &Entry) {}
+//! #     async fn apply(&self, _entry: Entry) {}
+//! async fn commit(&self, entry: Entry) {
+//!     let span = bach::profile::span("replicate");
+//!     self.replicate(&entry).await;
+//!     drop(span);
+//!
+//!     let _span = bach::profile::span("apply");
+//!     self.apply(entry).await;
+//! }
+//! # }
+//! ```
+//!
+//! Totals are tracked per thread and reset at the start of each run. When the run finishes, the
+//! report is printed to stderr, and it's still available from [`report`] until the next run
+//! starts. Spans can be nested, in which case the time spent in the inner span is also counted
+//! towards the outer one.
+
+use crate::time::{Duration, Instant};
+use core::fmt;
+use std::{cell::RefCell, collections::BTreeMap};
+
+thread_local! {
+    static PHASES: RefCell<BTreeMap<&'static str, Phase>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Starts measuring the simulated time spent in `name` until the returned guard is dropped
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation.
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        start: Instant::now(),
+    }
+}
+
+/// Returns the accumulated time for every label that has been recorded on the current thread
+pub fn report() -> Report {
+    let phases = PHASES.with(|phases| phases.borrow().clone());
+    Report { phases }
+}
+
+/// Clears all of the accumulated time on the current thread
+pub fn reset() {
+    PHASES.with(|phases| phases.borrow_mut().clear());
+}
+
+/// Called when a run starts on the current thread
+pub(crate) fn start_run() {
+    reset();
+}
+
+/// Called when a run finishes on the current thread
+pub(crate) fn finish_run() {
+    let report = report();
+    if !report.is_empty() {
+        eprintln!("simulated time profile:\n{report}");
+    }
+}
+
+/// Records the time since its creation when dropped
+#[must_use = "the span is recorded as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Span {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the simulated time since the span was started
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Ends the span, returning the time that was recorded
+    pub fn finish(self) -> Duration {
+        self.elapsed()
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        // the runtime may have already shut down, in which case the span never finished
+        let Some(now) = Instant::try_now() else {
+            return;
+        };
+        let elapsed = now.elapsed_since_start() - self.start.elapsed_since_start();
+
+        measure!("profile_span", elapsed, "span" = self.name);
+
+        let _ = PHASES.try_with(|phases| {
+            phases
+                .borrow_mut()
+                .entry(self.name)
+                .or_default()
+                .record(elapsed);
+        });
+    }
+}
+
+/// The accumulated time for a single label
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Phase {
+    /// The number of spans that were recorded
+    pub count: u64,
+    /// The sum of the time spent in all of the spans
+    pub total: Duration,
+    /// The shortest span
+    pub min: Duration,
+    /// The longest span
+    pub max: Duration,
+}
+
+impl Phase {
+    fn record(&mut self, elapsed: Duration) {
+        self.min = if self.count == 0 {
+            elapsed
+        } else {
+            self.min.min(elapsed)
+        };
+        self.max = self.max.max(elapsed);
+        self.total += elapsed;
+        self.count += 1;
+    }
+
+    /// Returns the average time spent in each span
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        self.total / self.count as u32
+    }
+}
+
+/// A snapshot of the accumulated time for each label, ordered by name
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    phases: BTreeMap<&'static str, Phase>,
+}
+
+impl Report {
+    /// Returns the accumulated time for the given label
+    pub fn get(&self, name: &str) -> Option<&Phase> {
+        self.phases.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Phase)> + '_ {
+        self.phases.iter().map(|(name, phase)| (*name, phase))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.phases.is_empty()
+    }
+
+    /// Returns the sum of the time spent in all of the labels
+    ///
+    /// Nested spans are counted once per label, so this can be more than the elapsed time.
+    pub fn total(&self) -> Duration {
+        self.phases.values().map(|phase| phase.total).sum()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.phases.keys().map(|name| name.len()).max().unwrap_or(0);
+        for (name, phase) in &self.phases {
+            writeln!(
+                f,
+                "{name:width$}  count={} total={:?} mean={:?} min={:?} max={:?}",
+                phase.count,
+                phase.total,
+                phase.mean(),
+                phase.min,
+                phase.max,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::default::Runtime, ext::*};
+
+    #[test]
+    fn phases() {
+        Runtime::new().run(|| {
+            async {
+                for i in 1..=3u64 {
+                    let _request = span("request");
+
+                    let connect = span("connect");
+                    i.ms().sleep().await;
+                    assert_eq!(connect.finish(), i.ms());
+
+                    let _transfer = span("transfer");
+                    10.ms().sleep().await;
+                }
+            }
+            .primary()
+            .spawn();
+        });
+
+        let report = report();
+        assert_eq!(
+            report.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["connect", "request", "transfer"]
+        );
+
+        let connect = report.get("connect").unwrap();
+        assert_eq!(connect.count, 3);
+        assert_eq!(connect.total, 6.ms());
+        assert_eq!(connect.mean(), 2.ms());
+        assert_eq!(connect.min, 1.ms());
+        assert_eq!(connect.max, 3.ms());
+
+        // nested spans count towards the outer span as well
+        assert_eq!(report.get("request").unwrap().total, 36.ms());
+        assert_eq!(report.get("transfer").unwrap().total, 30.ms());
+    }
+
+    #[test]
+    fn per_run() {
+        let mut rt = Runtime::new();
+        rt.run(|| {
+            async {
+                let _span = span("first");
+                1.ms().sleep().await;
+            }
+            .primary()
+            .spawn();
+        });
+        // the report is available after the run finishes
+        drop(rt);
+        assert_eq!(report().get("first").unwrap().count, 1);
+
+        // and is cleared once the next run starts
+        let _rt = Runtime::new();
+        assert!(report().is_empty());
+    }
+}
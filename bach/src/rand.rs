@@ -10,6 +10,103 @@ use rand_xoshiro::Xoshiro256PlusPlus;
 
 pub use bolero_generator::prelude::*;
 
+/// Shuffles the slice in place with the simulation RNG
+pub fn shuffle<T>(slice: &mut [T]) {
+    AnySliceMutExt::shuffle(slice);
+}
+
+/// Returns a random element from the slice, or `None` if it's empty
+pub fn choose<T>(slice: &[T]) -> Option<&T> {
+    if slice.is_empty() {
+        return None;
+    }
+    Some(slice.pick())
+}
+
+/// Returns `amount` distinct elements from the slice, in random order
+///
+/// If `amount` is larger than the slice, all of its elements are returned.
+pub fn sample<T: Clone>(slice: &[T], amount: usize) -> Vec<T> {
+    let amount = amount.min(slice.len());
+    let mut indexes: Vec<_> = (0..slice.len()).collect();
+
+    // only the first `amount` positions need to be shuffled
+    for src in 0..amount {
+        let dst = (src..slice.len()).any();
+        indexes.swap(src, dst);
+    }
+
+    indexes[..amount]
+        .iter()
+        .map(|index| slice[*index].clone())
+        .collect()
+}
+
+/// Picks values according to their relative weights, using the simulation RNG
+///
+/// ```
+/// # use bach::{environment::default::Runtime, group::Group, rand::Weighted};
+/// # Runtime::new().run(|| {
+/// let targets = Weighted::new([(Group::new("hot"), 80), (Group::new("cold"), 20)]);
+/// let target = *targets.pick();
+/// # assert!(target == Group::new("hot") || target == Group::new("cold"));
+/// # });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Weighted<T> {
+    values: Vec<T>,
+    /// The running total of the weights, which is searched for each pick
+    cumulative: Vec<u64>,
+}
+
+impl<T> Weighted<T> {
+    /// # Panics
+    ///
+    /// Panics if there are no values with a non-zero weight.
+    pub fn new<I: IntoIterator<Item = (T, u64)>>(values: I) -> Self {
+        let mut total = 0u64;
+        let mut weighted = Self {
+            values: vec![],
+            cumulative: vec![],
+        };
+
+        for (value, weight) in values {
+            if weight == 0 {
+                continue;
+            }
+            total = total.checked_add(weight).expect("total weight overflowed");
+            weighted.values.push(value);
+            weighted.cumulative.push(total);
+        }
+
+        assert!(total > 0, "at least one value must have a non-zero weight");
+
+        weighted
+    }
+
+    pub fn pick(&self) -> &T {
+        let total = *self.cumulative.last().unwrap();
+        let target = (0..total).any();
+        let index = self.cumulative.partition_point(|sum| *sum <= target);
+        &self.values[index]
+    }
+}
+
+pub trait ShuffleExt<T> {
+    /// Returns a shuffled copy of the elements, using the simulation RNG
+    ///
+    /// Prefer this over `rand::thread_rng` so the order is reproducible from the seed.
+    fn shuffled(&self) -> Vec<T>;
+}
+
+impl<T: Clone> ShuffleExt<T> for [T] {
+    fn shuffled(&self) -> Vec<T> {
+        let mut values = self.to_vec();
+        shuffle(&mut values);
+        values
+    }
+}
+
 pub struct Scope {
     driver: Option<Box<driver::object::Object<driver::Rng<Xoshiro256PlusPlus>>>>,
 }
@@ -0,0 +1,166 @@
+//! A registry of named simulated services
+//!
+//! Crates that provide simulated versions of external dependencies, like a key-value store or an
+//! object store, register a handle to the service under a well-known name. Other components in
+//! the simulation then look it up by name instead of having the handle threaded through to them.
+//!
+//! ```no_run
+//! # use bach::ext::*;
+//! # #[derive(Clone)]
+//! # pub struct Redis;
+//! # impl Redis {
+//! #     fn new() -> (Self, impl core::future::Future<Output = ()> + Send + 'static) {
+//! #         (Self, async {})
+//! #     }
+//! #     async fn set(&self, _key: &str, _value: &str) {}
+//! # }
+//! // in the crate providing the simulated service
+//! pub fn start() {
+//!     let (handle, server) = Redis::new();
+//!     server.group("redis").spawn();
+//!     bach::services::register("redis", handle);
+//! }
+//!
+//! // in a component that depends on it
+//! # async fn component() {
+//! let redis: Redis = bach::services::wait("redis").await;
+//! redis.set("key", "value").await;
+//! # }
+//! ```
+//!
+//! Each runtime has its own registry, which is cleared when the runtime is dropped. Handles are
+//! returned by cloning, so they are usually cheap wrappers around a channel or an `Arc`.
+
+use core::any::{type_name, Any};
+use event_listener_strategy::event_listener::Event;
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// Registers `service` under `name`, replacing any service that was previously registered with
+/// that name
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation.
+pub fn register<T>(name: &str, service: T)
+where
+    T: 'static + Clone + Send + Sync,
+{
+    registry(|registry| registry.register(name, service))
+}
+
+/// Returns a handle to the service registered under `name`, if any
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation or if the service isn't a `T`.
+pub fn get<T>(name: &str) -> Option<T>
+where
+    T: 'static + Clone + Send + Sync,
+{
+    registry(|registry| registry.get(name))
+}
+
+/// Waits until a service is registered under `name` and returns a handle to it
+///
+/// This allows components to be started in any order.
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation or if the service isn't a `T`.
+pub async fn wait<T>(name: &str) -> T
+where
+    T: 'static + Clone + Send + Sync,
+{
+    loop {
+        let listener = registry(|registry| {
+            if let Some(service) = registry.get(name) {
+                return Ok(service);
+            }
+            Err(registry.registered.listen())
+        });
+
+        match listener {
+            Ok(service) => return service,
+            Err(listener) => listener.await,
+        }
+    }
+}
+
+/// Removes the service registered under `name`, returning `true` if there was one
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation.
+pub fn remove(name: &str) -> bool {
+    registry(|registry| registry.remove(name))
+}
+
+/// Returns the names of all of the registered services, sorted by name
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation.
+pub fn names() -> Vec<String> {
+    registry(|registry| registry.services.lock().unwrap().keys().cloned().collect())
+}
+
+fn registry<F: FnOnce(&Registry) -> R, R>(f: F) -> R {
+    crate::task::scope::borrow_with(|handle| f(handle.services()))
+}
+
+struct Entry {
+    type_name: &'static str,
+    service: Box<dyn Any + Send + Sync>,
+}
+
+#[derive(Default)]
+pub(crate) struct Registry {
+    services: Mutex<BTreeMap<String, Entry>>,
+    registered: Event,
+}
+
+impl Registry {
+    fn register<T>(&self, name: &str, service: T)
+    where
+        T: 'static + Clone + Send + Sync,
+    {
+        let entry = Entry {
+            type_name: type_name::<T>(),
+            service: Box::new(service),
+        };
+        let prev = self.services.lock().unwrap().insert(name.to_owned(), entry);
+        // drop the previous service outside of the lock in case it interacts with the registry
+        drop(prev);
+        self.registered.notify(usize::MAX);
+    }
+
+    fn get<T>(&self, name: &str) -> Option<T>
+    where
+        T: 'static + Clone + Send + Sync,
+    {
+        let services = self.services.lock().unwrap();
+        let entry = services.get(name)?;
+        let service = entry.service.downcast_ref::<T>().unwrap_or_else(|| {
+            panic!(
+                "service {name:?} is a {}, not a {}",
+                entry.type_name,
+                type_name::<T>()
+            )
+        });
+        Some(service.clone())
+    }
+
+    fn remove(&self, name: &str) -> bool {
+        let entry = self.services.lock().unwrap().remove(name);
+        let removed = entry.is_some();
+        // drop the service outside of the lock in case it interacts with the registry
+        drop(entry);
+        removed
+    }
+
+    /// Drops all of the services
+    pub fn clear(&self) {
+        let services = core::mem::take(&mut *self.services.lock().unwrap());
+        drop(services);
+    }
+}
@@ -0,0 +1,12 @@
+//! Deterministic stream utilities for simulations
+
+pub use futures_core::Stream;
+
+pub mod futures_ordered;
+pub mod futures_unordered;
+pub mod join;
+pub mod time;
+
+pub use futures_ordered::FuturesOrdered;
+pub use futures_unordered::FuturesUnordered;
+pub use time::StreamTimeExt;
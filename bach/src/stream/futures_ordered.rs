@@ -0,0 +1,175 @@
+//! A set of futures that yields outputs in the order the futures were pushed
+//!
+//! The futures are polled concurrently through a [`FuturesUnordered`], so the polling order
+//! follows the same rules, but outputs that complete early are held back until all of the
+//! futures pushed before them have completed.
+
+use super::FuturesUnordered;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::{FusedStream, Stream};
+use pin_project_lite::pin_project;
+use std::collections::BTreeMap;
+
+#[must_use = "streams do nothing unless polled"]
+pub struct FuturesOrdered<F: Future> {
+    futures: FuturesUnordered<Indexed<F>>,
+    completed: BTreeMap<u64, F::Output>,
+    next_push: u64,
+    next_output: u64,
+}
+
+impl<F: Future> Default for FuturesOrdered<F> {
+    fn default() -> Self {
+        Self {
+            futures: Default::default(),
+            completed: Default::default(),
+            next_push: 0,
+            next_output: 0,
+        }
+    }
+}
+
+impl<F: Future> fmt::Debug for FuturesOrdered<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FuturesOrdered")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<F: Future> FuturesOrdered<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of outputs that have yet to be yielded
+    pub fn len(&self) -> usize {
+        self.futures.len() + self.completed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a future to the end of the set
+    pub fn push(&mut self, future: F) {
+        let index = self.next_push;
+        self.next_push += 1;
+        self.futures.push(Indexed {
+            index,
+            inner: future,
+        });
+    }
+
+    /// Returns the output of the next future in push order, or `None` if the set is empty
+    pub async fn next(&mut self) -> Option<F::Output> {
+        core::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<F: Future> Unpin for FuturesOrdered<F> {}
+
+impl<F: Future> Stream for FuturesOrdered<F> {
+    type Item = F::Output;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(output) = this.completed.remove(&this.next_output) {
+                this.next_output += 1;
+                return Poll::Ready(Some(output));
+            }
+
+            match Pin::new(&mut this.futures).poll_next(cx) {
+                Poll::Ready(Some((index, output))) => {
+                    this.completed.insert(index, output);
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+impl<F: Future> FusedStream for FuturesOrdered<F> {
+    fn is_terminated(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl<F: Future> FromIterator<F> for FuturesOrdered<F> {
+    fn from_iter<T: IntoIterator<Item = F>>(iter: T) -> Self {
+        let mut futures = Self::new();
+        futures.extend(iter);
+        futures
+    }
+}
+
+impl<F: Future> Extend<F> for FuturesOrdered<F> {
+    fn extend<T: IntoIterator<Item = F>>(&mut self, iter: T) {
+        for future in iter {
+            self.push(future);
+        }
+    }
+}
+
+pin_project! {
+    struct Indexed<F> {
+        index: u64,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F: Future> Future for Indexed<F> {
+    type Output = (u64, F::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = core::task::ready!(this.inner.poll(cx));
+        Poll::Ready((*this.index, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::default::Runtime, ext::*};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn push_order() {
+        let outputs = Arc::new(Mutex::new(vec![]));
+
+        Runtime::new().run(|| {
+            let outputs = outputs.clone();
+            async move {
+                // later futures complete first
+                let mut futures: FuturesOrdered<_> = (0..5u64)
+                    .map(|i| async move {
+                        (5 - i).ms().sleep().await;
+                        i
+                    })
+                    .collect();
+
+                while let Some(output) = futures.next().await {
+                    outputs.lock().unwrap().push(output);
+                }
+            }
+            .primary()
+            .spawn();
+        });
+
+        assert_eq!(*outputs.lock().unwrap(), [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,221 @@
+//! A deterministic set of futures that yields outputs as they complete
+//!
+//! [`FuturesUnordered`] is a drop-in for `futures::stream::FuturesUnordered` with a polling order
+//! that only depends on the order futures were pushed and the simulation seed. Woken futures are
+//! polled in the order they were pushed. When the `coop` feature is enabled and the runtime has
+//! coop scheduling turned on, the order is instead shuffled with [`crate::rand`] so the different
+//! completion orders can be explored across seeds.
+
+use crate::ext::*;
+use alloc::{sync::Arc, task::Wake};
+use atomic_waker::AtomicWaker;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use futures_core::{FusedStream, Stream};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+};
+
+#[must_use = "streams do nothing unless polled"]
+pub struct FuturesUnordered<F> {
+    futures: BTreeMap<u64, Pin<Box<F>>>,
+    next_id: u64,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    ready: Mutex<BTreeSet<u64>>,
+    waker: AtomicWaker,
+}
+
+struct FutureWaker {
+    id: u64,
+    shared: Arc<Shared>,
+}
+
+impl Wake for FutureWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.shared.ready.lock().unwrap().insert(self.id);
+        self.shared.waker.wake();
+    }
+}
+
+impl<F> Default for FuturesUnordered<F> {
+    fn default() -> Self {
+        Self {
+            futures: Default::default(),
+            next_id: 0,
+            shared: Default::default(),
+        }
+    }
+}
+
+impl<F> fmt::Debug for FuturesUnordered<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FuturesUnordered")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<F> FuturesUnordered<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of futures that have yet to complete
+    pub fn len(&self) -> usize {
+        self.futures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.futures.is_empty()
+    }
+
+    /// Adds a future to the set
+    ///
+    /// The future isn't polled until the stream is.
+    pub fn push(&mut self, future: F) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.futures.insert(id, Box::pin(future));
+        // every future gets an initial poll
+        self.shared.ready.lock().unwrap().insert(id);
+    }
+
+    /// Drops all of the futures in the set
+    pub fn clear(&mut self) {
+        self.futures.clear();
+        self.shared.ready.lock().unwrap().clear();
+    }
+}
+
+impl<F: Future> FuturesUnordered<F> {
+    /// Returns the output of the next future to complete, or `None` if the set is empty
+    pub async fn next(&mut self) -> Option<F::Output> {
+        core::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    fn next_ready(&self) -> Option<u64> {
+        let mut ready = self.shared.ready.lock().unwrap();
+
+        let id = if is_shuffled() && ready.len() > 1 {
+            let index = (0..ready.len()).any();
+            ready.iter().nth(index).copied()
+        } else {
+            ready.first().copied()
+        }?;
+
+        ready.remove(&id);
+        Some(id)
+    }
+}
+
+fn is_shuffled() -> bool {
+    cfg!(feature = "coop") && crate::coop::scope::try_borrow_with(|coop| coop.is_some())
+}
+
+impl<F: Future> Stream for FuturesUnordered<F> {
+    type Item = F::Output;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // bound the amount of work done in a single poll so other tasks get a chance to run
+        let mut budget = self.futures.len();
+
+        loop {
+            if self.futures.is_empty() {
+                return Poll::Ready(None);
+            }
+
+            let Some(id) = self.next_ready() else {
+                self.shared.waker.register(cx.waker());
+
+                // check again in case a future was woken before the registration
+                if self.shared.ready.lock().unwrap().is_empty() {
+                    return Poll::Pending;
+                }
+                continue;
+            };
+
+            if budget == 0 {
+                // put the future back and yield to the executor
+                self.shared.ready.lock().unwrap().insert(id);
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            budget -= 1;
+
+            let shared = self.shared.clone();
+            let Some(future) = self.futures.get_mut(&id) else {
+                // the future has already completed
+                continue;
+            };
+
+            let waker = Waker::from(Arc::new(FutureWaker { id, shared }));
+            let mut future_cx = Context::from_waker(&waker);
+
+            if let Poll::Ready(output) = future.as_mut().poll(&mut future_cx) {
+                self.futures.remove(&id);
+                return Poll::Ready(Some(output));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+impl<F: Future> FusedStream for FuturesUnordered<F> {
+    fn is_terminated(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl<F> FromIterator<F> for FuturesUnordered<F> {
+    fn from_iter<T: IntoIterator<Item = F>>(iter: T) -> Self {
+        let mut futures = Self::new();
+        futures.extend(iter);
+        futures
+    }
+}
+
+impl<F> Extend<F> for FuturesUnordered<F> {
+    fn extend<T: IntoIterator<Item = F>>(&mut self, iter: T) {
+        for future in iter {
+            self.push(future);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tests::executor;
+
+    #[test]
+    fn push_order() {
+        let mut executor = executor();
+
+        let outputs = executor.block_on(async {
+            let mut futures: FuturesUnordered<_> = (0..10).map(|i| async move { i }).collect();
+            let mut outputs = vec![];
+            while let Some(output) = futures.next().await {
+                outputs.push(output);
+            }
+            outputs
+        });
+
+        assert_eq!(outputs, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,97 @@
+//! Scatter-gather helpers that record how each branch of a fan-out performed
+//!
+//! Every joined set is identified by a name, which is attached as the `join` label to the
+//! following metrics:
+//!
+//! * `join_branches` - the number of branches that were started
+//! * `join_branch_latency` - the time each branch took to complete
+//! * `join_latency` - the time until all of the branches completed, or the first one failed
+//! * `join_cost` - the sum of the branch latencies, i.e. the total time spent across branches
+//! * `join_failed` - the number of joins that returned an error
+//!
+//! ```no_run
+//! # use bach::stream::join;
+//! # struct Replica;
+//! # impl Replica {
+//! #     async fn request(&self, key: u64) -> Result<u64, std::io::Error> {
+//! #         Ok(key)
+//! #     }
+//! # }
+//! # async fn example(replicas: Vec<Replica>, key: u64) -> Result<(), std::io::Error> {
+//! let responses = join::try_join_all(
+//!     "fanout",
+//!     replicas.iter().map(|replica| replica.request(key)),
+//! )
+//! .await?;
+//! # let _: Vec<u64> = responses;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::FuturesUnordered;
+use crate::time::Instant;
+use core::future::Future;
+
+/// Waits for all of the futures to complete, returning their outputs in the same order
+pub async fn join_all<I>(name: &'static str, futures: I) -> Vec<<I::Item as Future>::Output>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    let res: Result<_, core::convert::Infallible> =
+        try_join_all(name, futures.into_iter().map(|f| async { Ok(f.await) })).await;
+    res.unwrap_or_else(|err| match err {})
+}
+
+/// Waits for all of the futures to complete successfully, returning their outputs in the same
+/// order
+///
+/// The first error is returned as soon as it happens and the remaining futures are dropped. Note
+/// that dropping a [`JoinHandle`](crate::executor::JoinHandle) detaches the task rather than
+/// cancelling it.
+pub async fn try_join_all<I, T, E>(name: &'static str, futures: I) -> Result<Vec<T>, E>
+where
+    I: IntoIterator,
+    I::Item: Future<Output = Result<T, E>>,
+{
+    let start = Instant::now();
+
+    let mut branches: FuturesUnordered<_> = futures
+        .into_iter()
+        .enumerate()
+        .map(|(index, future)| async move {
+            let output = future.await;
+            (index, output, start.elapsed())
+        })
+        .collect();
+
+    let len = branches.len();
+    count!("join_branches", len as u64, "join" = name);
+
+    let mut outputs: Vec<Option<T>> = (0..len).map(|_| None).collect();
+    let mut cost = crate::time::Duration::ZERO;
+
+    let res = loop {
+        let Some((index, output, latency)) = branches.next().await else {
+            break Ok(());
+        };
+
+        measure!("join_branch_latency", latency, "join" = name);
+        cost += latency;
+
+        match output {
+            Ok(output) => outputs[index] = Some(output),
+            Err(err) => break Err(err),
+        }
+    };
+
+    measure!("join_latency", start.elapsed(), "join" = name);
+    measure!("join_cost", cost, "join" = name);
+
+    if let Err(err) = res {
+        count!("join_failed", "join" = name);
+        return Err(err);
+    }
+
+    Ok(outputs.into_iter().map(|output| output.unwrap()).collect())
+}
@@ -0,0 +1,272 @@
+//! Stream adapters driven by simulated time
+
+use crate::time::{scheduler::Timer, sleep, sleep_until, Duration, Instant};
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+pub trait StreamTimeExt: Stream + Sized {
+    /// Delays items so at most one is yielded per `period`
+    ///
+    /// Items are never dropped; they are held back until the period has elapsed.
+    fn throttle(self, period: Duration) -> Throttle<Self> {
+        Throttle {
+            inner: self,
+            period,
+            delay: None,
+        }
+    }
+
+    /// Yields the most recent item only once no other items have arrived for `duration`
+    ///
+    /// A pending item is yielded immediately when the inner stream ends.
+    fn debounce(self, duration: Duration) -> Debounce<Self> {
+        Debounce {
+            inner: self,
+            duration,
+            pending: None,
+            delay: None,
+            is_done: false,
+        }
+    }
+
+    /// Collects items into batches of up to `capacity`, yielding a partial batch once `duration`
+    /// has passed since its first item arrived
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is `0`.
+    fn buffer_timeout(self, capacity: usize, duration: Duration) -> BufferTimeout<Self> {
+        assert!(capacity > 0, "capacity must be greater than 0");
+        BufferTimeout {
+            inner: self,
+            capacity,
+            duration,
+            buffer: Vec::with_capacity(capacity),
+            delay: None,
+            is_done: false,
+        }
+    }
+
+    /// Yields the most recent item at the end of every `period`, if one arrived during it
+    ///
+    /// Items that are superseded within a period are dropped. Periods are measured from the first
+    /// poll and don't drift when the consumer is slow to poll. A pending item is yielded
+    /// immediately when the inner stream ends.
+    fn sample(self, period: Duration) -> Sample<Self> {
+        Sample {
+            inner: self,
+            period,
+            latest: None,
+            deadline: None,
+            interval: None,
+            is_done: false,
+        }
+    }
+}
+
+impl<S: Stream> StreamTimeExt for S {}
+
+/// Polls the timer, clearing it once it has expired
+fn poll_delay(delay: &mut Option<Timer>, cx: &mut Context<'_>) -> Poll<()> {
+    let Some(timer) = delay.as_mut() else {
+        return Poll::Ready(());
+    };
+    ready!(Pin::new(timer).poll(cx));
+    *delay = None;
+    Poll::Ready(())
+}
+
+pin_project! {
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Throttle<S> {
+        #[pin]
+        inner: S,
+        period: Duration,
+        delay: Option<Timer>,
+    }
+}
+
+impl<S: Stream> Stream for Throttle<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        ready!(poll_delay(this.delay, cx));
+
+        let item = ready!(this.inner.poll_next(cx));
+        if item.is_some() {
+            *this.delay = Some(sleep(*this.period));
+        }
+        Poll::Ready(item)
+    }
+}
+
+pin_project! {
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Debounce<S: Stream> {
+        #[pin]
+        inner: S,
+        duration: Duration,
+        pending: Option<S::Item>,
+        delay: Option<Timer>,
+        is_done: bool,
+    }
+}
+
+impl<S: Stream> Stream for Debounce<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        while !*this.is_done {
+            match this.inner.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    // restart the quiet period with every new item
+                    *this.pending = Some(item);
+                    *this.delay = Some(sleep(*this.duration));
+                }
+                Poll::Ready(None) => {
+                    *this.is_done = true;
+                    *this.delay = None;
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        if *this.is_done {
+            return Poll::Ready(this.pending.take());
+        }
+
+        if this.pending.is_none() {
+            return Poll::Pending;
+        }
+
+        ready!(poll_delay(this.delay, cx));
+        Poll::Ready(this.pending.take())
+    }
+}
+
+pin_project! {
+    #[must_use = "streams do nothing unless polled"]
+    pub struct BufferTimeout<S: Stream> {
+        #[pin]
+        inner: S,
+        capacity: usize,
+        duration: Duration,
+        buffer: Vec<S::Item>,
+        delay: Option<Timer>,
+        is_done: bool,
+    }
+}
+
+impl<S: Stream> BufferTimeout<S> {
+    fn flush(buffer: &mut Vec<S::Item>, capacity: usize) -> Vec<S::Item> {
+        mem::replace(buffer, Vec::with_capacity(capacity))
+    }
+}
+
+impl<S: Stream> Stream for BufferTimeout<S> {
+    type Item = Vec<S::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        while !*this.is_done {
+            match this.inner.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    // the timeout starts with the first item in the batch
+                    if this.buffer.is_empty() {
+                        *this.delay = Some(sleep(*this.duration));
+                    }
+
+                    this.buffer.push(item);
+
+                    if this.buffer.len() >= *this.capacity {
+                        *this.delay = None;
+                        let batch = Self::flush(this.buffer, *this.capacity);
+                        return Poll::Ready(Some(batch));
+                    }
+                }
+                Poll::Ready(None) => {
+                    *this.is_done = true;
+                    *this.delay = None;
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        if this.buffer.is_empty() {
+            return if *this.is_done {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            };
+        }
+
+        if !*this.is_done {
+            ready!(poll_delay(this.delay, cx));
+        }
+
+        let batch = Self::flush(this.buffer, *this.capacity);
+        Poll::Ready(Some(batch))
+    }
+}
+
+pin_project! {
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Sample<S: Stream> {
+        #[pin]
+        inner: S,
+        period: Duration,
+        latest: Option<S::Item>,
+        deadline: Option<Instant>,
+        interval: Option<Timer>,
+        is_done: bool,
+    }
+}
+
+impl<S: Stream> Stream for Sample<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        while !*this.is_done {
+            match this.inner.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => *this.latest = Some(item),
+                Poll::Ready(None) => {
+                    *this.is_done = true;
+                    *this.interval = None;
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        if *this.is_done {
+            return Poll::Ready(this.latest.take());
+        }
+
+        loop {
+            let period = *this.period;
+            let deadline = *this.deadline.get_or_insert_with(|| Instant::now() + period);
+            let interval = this.interval.get_or_insert_with(|| sleep_until(deadline));
+            ready!(Pin::new(interval).poll(cx));
+
+            // the next period starts where this one ended, rather than when the consumer polls
+            *this.deadline = Some(deadline + period);
+            *this.interval = None;
+
+            if let Some(item) = this.latest.take() {
+                return Poll::Ready(Some(item));
+            }
+        }
+    }
+}
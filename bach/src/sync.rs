@@ -1,3 +1,12 @@
+pub mod broadcast;
 pub mod channel;
+pub mod condvar;
 pub mod duplex;
+pub mod lease;
+pub mod lost_wakeup;
+pub mod mutex;
 pub mod queue;
+
+pub use condvar::Condvar;
+pub use lease::Lease;
+pub use mutex::{Mutex, MutexGuard};
@@ -0,0 +1,211 @@
+//! An out-of-band control channel for coordinating tasks across groups
+//!
+//! Messages are delivered to every subscriber instantly, without going through any network
+//! model, which makes it useful for orchestrating test phases across simulated nodes.
+//!
+//! Under coop scheduling, the receive operations of a channel's subscribers conflict with each
+//! other, so the order in which subscribers that receive in the same round observe a message is
+//! explored.
+
+use super::{
+    channel,
+    queue::{
+        vec_deque::{self, Overflow},
+        PushError,
+    },
+};
+use crate::coop::Operation;
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    ops::Deref,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use std::sync::Mutex;
+
+/// Creates a new broadcast channel
+pub fn new<T: 'static + Clone + Send>() -> Sender<T> {
+    Sender(Arc::new(Inner {
+        subscribers: Mutex::new(vec![]),
+        capacity: None,
+        operation: Operation::register(),
+    }))
+}
+
+/// Creates a broadcast channel where each subscriber holds up to `capacity` messages
+///
+/// A subscriber that falls behind loses its oldest messages, like a lagging receiver of a tokio
+/// broadcast channel, so a slow subscriber never holds up the sender or the other subscribers.
+/// The number of lost messages is reported by [`Receiver::lagged`].
+///
+/// # Panics
+///
+/// Panics if `capacity` is `0`.
+pub fn bounded<T: 'static + Clone + Send>(capacity: usize) -> Sender<T> {
+    assert!(capacity > 0, "capacity must be greater than 0");
+    Sender(Arc::new(Inner {
+        subscribers: Mutex::new(vec![]),
+        capacity: Some(capacity),
+        operation: Operation::register(),
+    }))
+}
+
+/// The sending side of a broadcast channel
+///
+/// Senders can be cloned and shared with any task. Once all of the senders are dropped, the
+/// subscribers are closed after draining any remaining messages.
+pub struct Sender<T>(Arc<Inner<T>>);
+
+struct Inner<T> {
+    subscribers: Mutex<Vec<Subscriber<T>>>,
+    capacity: Option<usize>,
+    /// Links the receive operations of the subscribers so they're interleaved with each other
+    operation: Operation,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Sender<T> {
+    /// Returns the number of subscribers that have been registered
+    ///
+    /// This may include subscribers that have been dropped since the last message was sent.
+    pub fn subscriber_count(&self) -> usize {
+        self.0.subscribers.lock().unwrap().len()
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+impl<T: 'static + Clone + Send> Sender<T> {
+    /// Registers a new listener
+    ///
+    /// The listener only receives messages that are sent after it subscribes.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let queue = vec_deque::Queue::builder()
+            .with_capacity(self.0.capacity)
+            .with_overflow(Overflow::PreferRecent)
+            .build();
+        let (sender, receiver) = channel::new(queue);
+        let operation = *receiver.recv_resource();
+        operation.conflicts_with(&self.0.operation);
+
+        let lagged = Arc::new(AtomicU64::new(0));
+        self.0.subscribers.lock().unwrap().push(Subscriber {
+            sender,
+            lagged: lagged.clone(),
+        });
+
+        Receiver {
+            inner: receiver,
+            subscription: Arc::new(Subscription { lagged, operation }),
+        }
+    }
+
+    /// Sends a message to all of the current subscribers, returning the number it was
+    /// delivered to
+    pub fn send(&self, msg: T) -> usize {
+        let mut subscribers = self.0.subscribers.lock().unwrap();
+
+        // drop any subscribers that have gone away
+        subscribers.retain(|subscriber| match subscriber.sender.try_push(msg.clone()) {
+            Ok(Some(_)) => {
+                count!("broadcast_lagged");
+                subscriber.lagged.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Ok(None) | Err(PushError::Full(_)) => true,
+            Err(PushError::Closed(_)) => false,
+        });
+
+        count!("broadcast", subscribers.len() as u64);
+
+        subscribers.len()
+    }
+}
+
+struct Subscriber<T> {
+    sender: channel::Sender<T>,
+    lagged: Arc<AtomicU64>,
+}
+
+/// State shared by all of the clones of a [`Receiver`]
+struct Subscription {
+    lagged: Arc<AtomicU64>,
+    operation: Operation,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // the channel can't be received from anymore so it no longer conflicts with the others
+        self.operation.clear_conflicts();
+    }
+}
+
+pin_project! {
+    /// The receiving side of a broadcast channel
+    ///
+    /// This dereferences to a [`channel::Receiver`], which provides the methods for receiving
+    /// messages.
+    pub struct Receiver<T> {
+        #[pin]
+        inner: channel::Receiver<T>,
+        subscription: Arc<Subscription>,
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns the number of messages that this subscriber lost by falling behind since the last
+    /// call
+    ///
+    /// Messages are only lost by subscribers of a [`bounded`] channel.
+    pub fn lagged(&self) -> u64 {
+        self.subscription.lagged.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl<T> Deref for Receiver<T> {
+    type Target = channel::Receiver<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            subscription: self.subscription.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("lagged", &self.subscription.lagged.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx)
+    }
+}
@@ -1,9 +1,13 @@
 use crate::{
-    coop::Operation,
-    sync::queue::{CloseError, PopError, PushError, Queue},
+    coop::{Acquire, Operation},
+    sync::queue::{
+        vec_deque::{self, Overflow},
+        CloseError, PopError, PushError, Queue, QueueExt as _,
+    },
 };
 use alloc::sync::Arc;
 use core::{
+    cell::{Cell, RefCell},
     fmt,
     future::Future,
     marker::{PhantomData, PhantomPinned},
@@ -19,6 +23,8 @@ use event_listener_strategy::{
 use futures_core::{ready, stream::Stream};
 use pin_project_lite::pin_project;
 use std::{
+    collections::HashMap,
+    panic::Location,
     process::abort,
     task::{RawWaker, RawWakerVTable, Waker},
 };
@@ -62,6 +68,24 @@ impl<T> Channel<T> {
     }
 }
 
+impl<T> Channel<T> {
+    /// Closes the channel and pops all of the messages that are still queued
+    fn drain_remaining(&self) -> Vec<T> {
+        let _ = self.close();
+
+        let mut remaining = vec![];
+        while let Ok(msg) = self.queue.pop() {
+            remaining.push(msg);
+        }
+
+        if !remaining.is_empty() {
+            count!("channel_drained", remaining.len() as u64);
+        }
+
+        remaining
+    }
+}
+
 impl<T, Q> Channel<T, Q> {
     fn notify_after_send(&self) {
         // Notify a blocked receive operation. If the notified operation gets canceled,
@@ -116,6 +140,94 @@ where
     (sender, receiver)
 }
 
+/// Creates a channel that holds up to `capacity` messages
+///
+/// The channel is backed by a FIFO [`vec_deque::Queue`] using the [default overflow policy]. The
+/// queue's span is named after the caller's location.
+///
+/// [default overflow policy]: set_default_overflow
+#[track_caller]
+pub fn bounded<T: 'static + Send>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let name = caller_name();
+    let queue = vec_deque::Queue::builder()
+        .with_capacity(Some(capacity))
+        .with_overflow(DEFAULT_OVERFLOW.with(|v| v.get()))
+        .build();
+    new(queue.span(name))
+}
+
+/// Creates a channel without a limit on the number of messages it holds
+///
+/// The queue's span is named after the caller's location.
+#[track_caller]
+pub fn unbounded<T: 'static + Send>() -> (Sender<T>, Receiver<T>) {
+    let name = caller_name();
+    let queue = vec_deque::Queue::builder().build();
+    new(queue.span(name))
+}
+
+thread_local! {
+    static DEFAULT_OVERFLOW: Cell<Overflow> = Cell::new(Overflow::default());
+    static CALLERS: RefCell<HashMap<(&'static str, u32, u32), &'static str>> = RefCell::new(HashMap::new());
+}
+
+/// Sets the overflow policy used by [`bounded`] channels created on the current thread,
+/// returning the previous policy
+pub fn set_default_overflow(overflow: Overflow) -> Overflow {
+    DEFAULT_OVERFLOW.with(|v| v.replace(overflow))
+}
+
+#[track_caller]
+fn caller_name() -> &'static str {
+    let location = Location::caller();
+    let key = (location.file(), location.line(), location.column());
+
+    // the name is only allocated once per call site
+    CALLERS.with(|callers| {
+        *callers
+            .borrow_mut()
+            .entry(key)
+            .or_insert_with(|| Box::leak(location.to_string().into_boxed_str()))
+    })
+}
+
+/// Drives `future` to completion by parking the current thread between polls
+///
+/// The simulation runs all of its tasks on a single thread, so blocking inside of it would stall
+/// every task, including the one that would unblock the caller.
+#[track_caller]
+fn block_on_host<F: Future>(operation: &str, alternative: &str, future: F) -> F::Output {
+    if crate::is_active() {
+        let task = crate::task::info::scope::try_borrow_with(|info| {
+            info.as_ref()
+                .map(|info| format!(" in {info}"))
+                .unwrap_or_default()
+        });
+        panic!(
+            "`{operation}` was called{task} inside of the simulation, which would block the executor forever; use `{alternative}` instead",
+        );
+    }
+
+    struct Unpark(std::thread::Thread);
+
+    impl std::task::Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = core::pin::pin!(future);
+
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+            return value;
+        }
+        std::thread::park();
+    }
+}
+
 fn waker<Q, T, const IS_SEND: bool>(channel: &Arc<Channel<T, Q>>) -> Waker {
     use core::mem::ManuallyDrop;
 
@@ -206,11 +318,33 @@ impl<T> Sender<T> {
         self.push(msg).await
     }
 
+    /// Pushes a message into the channel, blocking the current thread until there is space
+    ///
+    /// This is meant for threads outside of the simulation, like code that was ported from
+    /// threads or a host thread feeding a simulation that runs on another thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from inside of a simulation, where blocking would stall the executor.
+    /// Use [`Self::send`] instead.
+    #[track_caller]
+    pub fn blocking_send(&self, msg: T) -> Result<(), PushError<T>> {
+        block_on_host("Sender::blocking_send", "send().await", self.push(msg))
+    }
+
     /// Closes the channel.
     pub fn close(&self) -> Result<(), CloseError> {
         self.channel.close()
     }
 
+    /// Closes the channel and returns the messages that were never received, in the order they
+    /// would have been received
+    ///
+    /// This is useful at the end of a scenario for checking which work was left unprocessed.
+    pub fn finish(self) -> Vec<T> {
+        self.channel.drain_remaining()
+    }
+
     /// Returns `true` if the channel is closed.
     pub fn is_closed(&self) -> bool {
         self.channel.queue.is_closed()
@@ -334,18 +468,80 @@ impl<T> Receiver<T> {
 
     /// Pops a message from the channel.
     pub async fn pop(&self) -> Result<T, PopError> {
-        self.channel.recv_resource.acquire().await;
+        self.recv().await
+    }
 
-        Pop::_new(PopInner {
-            receiver: self,
-            listener: None,
-            _pin: PhantomPinned,
-        })
-        .await
+    /// Receives a message from the channel
+    ///
+    /// The returned [`Recv`] future is cancel-safe: a message is only taken off of the channel
+    /// when the future completes, so dropping it, like when another branch of a `select!` wins,
+    /// never loses a message.
+    pub fn recv(&self) -> Recv<'_, T> {
+        let resource = &self.channel.recv_resource;
+        Recv {
+            acquire: cfg!(feature = "coop").then(|| resource.acquire()),
+            pop: Pop::_new(PopInner {
+                receiver: self,
+                listener: None,
+                _pin: PhantomPinned,
+            }),
+        }
+    }
+
+    /// Polls for the next message, registering the current task to be woken once one arrives
+    ///
+    /// This is meant for hand-written futures and state machines that multiplex several channels
+    /// within a single task. Unlike [`Self::recv`], this doesn't go through the coop scheduler.
+    /// The receiver keeps track of the registration, so each task should poll its own clone.
+    pub fn poll_recv(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, PopError>> {
+        let mut this = self;
+        loop {
+            // If this receiver is listening for events, first wait for a notification.
+            {
+                let this = this.as_mut().project();
+                if let Some(listener) = this.listener.as_mut() {
+                    ready!(Pin::new(listener).poll(cx));
+                    *this.listener = None;
+                }
+            }
+
+            loop {
+                // Attempt to receive a message.
+                match this.try_pop() {
+                    Err(PopError::Empty) => {}
+                    result => {
+                        // The receiver is not blocked on an event - drop the listener.
+                        let this = this.as_mut().project();
+                        *this.listener = None;
+                        return Poll::Ready(result);
+                    }
+                }
+
+                // Receiving failed - now start listening for notifications or wait for one.
+                let this = this.as_mut().project();
+                if this.listener.is_some() {
+                    // Go back to the outer loop to wait for a notification.
+                    break;
+                } else {
+                    *this.listener = Some(this.channel.stream_ops.listen());
+                }
+            }
+        }
     }
 
-    pub async fn recv(&self) -> Result<T, PopError> {
-        self.pop().await
+    /// Pops a message from the channel, blocking the current thread until one is available
+    ///
+    /// This is meant for threads outside of the simulation, like code that was ported from
+    /// threads or a host thread collecting the output of a simulation that runs on another
+    /// thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from inside of a simulation, where blocking would stall the executor.
+    /// Use [`Self::recv`] instead.
+    #[track_caller]
+    pub fn blocking_recv(&self) -> Result<T, PopError> {
+        block_on_host("Receiver::blocking_recv", "recv().await", self.pop())
     }
 
     /// Closes the channel.
@@ -353,6 +549,15 @@ impl<T> Receiver<T> {
         self.channel.close()
     }
 
+    /// Closes the channel and returns all of the messages that are still queued, in the order
+    /// they would have been received
+    ///
+    /// Messages that a queue is still holding back, like those in flight on a
+    /// [`latent`](crate::sync::queue::latent) queue, are not included.
+    pub fn drain_remaining(&self) -> Vec<T> {
+        self.channel.drain_remaining()
+    }
+
     /// Returns `true` if the channel is closed.
     pub fn is_closed(&self) -> bool {
         self.channel.queue.is_closed()
@@ -400,6 +605,11 @@ impl<T> Receiver<T> {
     pub fn same_channel(&self, other: &Receiver<T>) -> bool {
         Arc::ptr_eq(&self.channel, &other.channel)
     }
+
+    /// Returns the coop operation that receivers acquire before popping a message
+    pub(crate) fn recv_resource(&self) -> &Operation {
+        &self.channel.recv_resource
+    }
 }
 
 impl<T> fmt::Debug for Receiver<T> {
@@ -429,45 +639,8 @@ impl<T> Clone for Receiver<T> {
 impl<T> Stream for Receiver<T> {
     type Item = T;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        loop {
-            // If this stream is listening for events, first wait for a notification.
-            {
-                let this = self.as_mut().project();
-                if let Some(listener) = this.listener.as_mut() {
-                    ready!(Pin::new(listener).poll(cx));
-                    *this.listener = None;
-                }
-            }
-
-            loop {
-                // Attempt to receive a message.
-                match self.try_pop() {
-                    Ok(msg) => {
-                        // The stream is not blocked on an event - drop the listener.
-                        let this = self.as_mut().project();
-                        *this.listener = None;
-                        return Poll::Ready(Some(msg));
-                    }
-                    Err(PopError::Closed) => {
-                        // The stream is not blocked on an event - drop the listener.
-                        let this = self.as_mut().project();
-                        *this.listener = None;
-                        return Poll::Ready(None);
-                    }
-                    Err(PopError::Empty) => {}
-                }
-
-                // Receiving failed - now start listening for notifications or wait for one.
-                let this = self.as_mut().project();
-                if this.listener.is_some() {
-                    // Go back to the outer loop to wait for a notification.
-                    break;
-                } else {
-                    *this.listener = Some(this.channel.stream_ops.listen());
-                }
-            }
-        }
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx).map(Result::ok)
     }
 }
 
@@ -636,8 +809,43 @@ impl<'a, T> EventListenerFuture for PushInner<'a, T> {
     }
 }
 
+pin_project! {
+    /// A future returned by [`Receiver::recv()`].
+    ///
+    /// The future is cancel-safe. See [`Receiver::recv()`] for details.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Recv<'a, T> {
+        // Waits for the coop scheduler before trying to receive.
+        acquire: Option<Acquire>,
+
+        #[pin]
+        pop: Pop<'a, T>,
+    }
+}
+
+impl<T> fmt::Debug for Recv<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Recv {{ .. }}")
+    }
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Result<T, PopError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if let Some(acquire) = this.acquire.as_mut() {
+            ready!(Pin::new(acquire).poll(cx));
+            *this.acquire = None;
+        }
+
+        this.pop.poll(cx)
+    }
+}
+
 easy_wrapper! {
-    /// A future returned by [`Receiver::pop()`].
+    /// Waits for a message once a [`Recv`] future has been scheduled.
     #[derive(Debug)]
     #[must_use = "futures do nothing unless you `.await` or poll them"]
     pub struct Pop<'a, T>(PopInner<'a, T> => Result<T, PopError>);
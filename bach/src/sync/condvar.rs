@@ -0,0 +1,105 @@
+//! A condition variable for waiting on changes to state protected by a [`Mutex`]
+//!
+//! ```no_run
+//! # use bach::sync::{Condvar, Mutex};
+//! # use std::collections::VecDeque;
+//! # async fn example() {
+//! let state = Mutex::new(VecDeque::new());
+//! let not_empty = Condvar::new();
+//!
+//! // consumer
+//! let mut items = not_empty.wait_while(state.lock().await, |items| items.is_empty()).await;
+//! let item = items.pop_front().unwrap();
+//!
+//! // producer
+//! state.lock().await.push_back(item);
+//! not_empty.notify_one();
+//! # let _: u64 = item;
+//! # }
+//! ```
+
+use super::{
+    lost_wakeup,
+    mutex::{Mutex, MutexGuard},
+};
+use crate::coop::Operation;
+use core::fmt;
+use event_listener_strategy::event_listener::Event;
+
+pub struct Condvar {
+    id: u64,
+    operation: Operation,
+    waiters: Event,
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Condvar").finish_non_exhaustive()
+    }
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            id: lost_wakeup::next_id(),
+            operation: Operation::register(),
+            waiters: Event::new(),
+        }
+    }
+
+    /// Releases the lock and waits for a notification, reacquiring the lock before returning
+    ///
+    /// Like [`std::sync::Condvar`], the condition should be checked again after waking up since
+    /// another task may have changed the state before the lock was reacquired. See
+    /// [`Self::wait_while`].
+    pub async fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex: &'a Mutex<T> = MutexGuard::mutex(&guard);
+
+        // listen before releasing the lock so notifications sent in between aren't missed
+        let listener = self.waiters.listen();
+        drop(guard);
+        let waiting = lost_wakeup::waiting(self.id, "condvar");
+        listener.await;
+        drop(waiting);
+
+        // let the coop scheduler explore the order that notified tasks resume in
+        self.operation.acquire().await;
+
+        mutex.lock().await
+    }
+
+    /// Waits until `condition` returns `false`, holding the lock when it is checked
+    pub async fn wait_while<'a, T: ?Sized, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard).await;
+        }
+        guard
+    }
+
+    /// Wakes up one of the waiting tasks
+    pub fn notify_one(&self) {
+        if self.waiters.notify_additional(1) == 0 {
+            lost_wakeup::missed(self.id);
+        }
+    }
+
+    /// Wakes up all of the waiting tasks
+    pub fn notify_all(&self) {
+        if self.waiters.notify(usize::MAX) == 0 {
+            lost_wakeup::missed(self.id);
+        }
+    }
+}
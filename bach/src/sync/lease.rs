@@ -0,0 +1,448 @@
+//! Time-bounded leases for testing lease-based coordination
+//!
+//! A [`Lease`] plays the role of the authority, like a lock service, that grants exclusive
+//! ownership for a TTL of simulated time. The holder keeps its [`Grant`] alive by renewing it
+//! before the TTL runs out. If it doesn't, the authority expires the grant and hands the lease to
+//! the next node that asks.
+//!
+//! A holder can only decide whether it still owns the lease from its own view, so there's always
+//! a window between checking the grant and acting on it. If the holder is delayed in that window,
+//! like when its [`Group`] is paused or a renewal is slowed down with
+//! [`Builder::with_renewal_delay`], two nodes can end up acting as the owner at the same time.
+//! Passing the grant's epoch to [`Lease::fence`] wherever the protected resource is modified
+//! catches these split-brain writes.
+//!
+//! ```no_run
+//! # use bach::{ext::*, sync::Lease};
+//! # async fn compute() -> u64 {
+//! #     0
+//! # }
+//! # fn store(_value: u64) {}
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let lease = Lease::builder(100.ms())
+//!     .with_renewal_delay(|| if (0..10).any() == 0 { 150.ms() } else { 1.ms() })
+//!     .build();
+//!
+//! // on each node
+//! let mut grant = lease.acquire().await;
+//! while grant.renew().await.is_ok() {
+//!     grant.check()?;
+//!     let value = compute().await;
+//!
+//!     // in the storage layer
+//!     lease.fence(grant.epoch())?;
+//!     store(value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    group::{self, Group},
+    time::{sleep, sleep_until, Duration, Instant},
+};
+use alloc::sync::Arc;
+use core::fmt;
+use event_listener_strategy::event_listener::Event;
+use std::sync::Mutex;
+
+/// Called each time the authority expires a grant that wasn't renewed in time
+pub type ExpireCallback = Box<dyn Fn(&Expired) + Send + Sync>;
+
+/// Returns how long each renewal takes to reach the authority
+pub type RenewalDelay = Box<dyn FnMut() -> Duration + Send>;
+
+pub struct Builder {
+    ttl: Duration,
+    on_expire: Vec<ExpireCallback>,
+    renewal_delay: Option<RenewalDelay>,
+}
+
+impl Builder {
+    /// Registers a callback that is notified when a grant expires
+    pub fn with_on_expire<F>(mut self, f: F) -> Self
+    where
+        F: 'static + Fn(&Expired) + Send + Sync,
+    {
+        self.on_expire.push(Box::new(f));
+        self
+    }
+
+    /// Delays each renewal by the returned duration before it reaches the authority
+    ///
+    /// This models slow networks or overloaded holders. Using [`crate::rand`] in the closure gives
+    /// delays that are reproducible for a given seed.
+    pub fn with_renewal_delay<F>(mut self, f: F) -> Self
+    where
+        F: 'static + FnMut() -> Duration + Send,
+    {
+        self.renewal_delay = Some(Box::new(f));
+        self
+    }
+
+    pub fn build(self) -> Lease {
+        Lease(Arc::new(Inner {
+            ttl: self.ttl,
+            state: Mutex::new(State::default()),
+            changed: Event::new(),
+            on_expire: self.on_expire,
+            renewal_delay: self.renewal_delay.map(Mutex::new),
+        }))
+    }
+}
+
+/// The authority that grants a lease to one holder at a time
+#[derive(Clone)]
+pub struct Lease(Arc<Inner>);
+
+struct Inner {
+    ttl: Duration,
+    state: Mutex<State>,
+    changed: Event,
+    on_expire: Vec<ExpireCallback>,
+    renewal_delay: Option<Mutex<RenewalDelay>>,
+}
+
+impl Inner {
+    /// Returns the TTL rounded up to a whole tick
+    ///
+    /// The watch task can only observe the expiration on a tick, so an unaligned deadline would
+    /// leave it waiting on a timer that fires without ever reaching it.
+    fn ttl(&self) -> Duration {
+        crate::time::resolution::round_up(self.ttl)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    epoch: u64,
+    holder: Option<Holder>,
+}
+
+#[derive(Clone, Copy)]
+struct Holder {
+    group: Group,
+    epoch: u64,
+    expires: Instant,
+}
+
+impl fmt::Debug for Lease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lease")
+            .field("ttl", &self.0.ttl)
+            .field("epoch", &self.epoch())
+            .field("holder", &self.holder())
+            .finish()
+    }
+}
+
+impl Lease {
+    pub fn new(ttl: Duration) -> Self {
+        Self::builder(ttl).build()
+    }
+
+    pub fn builder(ttl: Duration) -> Builder {
+        Builder {
+            ttl,
+            on_expire: vec![],
+            renewal_delay: None,
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.0.ttl
+    }
+
+    /// Returns the epoch of the most recent grant, which increases with each grant
+    pub fn epoch(&self) -> u64 {
+        self.0.state.lock().unwrap().epoch
+    }
+
+    /// Returns the group that currently holds the lease, according to the authority
+    pub fn holder(&self) -> Option<Group> {
+        let state = self.0.state.lock().unwrap();
+        let holder = state.holder?;
+        (Instant::now() < holder.expires).then_some(holder.group)
+    }
+
+    /// Returns an error if `epoch` doesn't belong to the current, unexpired grant
+    ///
+    /// This should be called by the resource that the lease protects, using the epoch from
+    /// [`Grant::epoch`] as a fencing token. Rejected epochs are counted as `lease_split_brain`,
+    /// since they mean a holder acted after losing the lease.
+    pub fn fence(&self, epoch: u64) -> Result<(), Fenced> {
+        let holder = self.0.state.lock().unwrap().holder;
+        let current = holder
+            .filter(|holder| Instant::now() < holder.expires)
+            .map(|holder| holder.epoch);
+
+        if current == Some(epoch) {
+            return Ok(());
+        }
+
+        count!("lease_split_brain");
+        Err(Fenced { epoch, current })
+    }
+
+    /// Grants the lease to the current group if nobody else holds it
+    pub fn try_acquire(&self) -> Option<Grant> {
+        let now = Instant::now();
+        let group = group::current();
+
+        let mut state = self.0.state.lock().unwrap();
+        if state.holder.is_some_and(|holder| now < holder.expires) {
+            return None;
+        }
+
+        state.epoch += 1;
+        let epoch = state.epoch;
+        let expires = now + self.0.ttl();
+        // the previous holder may have expired without its watch task having woken up yet
+        let previous = state.holder.replace(Holder {
+            group,
+            epoch,
+            expires,
+        });
+        drop(state);
+
+        if let Some(previous) = previous {
+            self.expire(previous);
+        }
+
+        count!("lease_granted");
+        self.watch(epoch);
+
+        Some(Grant {
+            lease: self.clone(),
+            group,
+            epoch,
+            expires,
+        })
+    }
+
+    /// Waits until the lease is free and grants it to the current group
+    pub async fn acquire(&self) -> Grant {
+        loop {
+            if let Some(grant) = self.try_acquire() {
+                return grant;
+            }
+
+            let listener = self.0.changed.listen();
+
+            // check again in case the lease was released before listening
+            if let Some(grant) = self.try_acquire() {
+                return grant;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Spawns a task that expires the grant once its TTL passes without a renewal
+    fn watch(&self, epoch: u64) {
+        let lease = self.clone();
+        let watch = async move {
+            let holder = loop {
+                let expires = {
+                    let mut state = lease.0.state.lock().unwrap();
+                    match state.holder {
+                        Some(holder) if holder.epoch == epoch => {
+                            if holder.expires <= Instant::now() {
+                                state.holder = None;
+                                break holder;
+                            }
+                            holder.expires
+                        }
+                        // the grant was released or already expired by `try_acquire`
+                        _ => return,
+                    }
+                };
+
+                sleep_until(expires).await;
+            };
+
+            lease.expire(holder);
+        };
+
+        // the authority is spawned outside of the current group so it isn't frozen along with
+        // the holder
+        crate::task::scope::borrow_with(|handle| handle.spawn_daemon_named(watch, "lease_expiry"));
+    }
+
+    /// Reports that `holder` was removed after its grant expired
+    fn expire(&self, holder: Holder) {
+        let expired = Expired {
+            group: holder.group,
+            epoch: holder.epoch,
+            at: holder.expires,
+        };
+
+        count!("lease_expired");
+
+        for on_expire in &self.0.on_expire {
+            on_expire(&expired);
+        }
+
+        self.0.changed.notify(usize::MAX);
+    }
+}
+
+/// Ownership of a [`Lease`], as seen by the holder
+///
+/// Dropping the grant doesn't release the lease. It stays held until it expires, the same way a
+/// crashed holder would leave it.
+pub struct Grant {
+    lease: Lease,
+    group: Group,
+    epoch: u64,
+    expires: Instant,
+}
+
+impl fmt::Debug for Grant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Grant")
+            .field("group", &self.group)
+            .field("epoch", &self.epoch)
+            .field("expires", &self.expires)
+            .finish()
+    }
+}
+
+impl Grant {
+    /// Returns the epoch of the grant, which can be used as a fencing token
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the time that the holder believes the grant expires
+    ///
+    /// This is measured from when the holder sent its last renewal, so it never comes after the
+    /// authority's expiration.
+    pub fn expires(&self) -> Instant {
+        self.expires
+    }
+
+    /// Returns `true` if the holder believes it still holds the lease
+    pub fn believes_held(&self) -> bool {
+        Instant::now() < self.expires
+    }
+
+    /// Returns `true` if the authority still considers the grant to be held
+    pub fn is_held(&self) -> bool {
+        let state = self.lease.0.state.lock().unwrap();
+        state
+            .holder
+            .is_some_and(|holder| holder.epoch == self.epoch && Instant::now() < holder.expires)
+    }
+
+    /// Returns an error if the holder believes the grant has expired
+    ///
+    /// This only consults the holder's view, which is all that a real node has to go on. Use
+    /// [`Lease::fence`] to check the grant from the authority's side.
+    pub fn check(&self) -> Result<(), Expired> {
+        if self.believes_held() {
+            return Ok(());
+        }
+        Err(self.expired())
+    }
+
+    /// Extends the grant by the lease's TTL
+    ///
+    /// The renewal is subject to the delay configured with [`Builder::with_renewal_delay`] and
+    /// fails if the grant expired before the renewal reached the authority.
+    pub async fn renew(&mut self) -> Result<(), Expired> {
+        let sent = Instant::now();
+
+        let delay = self
+            .lease
+            .0
+            .renewal_delay
+            .as_ref()
+            .map(|delay| (delay.lock().unwrap())());
+        if let Some(delay) = delay {
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+        }
+
+        let now = Instant::now();
+        let mut state = self.lease.0.state.lock().unwrap();
+        match state.holder.as_mut() {
+            Some(holder) if holder.epoch == self.epoch && now < holder.expires => {
+                holder.expires = now + self.lease.0.ttl();
+                self.expires = sent + self.lease.0.ttl;
+                Ok(())
+            }
+            _ => {
+                drop(state);
+                count!("lease_renewal_failed");
+                Err(self.expired())
+            }
+        }
+    }
+
+    /// Gives up the lease so another holder can acquire it immediately
+    pub fn release(self) {
+        let mut state = self.lease.0.state.lock().unwrap();
+        if state
+            .holder
+            .is_some_and(|holder| holder.epoch == self.epoch)
+        {
+            state.holder = None;
+            drop(state);
+            self.lease.0.changed.notify(usize::MAX);
+        }
+    }
+
+    fn expired(&self) -> Expired {
+        Expired {
+            group: self.group,
+            epoch: self.epoch,
+            at: self.expires,
+        }
+    }
+}
+
+/// A grant that is no longer held
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Expired {
+    /// The group that held the grant
+    pub group: Group,
+    /// The epoch of the grant
+    pub epoch: u64,
+    /// The time that the grant expired, according to whoever reported it
+    pub at: Instant,
+}
+
+impl std::error::Error for Expired {}
+
+impl fmt::Display for Expired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lease epoch {} held by {} expired at {}",
+            self.epoch, self.group, self.at
+        )
+    }
+}
+
+/// A stale epoch that was rejected by [`Lease::fence`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fenced {
+    /// The epoch that was rejected
+    pub epoch: u64,
+    /// The epoch of the current grant, if the lease is held
+    pub current: Option<u64>,
+}
+
+impl std::error::Error for Fenced {}
+
+impl fmt::Display for Fenced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lease epoch {} was fenced", self.epoch)?;
+        if let Some(current) = self.current {
+            write!(f, " by epoch {current}")?;
+        }
+        Ok(())
+    }
+}
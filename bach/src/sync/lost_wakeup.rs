@@ -0,0 +1,176 @@
+//! Detection of lost wakeups on stateless notification primitives
+//!
+//! A [`Condvar`](super::Condvar) notification only reaches the tasks that are already waiting.
+//! If a task notifies before the other side starts waiting, and the waiter doesn't check a
+//! condition first, the waiter blocks forever. Without any extra information, this shows up as
+//! a stalled runtime with no hint about where the wakeup went.
+//!
+//! When enabled with
+//! [`Runtime::with_lost_wakeup_detection`](crate::environment::default::Runtime::with_lost_wakeup_detection),
+//! notifications that found no waiters are recorded along with the tasks that are currently
+//! waiting. Any waiter that started waiting after such a notification on the same primitive is
+//! reported as a [`LostWakeup`], both in the stall panic and through
+//! [`Runtime::lost_wakeups`](crate::environment::default::Runtime::lost_wakeups).
+//!
+//! Channels, mutexes and other primitives that keep state between notifications can't lose
+//! wakeups this way, so they aren't tracked.
+
+use crate::{task::Info, time::Instant};
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// Returns a unique id for a tracked primitive
+pub(crate) fn next_id() -> u64 {
+    static IDS: AtomicU64 = AtomicU64::new(0);
+    IDS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Records a notification that didn't reach any waiters
+pub(crate) fn missed(primitive: u64) {
+    with_tracker(|tracker| {
+        let notification = Notification {
+            at: Instant::now(),
+            task: current_task(),
+        };
+        tracker
+            .state
+            .lock()
+            .unwrap()
+            .missed
+            .insert(primitive, notification);
+    });
+}
+
+/// Records that the current task is waiting on the primitive until the returned guard is dropped
+pub(crate) fn waiting(primitive: u64, kind: &'static str) -> Option<Waiting> {
+    with_tracker(|tracker| {
+        let task = current_task()?;
+        let key = (primitive, task.id());
+        let waiter = Waiter {
+            kind,
+            task,
+            since: Instant::now(),
+        };
+        tracker.state.lock().unwrap().waiting.insert(key, waiter);
+        Some(Waiting {
+            tracker: tracker.clone(),
+            key,
+        })
+    })
+    .flatten()
+}
+
+fn with_tracker<F: FnOnce(&alloc::sync::Arc<Tracker>) -> R, R>(f: F) -> Option<R> {
+    crate::task::scope::try_borrow_with(|handle| {
+        let tracker = handle.as_ref()?.lost_wakeup_tracker();
+        if !tracker.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(f(tracker))
+    })
+}
+
+fn current_task() -> Option<Info> {
+    crate::task::info::scope::try_borrow_with(|info| info.clone())
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Tracker {
+    enabled: AtomicBool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The most recent notification on each primitive that didn't reach any waiters
+    missed: BTreeMap<u64, Notification>,
+    waiting: BTreeMap<(u64, u64), Waiter>,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    kind: &'static str,
+    task: Info,
+    since: Instant,
+}
+
+impl Tracker {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the waiters that started waiting after a notification on the same primitive
+    /// found nobody to wake
+    pub fn lost(&self) -> Vec<LostWakeup> {
+        let state = self.state.lock().unwrap();
+        state
+            .waiting
+            .iter()
+            .filter_map(|((primitive, _), waiter)| {
+                let notification = state.missed.get(primitive)?;
+                if notification.at > waiter.since {
+                    return None;
+                }
+                Some(LostWakeup {
+                    primitive: waiter.kind,
+                    waiter: waiter.task.clone(),
+                    waiting_since: waiter.since,
+                    notification: notification.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Stops tracking a waiter once it's woken or dropped
+pub(crate) struct Waiting {
+    tracker: alloc::sync::Arc<Tracker>,
+    key: (u64, u64),
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        let waiter = self.tracker.state.lock().unwrap().waiting.remove(&self.key);
+        drop(waiter);
+    }
+}
+
+/// A notification that didn't reach any waiters
+#[derive(Clone, Debug)]
+pub struct Notification {
+    /// The time the notification was sent
+    pub at: Instant,
+    /// The task that sent the notification, if it was sent from a task
+    pub task: Option<Info>,
+}
+
+/// A task that is waiting for a notification that was already sent
+#[derive(Clone, Debug)]
+pub struct LostWakeup {
+    /// The kind of primitive being waited on, like `"condvar"`
+    pub primitive: &'static str,
+    /// The task that is still waiting
+    pub waiter: Info,
+    /// The time the task started waiting
+    pub waiting_since: Instant,
+    /// The most recent notification on the primitive that found no waiters
+    pub notification: Notification,
+}
+
+impl fmt::Display for LostWakeup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} started waiting on a {} at {}, but the notification from ",
+            self.waiter, self.primitive, self.waiting_since
+        )?;
+        match &self.notification.task {
+            Some(task) => write!(f, "{task}")?,
+            None => write!(f, "outside of a task")?,
+        }
+        write!(f, " at {} found no waiters", self.notification.at)
+    }
+}
@@ -0,0 +1,247 @@
+//! A mutual exclusion lock for sharing state between tasks
+//!
+//! Unlike [`std::sync::Mutex`], waiting for the lock yields to the executor instead of blocking
+//! the thread, so a task can hold the guard across `.await` points. When the `coop` feature is
+//! enabled, the order in which competing tasks acquire the lock is explored by the coop
+//! scheduler.
+//!
+//! Poisoning is opt-in through [`Mutex::with_poisoning`]. Once enabled, a task that panics while
+//! holding the lock marks it as poisoned, which [`Mutex::lock_checked`] reports so recovery paths
+//! can be tested deterministically. A panic ends the simulation unless the task was spawned with
+//! [`CatchUnwindExt::catch_unwind`](crate::ext::CatchUnwindExt::catch_unwind), which lets the
+//! other tasks observe the poisoned lock.
+
+use crate::coop::Operation;
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use event_listener_strategy::event_listener::Event;
+use std::sync::{LockResult, PoisonError};
+
+pub struct Mutex<T: ?Sized> {
+    operation: Operation,
+    locked: AtomicBool,
+    poisoning: bool,
+    poisoned: AtomicBool,
+    unlocked: Event,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+        match self.try_lock() {
+            Some(guard) => d.field("value", &&*guard),
+            None => d.field("value", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T> Mutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            operation: Operation::register(),
+            locked: AtomicBool::new(false),
+            poisoning: false,
+            poisoned: AtomicBool::new(false),
+            unlocked: Event::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Marks the lock as poisoned when a task panics while holding it
+    pub fn with_poisoning(mut self) -> Self {
+        self.poisoning = true;
+        self
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the lock, waiting until it is released if another task is holding it
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        self.operation.acquire().await;
+
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+
+            let listener = self.unlocked.listen();
+
+            // check again in case the lock was released before listening
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Acquires the lock, returning an error if a previous holder panicked
+    ///
+    /// The guard is still available through [`PoisonError::into_inner`] to recover the state.
+    pub async fn lock_checked(&self) -> LockResult<MutexGuard<'_, T>> {
+        let guard = self.lock().await;
+        if self.is_poisoned() {
+            return Err(PoisonError::new(guard));
+        }
+        Ok(guard)
+    }
+
+    /// Acquires the lock if it isn't currently held
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard {
+                mutex: self,
+                panicking: std::thread::panicking(),
+            })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if a task panicked while holding the lock
+    ///
+    /// This is always `false` unless poisoning was enabled with [`Mutex::with_poisoning`].
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Clears the poisoned state after the protected value has been recovered
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns a mutable reference to the value
+    ///
+    /// No locking is needed since the borrow guarantees exclusive access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Holds the lock until it is dropped
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    /// Whether the thread was already panicking when the lock was acquired
+    panicking: bool,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Returns the mutex that the guard is holding
+    pub fn mutex(guard: &Self) -> &'a Mutex<T> {
+        guard.mutex
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.mutex.poisoning && !self.panicking && std::thread::panicking() {
+            count!("mutex_poisoned");
+            self.mutex.poisoned.store(true, Ordering::Relaxed);
+        }
+        self.mutex.locked.store(false, Ordering::Release);
+        self.mutex.unlocked.notify(1);
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::default::Runtime, executor::JoinHandle, ext::*};
+    use alloc::sync::Arc;
+
+    /// Spawns a task that panics while holding the lock across an `.await` point
+    fn panic_while_locked(mutex: &Arc<Mutex<u32>>) -> JoinHandle<std::thread::Result<()>> {
+        let mutex = mutex.clone();
+        async move {
+            let mut value = mutex.lock().await;
+            *value += 1;
+            1.ms().sleep().await;
+            panic!("oops");
+        }
+        .catch_unwind()
+        .spawn()
+    }
+
+    #[test]
+    fn poisoning_is_opt_in() {
+        let mutex = Arc::new(Mutex::new(0));
+
+        Runtime::new().run(|| {
+            let panicked = panic_while_locked(&mutex);
+            async move {
+                assert!(panicked.await.is_err());
+                assert!(!mutex.is_locked());
+                assert!(!mutex.is_poisoned());
+                assert_eq!(*mutex.lock_checked().await.unwrap(), 1);
+            }
+            .primary()
+            .spawn();
+        });
+    }
+
+    #[test]
+    fn poisoning() {
+        let mutex = Arc::new(Mutex::new(0).with_poisoning());
+
+        Runtime::new().run(|| {
+            let panicked = panic_while_locked(&mutex);
+            async move {
+                // wait on the lock while the other task is holding it
+                1.us().sleep().await;
+                let guard = mutex.lock_checked().await.unwrap_err();
+                assert_eq!(*guard.into_inner(), 1);
+                assert!(panicked.await.is_err());
+
+                mutex.clear_poison();
+                assert!(mutex.lock_checked().await.is_ok());
+            }
+            .primary()
+            .spawn();
+        });
+    }
+}
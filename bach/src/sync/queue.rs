@@ -1,11 +1,22 @@
-use crate::{sync::channel, time::Instant};
-use core::fmt;
-use std::{sync::Arc, task::Context};
-
+use crate::{
+    sync::channel,
+    time::{Duration, Instant},
+};
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+use std::{collections::hash_map::DefaultHasher, sync::Arc, task::Context};
+
+pub mod congestion;
+pub mod conserve;
 pub mod latent;
+pub mod loss;
+pub mod occupancy;
 pub mod priority;
 pub mod sojourn;
 pub mod span;
+pub mod throughput;
 pub mod vec_deque;
 
 pub trait Queue<T> {
@@ -72,12 +83,67 @@ pub trait Conditional<T>: Queue<T> {
     fn find_pop<F: Fn(&T) -> bool>(&self, check: F) -> Result<T, PopError>;
 }
 
+/// A queue whose pending items can be inspected without removing them
+///
+/// This is useful for asserting the exact set of pending work at interesting points of a
+/// simulation, e.g. with `insta` snapshots.
+pub trait Snapshot<T>: Queue<T> {
+    /// Returns a copy of the pending items, in the order they would be popped
+    fn snapshot(&self) -> Vec<T>;
+
+    /// Returns a digest of the pending items
+    ///
+    /// The digest is stable across runs of the same build, so it can be compared against a
+    /// recorded value when the items are too large to snapshot.
+    fn digest(&self) -> u64
+    where
+        T: Hash,
+    {
+        let mut hasher = DefaultHasher::new();
+        self.snapshot().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl<T, Q> Snapshot<T> for Arc<Q>
+where
+    Q: Snapshot<T>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.as_ref().snapshot()
+    }
+}
+
 pub trait QueueExt<T>: 'static + Queue<T> + Sized + Send + Sync {
     #[inline]
     fn span(self, name: &'static str) -> span::Queue<Self> {
         span::Queue::new(self, name)
     }
 
+    /// Publishes the queue's occupancy and drops to subscribers
+    #[inline]
+    fn congestion(self) -> congestion::Queue<Self> {
+        congestion::Queue::new(self)
+    }
+
+    /// Panics if the queue loses a message instead of delivering it or reporting it as dropped
+    #[inline]
+    fn conserve(self, name: &'static str) -> conserve::Queue<Self> {
+        conserve::Queue::new(self, name)
+    }
+
+    /// Drops each pushed item with the given `probability`, using the simulation RNG
+    #[inline]
+    fn loss(self, probability: f64) -> loss::Queue<Self> {
+        loss::Queue::new(self, probability)
+    }
+
+    /// Records the number of items popped from the queue in each `interval`
+    #[inline]
+    fn throughput(self, name: &'static str, interval: Duration) -> throughput::Queue<T, Self> {
+        throughput::Queue::new(self, name, interval)
+    }
+
     #[inline]
     fn channel(self) -> (channel::Sender<T>, channel::Receiver<T>) {
         channel::new(self)
@@ -92,6 +158,12 @@ pub trait InstantQueueExt<T>: 'static + Queue<(Instant, T)> + Sized + Send + Syn
         sojourn::Queue::new(self)
     }
 
+    /// Tracks the time-weighted queue length, arrival rate, and sojourn time
+    #[inline]
+    fn occupancy(self) -> occupancy::Queue<T, Self> {
+        occupancy::Queue::new(self)
+    }
+
     #[inline]
     fn latent<L>(self, latency: L) -> latent::Queue<T, Self, L>
     where
@@ -0,0 +1,225 @@
+//! Explicit congestion feedback from a bottleneck queue
+//!
+//! Transports built on top of simulated links usually have to infer congestion from loss or
+//! delay. Wrapping the bottleneck queue instead publishes a [`Signal`] each time its occupancy
+//! changes or an item is dropped, so experimental transports can react to explicit feedback.
+//!
+//! ```no_run
+//! # use bach::{
+//! #     environment::default::Runtime,
+//! #     ext::*,
+//! #     sync::queue::vec_deque::{self, Overflow},
+//! # };
+//! # use std::sync::Arc;
+//! # type Packet = Vec<u8>;
+//! # struct Window;
+//! # impl Window {
+//! #     fn halve(&mut self) {}
+//! # }
+//! # Runtime::new().run(|| {
+//! # let mut window = Window;
+//! let queue = Arc::new(
+//!     vec_deque::Queue::builder()
+//!         .with_capacity(Some(16))
+//!         .with_overflow(Overflow::PreferRecent)
+//!         .build::<Packet>()
+//!         .congestion(),
+//! );
+//! let feedback = queue.subscribe();
+//! let (sender, receiver) = queue.channel();
+//!
+//! async move {
+//!     while let Ok(signal) = feedback.recv().await {
+//!         if signal.is_dropped() || signal.utilization() > 0.8 {
+//!             window.halve();
+//!         }
+//!     }
+//! }
+//! .spawn();
+//! # drop((sender, receiver));
+//! # });
+//! ```
+
+use super::{CloseError, PopError, PushError};
+use crate::sync::broadcast::{self, Receiver};
+use core::fmt;
+use std::task::Context;
+
+/// A change in the state of the queue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// The number of queued items changed
+    Occupancy { len: usize, capacity: Option<usize> },
+    /// An item was displaced because the queue was full
+    Dropped { len: usize, capacity: Option<usize> },
+}
+
+impl Signal {
+    /// Returns the number of queued items after the change
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Occupancy { len, .. } | Self::Dropped { len, .. } => *len,
+        }
+    }
+
+    /// Returns `true` if the queue was empty after the change
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        match self {
+            Self::Occupancy { capacity, .. } | Self::Dropped { capacity, .. } => *capacity,
+        }
+    }
+
+    /// Returns `true` if an item was dropped
+    pub fn is_dropped(&self) -> bool {
+        matches!(self, Self::Dropped { .. })
+    }
+
+    /// Returns the fraction of the capacity that is in use, or `0.0` for unbounded queues
+    pub fn utilization(&self) -> f64 {
+        match self.capacity() {
+            Some(capacity) => self.len() as f64 / capacity as f64,
+            None => 0.0,
+        }
+    }
+}
+
+pub struct Queue<Q> {
+    inner: Q,
+    signals: broadcast::Sender<Signal>,
+}
+
+impl<Q: Default> Default for Queue<Q> {
+    fn default() -> Self {
+        Self::new(Q::default())
+    }
+}
+
+impl<Q: fmt::Debug> fmt::Debug for Queue<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<Q> Queue<Q> {
+    pub fn new(inner: Q) -> Self {
+        Self {
+            inner,
+            signals: broadcast::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+
+    /// Returns a receiver for the signals published after it subscribes
+    ///
+    /// Signals are delivered without any delay. Subscribers that need to model the time it
+    /// takes for feedback to reach the sender should sleep after receiving them.
+    pub fn subscribe(&self) -> Receiver<Signal> {
+        self.signals.subscribe()
+    }
+
+    fn occupancy<T>(&self)
+    where
+        Q: super::Queue<T>,
+    {
+        self.signals.send(Signal::Occupancy {
+            len: self.inner.len(),
+            capacity: self.inner.capacity(),
+        });
+    }
+
+    fn pushed<T>(&self, result: &Result<Option<T>, PushError<T>>)
+    where
+        Q: super::Queue<T>,
+    {
+        match result {
+            Ok(None) => self.occupancy(),
+            Ok(Some(_)) => {
+                self.signals.send(Signal::Dropped {
+                    len: self.inner.len(),
+                    capacity: self.inner.capacity(),
+                });
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+impl<T, Q> super::Queue<T> for Queue<Q>
+where
+    Q: super::Queue<T>,
+{
+    fn push(&self, value: T) -> Result<Option<T>, PushError<T>> {
+        let result = self.inner.push(value);
+        self.pushed(&result);
+        result
+    }
+
+    fn push_with_context(&self, value: T, cx: &mut Context) -> Result<Option<T>, PushError<T>> {
+        let result = self.inner.push_with_context(value, cx);
+        self.pushed(&result);
+        result
+    }
+
+    fn pop(&self) -> Result<T, PopError> {
+        let value = self.inner.pop()?;
+        self.occupancy();
+        Ok(value)
+    }
+
+    fn pop_with_context(&self, cx: &mut Context) -> Result<T, PopError> {
+        let value = self.inner.pop_with_context(cx)?;
+        self.occupancy();
+        Ok(value)
+    }
+
+    fn close(&self) -> Result<(), CloseError> {
+        self.inner.close()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+}
+
+impl<T, Q> super::Conditional<T> for Queue<Q>
+where
+    Q: super::Conditional<T>,
+{
+    fn find_pop<F: Fn(&T) -> bool>(&self, check: F) -> Result<T, PopError> {
+        let value = self.inner.find_pop(check)?;
+        self.occupancy();
+        Ok(value)
+    }
+}
+
+impl<T, Q> super::Snapshot<T> for Queue<Q>
+where
+    Q: super::Snapshot<T>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.inner.snapshot()
+    }
+}
@@ -0,0 +1,276 @@
+//! Checks that a queue accounts for every message pushed into it
+//!
+//! A message that is accepted by the queue must either be popped or reported as dropped through
+//! the `push` return value. Anything else means the queue lost it, which is usually a bug in a
+//! custom [`Queue`](super::Queue) implementation.
+
+use super::{CloseError, Conditional, PopError, PushError};
+use core::{
+    fmt, ops,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::task::Context;
+
+pub struct Queue<Q> {
+    name: &'static str,
+    inner: Q,
+    ledger: Ledger,
+}
+
+#[derive(Default)]
+struct Ledger {
+    pushed: AtomicU64,
+    popped: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl<Q: Default> Default for Queue<Q> {
+    fn default() -> Self {
+        Self::new(Q::default(), "")
+    }
+}
+
+impl<Q: fmt::Debug> fmt::Debug for Queue<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<Q> ops::Deref for Queue<Q> {
+    type Target = Q;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<Q> Queue<Q> {
+    pub fn new(inner: Q, name: &'static str) -> Self {
+        Self {
+            name,
+            inner,
+            ledger: Ledger::default(),
+        }
+    }
+
+    /// Returns the number of messages that were accepted by the queue
+    pub fn pushed(&self) -> u64 {
+        self.ledger.pushed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of messages that were popped from the queue
+    pub fn popped(&self) -> u64 {
+        self.ledger.popped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of accepted messages that the queue reported as dropped
+    pub fn dropped(&self) -> u64 {
+        self.ledger.dropped.load(Ordering::Relaxed)
+    }
+
+    fn on_push<T>(&self, res: &Result<Option<T>, PushError<T>>) {
+        if let Ok(prev) = res {
+            self.ledger.pushed.fetch_add(1, Ordering::Relaxed);
+            if prev.is_some() {
+                self.ledger.dropped.fetch_add(1, Ordering::Relaxed);
+                count!("conserve_dropped", "queue" = self.name);
+            }
+        }
+    }
+
+    fn on_pop<T>(&self, res: &Result<T, PopError>) {
+        if res.is_ok() {
+            self.ledger.popped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn check<T>(&self)
+    where
+        Q: super::Queue<T>,
+    {
+        let pushed = self.pushed();
+        let popped = self.popped();
+        let dropped = self.dropped();
+        let queued = self.inner.len() as u64;
+
+        let accounted = popped + dropped + queued;
+
+        if pushed != accounted && !std::thread::panicking() {
+            panic!(
+                "queue {:?} did not conserve messages: pushed={pushed} popped={popped} dropped={dropped} queued={queued}",
+                self.name
+            );
+        }
+    }
+}
+
+impl<T, Q> super::Queue<T> for Queue<Q>
+where
+    Q: super::Queue<T>,
+{
+    fn push(&self, value: T) -> Result<Option<T>, PushError<T>> {
+        let res = self.inner.push(value);
+        self.on_push(&res);
+        self.check::<T>();
+        res
+    }
+
+    fn push_with_context(&self, value: T, cx: &mut Context) -> Result<Option<T>, PushError<T>> {
+        let res = self.inner.push_with_context(value, cx);
+        self.on_push(&res);
+        self.check::<T>();
+        res
+    }
+
+    fn pop(&self) -> Result<T, PopError> {
+        let res = self.inner.pop();
+        self.on_pop(&res);
+        self.check::<T>();
+        res
+    }
+
+    fn pop_with_context(&self, cx: &mut Context) -> Result<T, PopError> {
+        let res = self.inner.pop_with_context(cx);
+        self.on_pop(&res);
+        self.check::<T>();
+        res
+    }
+
+    fn close(&self) -> Result<(), CloseError> {
+        self.inner.close()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+}
+
+impl<T, Q> Conditional<T> for Queue<Q>
+where
+    Q: Conditional<T>,
+{
+    fn find_pop<F: Fn(&T) -> bool>(&self, check: F) -> Result<T, PopError> {
+        let res = self.inner.find_pop(check);
+        self.on_pop(&res);
+        self.check::<T>();
+        res
+    }
+}
+
+impl<T, Q> super::Snapshot<T> for Queue<Q>
+where
+    Q: super::Snapshot<T>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.inner.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::queue::{
+        vec_deque::{self, Overflow},
+        Queue as _, QueueExt as _,
+    };
+    use std::sync::Mutex;
+
+    #[test]
+    fn overflow_is_accounted() {
+        let queue = vec_deque::Queue::builder()
+            .with_capacity(Some(2))
+            .with_overflow(Overflow::PreferRecent)
+            .build()
+            .conserve("overflow");
+
+        for i in 0..5 {
+            queue.push(i).unwrap();
+        }
+        assert!(queue.pop().is_ok());
+
+        assert_eq!(queue.pushed(), 5);
+        assert_eq!(queue.popped(), 1);
+        assert_eq!(queue.dropped(), 3);
+    }
+
+    /// A queue that silently discards every other message
+    #[derive(Default)]
+    struct Lossy(Mutex<(bool, vec_deque::Queue<u32>)>);
+
+    impl super::super::Queue<u32> for Lossy {
+        fn push(&self, value: u32) -> Result<Option<u32>, PushError<u32>> {
+            let mut inner = self.0.lock().unwrap();
+            inner.0 = !inner.0;
+            if inner.0 {
+                inner.1.push(value)
+            } else {
+                Ok(None)
+            }
+        }
+
+        fn push_with_context(
+            &self,
+            value: u32,
+            _cx: &mut Context,
+        ) -> Result<Option<u32>, PushError<u32>> {
+            self.push(value)
+        }
+
+        fn pop(&self) -> Result<u32, PopError> {
+            self.0.lock().unwrap().1.pop()
+        }
+
+        fn pop_with_context(&self, _cx: &mut Context) -> Result<u32, PopError> {
+            self.pop()
+        }
+
+        fn close(&self) -> Result<(), CloseError> {
+            self.0.lock().unwrap().1.close()
+        }
+
+        fn is_closed(&self) -> bool {
+            self.0.lock().unwrap().1.is_closed()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.lock().unwrap().1.is_empty()
+        }
+
+        fn is_full(&self) -> bool {
+            self.0.lock().unwrap().1.is_full()
+        }
+
+        fn len(&self) -> usize {
+            self.0.lock().unwrap().1.len()
+        }
+
+        fn capacity(&self) -> Option<usize> {
+            None
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "queue \"lossy\" did not conserve messages")]
+    fn lost_message() {
+        let queue = Lossy::default().conserve("lossy");
+        queue.push(1).unwrap();
+        queue.push(2).unwrap();
+    }
+}
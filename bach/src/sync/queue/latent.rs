@@ -1,10 +1,10 @@
 use super::{CloseError, PopError, PushError};
 use crate::{
     ext::*,
-    time::{Duration, Instant},
+    time::{resolution, Duration, Instant},
     tracing::{debug_span, Instrument},
 };
-use std::{marker::PhantomData, task::Context};
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc, task::Context};
 
 pub trait Latency<T> {
     fn for_value(&self, value: &T) -> Duration;
@@ -16,6 +16,151 @@ impl<T> Latency<T> for Duration {
     }
 }
 
+impl<T, L: Latency<T>> Latency<T> for Arc<L> {
+    fn for_value(&self, value: &T) -> Duration {
+        self.as_ref().for_value(value)
+    }
+}
+
+pub trait LatencyExt<T>: Latency<T> + Sized {
+    /// Adds a uniformly-distributed random delay of up to `max` to each value
+    ///
+    /// The delay is a whole number of ticks, since the timer can't resolve anything finer.
+    fn with_jitter(self, max: Duration) -> Jitter<Self> {
+        Jitter { inner: self, max }
+    }
+
+    /// Adds the latency of `other` to each value
+    ///
+    /// This can be used to combine a propagation delay with a size-based transmission delay.
+    fn plus<L: Latency<T>>(self, other: L) -> Plus<Self, L> {
+        Plus(self, other)
+    }
+}
+
+impl<T, L: Latency<T>> LatencyExt<T> for L {}
+
+/// Computes the latency of each value with a function
+pub fn from_fn<T, F: Fn(&T) -> Duration>(f: F) -> FromFn<F> {
+    FromFn(f)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FromFn<F>(F);
+
+impl<T, F: Fn(&T) -> Duration> Latency<T> for FromFn<F> {
+    fn for_value(&self, value: &T) -> Duration {
+        (self.0)(value)
+    }
+}
+
+/// Selects the latency for each value with a key from its metadata, e.g. its destination
+///
+/// Values with a key that hasn't been configured with [`PerKey::with`] use `default`.
+///
+/// ```no_run
+/// # use bach::{ext::*, sync::queue::latent::{self, LatencyExt as _}};
+/// # use std::net::SocketAddr;
+/// # type Bytes = Vec<u8>;
+/// # let us_east: SocketAddr = "10.0.0.1:443".parse().unwrap();
+/// # let eu_west: SocketAddr = "10.1.0.1:443".parse().unwrap();
+/// let latency = latent::per_key(|(addr, _): &(SocketAddr, Bytes)| *addr, 10.ms())
+///     .with(us_east, 40.ms())
+///     .with(eu_west, 90.ms())
+///     .with_jitter(2.ms());
+/// ```
+pub fn per_key<T, K, F, L>(key: F, default: L) -> PerKey<K, F, L>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+    L: Latency<T>,
+{
+    PerKey {
+        key,
+        latencies: BTreeMap::new(),
+        default,
+    }
+}
+
+pub struct PerKey<K, F, L> {
+    key: F,
+    latencies: BTreeMap<K, L>,
+    default: L,
+}
+
+impl<K: Ord, F, L> PerKey<K, F, L> {
+    /// Sets the latency for values with the given key
+    pub fn with(mut self, key: K, latency: L) -> Self {
+        self.latencies.insert(key, latency);
+        self
+    }
+}
+
+impl<T, K, F, L> Latency<T> for PerKey<K, F, L>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+    L: Latency<T>,
+{
+    fn for_value(&self, value: &T) -> Duration {
+        let key = (self.key)(value);
+        self.latencies
+            .get(&key)
+            .unwrap_or(&self.default)
+            .for_value(value)
+    }
+}
+
+/// Delays each value by the time it takes to transmit it at `bytes_per_sec`
+///
+/// # Panics
+///
+/// Panics if `bytes_per_sec` is `0`.
+pub fn transmission<T, F: Fn(&T) -> usize>(bytes_per_sec: u64, size: F) -> Transmission<F> {
+    assert!(bytes_per_sec > 0, "bytes_per_sec must be greater than 0");
+    Transmission {
+        bytes_per_sec,
+        size,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Transmission<F> {
+    bytes_per_sec: u64,
+    size: F,
+}
+
+impl<T, F: Fn(&T) -> usize> Latency<T> for Transmission<F> {
+    fn for_value(&self, value: &T) -> Duration {
+        let bytes = (self.size)(value) as u128;
+        let nanos = bytes * 1_000_000_000 / self.bytes_per_sec as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Jitter<L> {
+    inner: L,
+    max: Duration,
+}
+
+impl<T, L: Latency<T>> Latency<T> for Jitter<L> {
+    fn for_value(&self, value: &T) -> Duration {
+        let max = resolution::duration_to_ticks(self.max);
+        let jitter = resolution::ticks_to_duration((0..=max).any());
+        self.inner.for_value(value) + jitter
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Plus<A, B>(A, B);
+
+impl<T, A: Latency<T>, B: Latency<T>> Latency<T> for Plus<A, B> {
+    fn for_value(&self, value: &T) -> Duration {
+        self.0.for_value(value) + self.1.for_value(value)
+    }
+}
+
 pub struct Queue<T, Q, L> {
     inner: Q,
     latency: L,
@@ -115,3 +260,18 @@ where
         self.inner.capacity()
     }
 }
+
+impl<T, Q, L> super::Snapshot<T> for Queue<T, Q, L>
+where
+    Q: super::Conditional<(Instant, T)> + super::Snapshot<(Instant, T)>,
+    L: Latency<T>,
+    T: 'static + Sync + Send,
+{
+    fn snapshot(&self) -> Vec<T> {
+        let mut items = self.inner.snapshot();
+        // items are popped once they're ready, in the order they were pushed when ready at the
+        // same time, so the sort needs to be stable
+        items.sort_by_key(|(target, _)| *target);
+        items.into_iter().map(|(_, value)| value).collect()
+    }
+}
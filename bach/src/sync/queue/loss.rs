@@ -0,0 +1,156 @@
+//! Random loss for simulated links
+//!
+//! Each pushed item is dropped with a fixed probability, drawn from [`crate::rand`] so the same
+//! seed always loses the same items. Lost items are reported through the `push` return value like
+//! any other drop, so the sender can't tell them apart from items lost to overflow.
+//!
+//! A queue models a single direction of a link, so asymmetric links wrap each direction with its
+//! own probability:
+//!
+//! ```no_run
+//! # use bach::{ext::*, sync::queue::vec_deque};
+//! # type Packet = Vec<u8>;
+//! let (to_server, from_client) = vec_deque::Queue::<(_, Packet)>::default()
+//!     .loss(0.01)
+//!     .latent(10.ms())
+//!     .channel();
+//! let (to_client, from_server) = vec_deque::Queue::<(_, Packet)>::default()
+//!     .loss(0.05)
+//!     .latent(10.ms())
+//!     .channel();
+//! ```
+
+use super::{CloseError, Conditional, PopError, PushError};
+use crate::rand::*;
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::task::Context;
+
+/// The resolution of the loss probability
+const SCALE: u64 = 1 << 32;
+
+pub struct Queue<Q> {
+    inner: Q,
+    threshold: u64,
+    lost: AtomicU64,
+}
+
+impl<Q: fmt::Debug> fmt::Debug for Queue<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<Q> Queue<Q> {
+    /// # Panics
+    ///
+    /// Panics if `probability` isn't between `0.0` and `1.0`.
+    pub fn new(inner: Q, probability: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "probability must be between 0 and 1"
+        );
+        Self {
+            inner,
+            threshold: (probability * SCALE as f64) as u64,
+            lost: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+
+    pub fn probability(&self) -> f64 {
+        self.threshold as f64 / SCALE as f64
+    }
+
+    /// Returns the number of items that were lost
+    pub fn lost(&self) -> u64 {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    fn is_lost(&self) -> bool {
+        // skip the draw for lossless queues so they don't consume randomness
+        if self.threshold == 0 {
+            return false;
+        }
+        if (0..SCALE).any() >= self.threshold {
+            return false;
+        }
+        self.lost.fetch_add(1, Ordering::Relaxed);
+        count!("loss");
+        true
+    }
+}
+
+impl<T, Q> super::Queue<T> for Queue<Q>
+where
+    Q: super::Queue<T>,
+{
+    fn push(&self, value: T) -> Result<Option<T>, PushError<T>> {
+        if self.is_lost() {
+            return Ok(Some(value));
+        }
+        self.inner.push(value)
+    }
+
+    fn push_with_context(&self, value: T, cx: &mut Context) -> Result<Option<T>, PushError<T>> {
+        if self.is_lost() {
+            return Ok(Some(value));
+        }
+        self.inner.push_with_context(value, cx)
+    }
+
+    fn pop(&self) -> Result<T, PopError> {
+        self.inner.pop()
+    }
+
+    fn pop_with_context(&self, cx: &mut Context) -> Result<T, PopError> {
+        self.inner.pop_with_context(cx)
+    }
+
+    fn close(&self) -> Result<(), CloseError> {
+        self.inner.close()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+}
+
+impl<T, Q> Conditional<T> for Queue<Q>
+where
+    Q: Conditional<T>,
+{
+    fn find_pop<F: Fn(&T) -> bool>(&self, check: F) -> Result<T, PopError> {
+        self.inner.find_pop(check)
+    }
+}
+
+impl<T, Q> super::Snapshot<T> for Queue<Q>
+where
+    Q: super::Snapshot<T>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.inner.snapshot()
+    }
+}
@@ -0,0 +1,287 @@
+//! Time-weighted occupancy of a queue for validating Little's Law
+//!
+//! Little's Law states that the average number of items in a stable queue, `L`, equals the
+//! arrival rate, `λ`, multiplied by the average time each item spends in the queue, `W`. All three
+//! are collected independently by this wrapper, so [`Stats::assert_littles_law`] is a quick check
+//! that a model and its measurements are consistent with each other.
+//!
+//! Items that are displaced from a full queue are counted as departures, the same as items that
+//! are popped, so the law still holds for queues that drop items.
+
+use super::{CloseError, PopError, PushError};
+use crate::time::{Duration, Instant};
+use core::fmt;
+use std::{marker::PhantomData, sync::Mutex, task::Context};
+
+/// The occupancy of a queue over a period of simulated time
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// The time between the first push and when the stats were collected
+    pub elapsed: Duration,
+    /// The number of items that were pushed
+    pub arrivals: u64,
+    /// The number of items that left the queue
+    pub departures: u64,
+    /// The time-weighted average number of items in the queue, `L`
+    pub mean_len: f64,
+    /// The number of items pushed per second, `λ`
+    pub arrival_rate: f64,
+    /// The average number of seconds that departed items spent in the queue, `W`
+    pub mean_sojourn: f64,
+}
+
+impl Stats {
+    /// Returns the relative difference between `L` and `λW`
+    pub fn littles_law_error(&self) -> f64 {
+        let expected = self.arrival_rate * self.mean_sojourn;
+        let scale = self.mean_len.abs().max(expected.abs());
+        if scale == 0.0 {
+            return 0.0;
+        }
+        (self.mean_len - expected).abs() / scale
+    }
+
+    /// Panics if `L` and `λW` differ by more than a relative `tolerance`
+    ///
+    /// Items that are still queued when the stats are collected only count toward `L`, so the
+    /// queue should be drained, or the run long enough that they don't matter, for the law to
+    /// hold.
+    #[track_caller]
+    pub fn assert_littles_law(&self, tolerance: f64) {
+        let error = self.littles_law_error();
+        assert!(
+            error <= tolerance,
+            "Little's Law doesn't hold within {tolerance}: L = {}, λW = {} (error {error})\n{self}",
+            self.mean_len,
+            self.arrival_rate * self.mean_sojourn,
+        );
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "elapsed={:?} arrivals={} departures={} L={} λ={}/s W={}s",
+            self.elapsed,
+            self.arrivals,
+            self.departures,
+            self.mean_len,
+            self.arrival_rate,
+            self.mean_sojourn
+        )
+    }
+}
+
+type PushResult<T> = Result<Option<T>, PushError<T>>;
+
+pub struct Queue<T, Q> {
+    inner: Q,
+    state: Mutex<State>,
+    value: PhantomData<T>,
+}
+
+#[derive(Default)]
+struct State {
+    start: Option<Instant>,
+    last: Option<Instant>,
+    len: usize,
+    /// The integral of the queue length over time, in item-seconds
+    area: f64,
+    arrivals: u64,
+    departures: u64,
+    sojourn: f64,
+}
+
+impl State {
+    /// Accumulates the occupancy since the last change
+    fn advance(&mut self, now: Instant) {
+        if let Some(last) = self.last {
+            self.area += self.len as f64 * now.saturating_duration_since(last).as_secs_f64();
+        }
+        self.start.get_or_insert(now);
+        self.last = Some(now);
+    }
+
+    fn depart(&mut self, arrived: Instant, now: Instant) {
+        self.departures += 1;
+        self.sojourn += now.saturating_duration_since(arrived).as_secs_f64();
+    }
+}
+
+impl<T, Q: Default> Default for Queue<T, Q> {
+    fn default() -> Self {
+        Self::new(Q::default())
+    }
+}
+
+impl<T, Q: fmt::Debug> fmt::Debug for Queue<T, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<T, Q> Queue<T, Q> {
+    pub fn new(inner: Q) -> Self {
+        Self {
+            inner,
+            state: Default::default(),
+            value: PhantomData,
+        }
+    }
+
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+
+    /// Returns the occupancy from the first push until now
+    pub fn stats(&self) -> Stats {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(now) = Instant::try_now() {
+            if state.start.is_some() {
+                state.advance(now);
+            }
+        }
+
+        let (Some(start), Some(last)) = (state.start, state.last) else {
+            return Stats::default();
+        };
+
+        let elapsed = last.saturating_duration_since(start);
+        let secs = elapsed.as_secs_f64();
+        let per_sec = |value: f64| if secs > 0.0 { value / secs } else { 0.0 };
+
+        Stats {
+            elapsed,
+            arrivals: state.arrivals,
+            departures: state.departures,
+            mean_len: per_sec(state.area),
+            arrival_rate: per_sec(state.arrivals as f64),
+            mean_sojourn: if state.departures > 0 {
+                state.sojourn / state.departures as f64
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn pushed(&self, displaced: Option<Instant>, now: Instant)
+    where
+        Q: super::Queue<(Instant, T)>,
+    {
+        let mut state = self.state.lock().unwrap();
+        state.advance(now);
+        state.arrivals += 1;
+        if let Some(arrived) = displaced {
+            state.depart(arrived, now);
+        }
+        state.len = self.inner.len();
+    }
+
+    fn popped(&self, arrived: Instant)
+    where
+        Q: super::Queue<(Instant, T)>,
+    {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.advance(now);
+        state.depart(arrived, now);
+        state.len = self.inner.len();
+    }
+
+    fn push_result(&self, now: Instant, result: PushResult<(Instant, T)>) -> PushResult<T>
+    where
+        Q: super::Queue<(Instant, T)>,
+    {
+        match result {
+            Ok(None) => {
+                self.pushed(None, now);
+                Ok(None)
+            }
+            Ok(Some((arrived, value))) => {
+                self.pushed(Some(arrived), now);
+                Ok(Some(value))
+            }
+            Err(PushError::Closed((_, value))) => Err(PushError::Closed(value)),
+            Err(PushError::Full((_, value))) => Err(PushError::Full(value)),
+        }
+    }
+}
+
+impl<T, Q> super::Queue<T> for Queue<T, Q>
+where
+    Q: super::Queue<(Instant, T)>,
+{
+    fn push(&self, value: T) -> Result<Option<T>, PushError<T>> {
+        let now = Instant::now();
+        let result = self.inner.push((now, value));
+        self.push_result(now, result)
+    }
+
+    fn push_with_context(&self, value: T, cx: &mut Context) -> Result<Option<T>, PushError<T>> {
+        let now = Instant::now();
+        let result = self.inner.push_with_context((now, value), cx);
+        self.push_result(now, result)
+    }
+
+    fn pop(&self) -> Result<T, PopError> {
+        let (arrived, value) = self.inner.pop()?;
+        self.popped(arrived);
+        Ok(value)
+    }
+
+    fn pop_with_context(&self, cx: &mut Context) -> Result<T, PopError> {
+        let (arrived, value) = self.inner.pop_with_context(cx)?;
+        self.popped(arrived);
+        Ok(value)
+    }
+
+    fn close(&self) -> Result<(), CloseError> {
+        self.inner.close()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+}
+
+impl<T, Q> super::Conditional<T> for Queue<T, Q>
+where
+    Q: super::Conditional<(Instant, T)>,
+{
+    fn find_pop<F: Fn(&T) -> bool>(&self, check: F) -> Result<T, PopError> {
+        let (arrived, value) = self.inner.find_pop(|(_, value)| check(value))?;
+        self.popped(arrived);
+        Ok(value)
+    }
+}
+
+impl<T, Q> super::Snapshot<T> for Queue<T, Q>
+where
+    Q: super::Snapshot<(Instant, T)>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.inner
+            .snapshot()
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+}
@@ -95,6 +95,21 @@ impl Queue<()> {
     }
 }
 
+impl<T> super::Snapshot<T> for Queue<T>
+where
+    T: core::cmp::Ord + Clone,
+{
+    fn snapshot(&self) -> Vec<T> {
+        let Ok(inner) = self.queue.lock() else {
+            return vec![];
+        };
+        // the largest item is popped first
+        let mut items = inner.0.clone().into_sorted_vec();
+        items.reverse();
+        items
+    }
+}
+
 impl<T> super::Queue<T> for Queue<T>
 where
     T: core::cmp::Ord,
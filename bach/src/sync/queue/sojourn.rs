@@ -5,6 +5,7 @@ use std::{marker::PhantomData, task::Context};
 
 pub struct Queue<T, Q> {
     inner: Q,
+    flow: Option<&'static str>,
     value: PhantomData<T>,
 }
 
@@ -24,10 +25,28 @@ impl<T, Q> Queue<T, Q> {
     pub fn new(inner: Q) -> Self {
         Self {
             inner,
+            flow: None,
             value: PhantomData,
         }
     }
 
+    /// Labels the recorded sojourn times with a flow name
+    ///
+    /// This makes it possible to tell apart the latencies of different links, e.g. with
+    /// [`assert_measure_within!`](crate::assert_measure_within).
+    pub fn with_flow(mut self, flow: &'static str) -> Self {
+        self.flow = Some(flow);
+        self
+    }
+
+    fn record(&self, time: Instant) {
+        if let Some(flow) = self.flow {
+            measure!("sojourn_time", time.elapsed(), "flow" = flow);
+        } else {
+            measure!("sojourn_time", time.elapsed());
+        }
+    }
+
     pub fn inner(&self) -> &Q {
         &self.inner
     }
@@ -42,7 +61,7 @@ where
         match self.inner.push(value) {
             Ok(None) => Ok(None),
             Ok(Some((t, value))) => {
-                measure!("sojourn_time", t.elapsed());
+                self.record(t);
                 Ok(Some(value))
             }
             Err(PushError::Closed((_, value))) => Err(PushError::Closed(value)),
@@ -55,7 +74,7 @@ where
         match self.inner.push_with_context(value, cx) {
             Ok(None) => Ok(None),
             Ok(Some((t, value))) => {
-                measure!("sojourn_time", t.elapsed());
+                self.record(t);
                 Ok(Some(value))
             }
             Err(PushError::Closed((_, value))) => Err(PushError::Closed(value)),
@@ -65,13 +84,13 @@ where
 
     fn pop(&self) -> Result<T, PopError> {
         let (t, value) = self.inner.pop()?;
-        measure!("sojourn_time", t.elapsed());
+        self.record(t);
         Ok(value)
     }
 
     fn pop_with_context(&self, cx: &mut Context) -> Result<T, PopError> {
         let (t, value) = self.inner.pop_with_context(cx)?;
-        measure!("sojourn_time", t.elapsed());
+        self.record(t);
         Ok(value)
     }
 
@@ -106,7 +125,20 @@ where
 {
     fn find_pop<F: Fn(&T) -> bool>(&self, check: F) -> Result<T, PopError> {
         let (t, value) = self.inner.find_pop(|(_, value)| check(value))?;
-        measure!("sojourn_time", t.elapsed());
+        self.record(t);
         Ok(value)
     }
 }
+
+impl<T, Q> super::Snapshot<T> for Queue<T, Q>
+where
+    Q: super::Snapshot<(Instant, T)>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.inner
+            .snapshot()
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+}
@@ -87,3 +87,12 @@ where
         self.span().in_scope(|| self.inner.capacity())
     }
 }
+
+impl<T, Q> super::Snapshot<T> for Queue<Q>
+where
+    Q: super::Snapshot<T>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.span().in_scope(|| self.inner.snapshot())
+    }
+}
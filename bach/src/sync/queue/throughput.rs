@@ -0,0 +1,196 @@
+//! Records how many items leave a queue in each interval of simulated time
+//!
+//! Every completed interval is recorded as a `throughput_items` measurement, along with
+//! `throughput_bytes` if a size function was provided, and kept in [`Queue::history`] so the
+//! goodput of a link can be plotted or asserted on without extra instrumentation.
+
+use super::{CloseError, PopError, PushError};
+use crate::time::{Duration, Instant};
+use core::fmt;
+use std::{sync::Mutex, task::Context};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Interval {
+    /// The time since the start of the simulation that the interval began
+    pub start: Duration,
+    /// The number of items that were popped during the interval
+    pub items: u64,
+    /// The number of bytes that were popped during the interval, if a size function was
+    /// provided
+    pub bytes: u64,
+}
+
+pub struct Queue<T, Q> {
+    name: &'static str,
+    inner: Q,
+    interval: Duration,
+    size: Option<fn(&T) -> usize>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    current: Option<Interval>,
+    history: Vec<Interval>,
+}
+
+impl<T, Q: fmt::Debug> fmt::Debug for Queue<T, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<T, Q> Queue<T, Q> {
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn new(inner: Q, name: &'static str, interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "interval must be greater than 0");
+        Self {
+            name,
+            inner,
+            interval,
+            size: None,
+            state: Default::default(),
+        }
+    }
+
+    /// Records the number of bytes in each item along with the item count
+    pub fn with_size(mut self, size: fn(&T) -> usize) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+
+    /// Returns all of the completed intervals
+    ///
+    /// Intervals without any items are included so the history is contiguous from the first
+    /// item to the current interval.
+    pub fn history(&self) -> Vec<Interval> {
+        self.state.lock().unwrap().history.clone()
+    }
+
+    fn record(&self, value: &T) {
+        let bytes = self.size.map_or(0, |size| size(value) as u64);
+
+        let now = Instant::now().elapsed_since_start();
+        let interval = self.interval.as_nanos();
+        let start = Duration::from_nanos((now.as_nanos() / interval * interval) as u64);
+
+        let mut state = self.state.lock().unwrap();
+
+        // close out any intervals that have passed since the last item
+        while let Some(current) = state.current.filter(|current| current.start < start) {
+            self.flush(&current);
+            state.history.push(current);
+            state.current = Some(Interval {
+                start: current.start + self.interval,
+                ..Default::default()
+            });
+        }
+
+        let current = state.current.get_or_insert(Interval {
+            start,
+            ..Default::default()
+        });
+        current.items += 1;
+        current.bytes += bytes;
+    }
+
+    fn flush(&self, interval: &Interval) {
+        measure!(
+            "throughput_items",
+            interval.items as f64,
+            "queue" = self.name
+        );
+        if self.size.is_some() {
+            measure!(
+                "throughput_bytes",
+                interval.bytes as f64,
+                "queue" = self.name
+            );
+        }
+    }
+}
+
+impl<T, Q> Drop for Queue<T, Q> {
+    fn drop(&mut self) {
+        // report the partial interval so short simulations still produce a sample
+        let state = self.state.get_mut().unwrap_or_else(|err| err.into_inner());
+        if let Some(current) = state.current.take() {
+            self.flush(&current);
+        }
+    }
+}
+
+impl<T, Q> super::Queue<T> for Queue<T, Q>
+where
+    Q: super::Queue<T>,
+{
+    fn push(&self, value: T) -> Result<Option<T>, PushError<T>> {
+        self.inner.push(value)
+    }
+
+    fn push_with_context(&self, value: T, cx: &mut Context) -> Result<Option<T>, PushError<T>> {
+        self.inner.push_with_context(value, cx)
+    }
+
+    fn pop(&self) -> Result<T, PopError> {
+        let value = self.inner.pop()?;
+        self.record(&value);
+        Ok(value)
+    }
+
+    fn pop_with_context(&self, cx: &mut Context) -> Result<T, PopError> {
+        let value = self.inner.pop_with_context(cx)?;
+        self.record(&value);
+        Ok(value)
+    }
+
+    fn close(&self) -> Result<(), CloseError> {
+        self.inner.close()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+}
+
+impl<T, Q> super::Conditional<T> for Queue<T, Q>
+where
+    Q: super::Conditional<T>,
+{
+    fn find_pop<F: Fn(&T) -> bool>(&self, check: F) -> Result<T, PopError> {
+        let value = self.inner.find_pop(check)?;
+        self.record(&value);
+        Ok(value)
+    }
+}
+
+impl<T, Q> super::Snapshot<T> for Queue<T, Q>
+where
+    Q: super::Snapshot<T>,
+{
+    fn snapshot(&self) -> Vec<T> {
+        self.inner.snapshot()
+    }
+}
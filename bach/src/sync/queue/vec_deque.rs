@@ -38,14 +38,40 @@ impl Overflow {
     }
 }
 
+/// Called with each item that is displaced from a full queue
+pub type OverflowCallback<T> = Box<dyn Fn(T) + Send + Sync>;
+
+/// A callback that is notified of items displaced by [`Overflow::PreferRecent`]
+///
+/// This is implemented for `()`, which disables the callback, and any `Fn(T)`.
+pub trait OnOverflow<T> {
+    fn into_callback(self) -> Option<OverflowCallback<T>>;
+}
+
+impl<T> OnOverflow<T> for () {
+    fn into_callback(self) -> Option<OverflowCallback<T>> {
+        None
+    }
+}
+
+impl<T, F> OnOverflow<T> for F
+where
+    F: 'static + Fn(T) + Send + Sync,
+{
+    fn into_callback(self) -> Option<OverflowCallback<T>> {
+        Some(Box::new(self))
+    }
+}
+
 #[derive(Default)]
-pub struct Builder {
+pub struct Builder<O = ()> {
     capacity: Option<usize>,
     discipline: Discipline,
     overflow: Overflow,
+    on_overflow: O,
 }
 
-impl Builder {
+impl<O> Builder<O> {
     pub fn with_capacity(mut self, capacity: Option<usize>) -> Self {
         self.capacity = capacity.map(|v| v.max(1));
         self
@@ -61,7 +87,24 @@ impl Builder {
         self
     }
 
-    pub fn build<T>(self) -> Queue<T> {
+    /// Calls `on_overflow` with each item that is displaced to make room for a new one
+    ///
+    /// The callback takes ownership of the displaced item, so `push` returns `Ok(None)` instead of
+    /// the item. It is called after the queue is unlocked, which allows it to salvage the item by
+    /// pushing it back into the queue or somewhere else.
+    pub fn with_on_overflow<F>(self, on_overflow: F) -> Builder<F> {
+        Builder {
+            capacity: self.capacity,
+            discipline: self.discipline,
+            overflow: self.overflow,
+            on_overflow,
+        }
+    }
+
+    pub fn build<T>(self) -> Queue<T>
+    where
+        O: OnOverflow<T>,
+    {
         let config = Config {
             capacity: self.capacity,
             discipline: self.discipline,
@@ -73,7 +116,12 @@ impl Builder {
             VecDeque::new()
         };
         let queue = Mutex::new((queue, true));
-        Queue { config, queue }
+        let on_overflow = self.on_overflow.into_callback();
+        Queue {
+            config,
+            queue,
+            on_overflow,
+        }
     }
 }
 
@@ -154,11 +202,12 @@ impl Config {
 pub struct Queue<T> {
     config: Config,
     queue: Mutex<(VecDeque<T>, bool)>,
+    on_overflow: Option<OverflowCallback<T>>,
 }
 
 impl<T> Default for Queue<T> {
     fn default() -> Self {
-        Builder::default().build()
+        Queue::builder().build()
     }
 }
 
@@ -192,13 +241,30 @@ impl<T> Queue<T> {
     }
 }
 
+impl<T: Clone> super::Snapshot<T> for Queue<T> {
+    fn snapshot(&self) -> Vec<T> {
+        self.queue
+            .lock()
+            .map_or_else(|_| vec![], |inner| inner.0.iter().cloned().collect())
+    }
+}
+
 impl<T> super::Queue<T> for Queue<T> {
     fn push(&self, value: T) -> Result<Option<T>, PushError<T>> {
         let Some(mut inner) = self.queue.lock().ok().filter(|v| v.1) else {
             return Err(PushError::Closed(value));
         };
 
-        self.config.push(&mut inner.0, value)
+        let res = self.config.push(&mut inner.0, value);
+        drop(inner);
+
+        match (res, &self.on_overflow) {
+            (Ok(Some(prev)), Some(on_overflow)) => {
+                on_overflow(prev);
+                Ok(None)
+            }
+            (res, _) => res,
+        }
     }
 
     fn push_with_context(&self, value: T, cx: &mut Context) -> Result<Option<T>, PushError<T>> {
@@ -72,3 +72,27 @@ mod fifo {
 
     push_pop_test!([0, 1, 2, 3, 4, 5]);
 }
+
+#[test]
+fn on_overflow() {
+    use std::sync::{Arc, Mutex};
+
+    let displaced = Arc::new(Mutex::new(vec![]));
+    let queue = Queue::builder()
+        .with_capacity(Some(2))
+        .with_overflow(Overflow::PreferRecent)
+        .with_on_overflow({
+            let displaced = displaced.clone();
+            move |value: u32| displaced.lock().unwrap().push(value)
+        })
+        .build();
+
+    for value in 0..5 {
+        // the callback takes the displaced item instead of the caller
+        assert_eq!(queue.push(value), Ok(None));
+    }
+
+    assert_eq!(*displaced.lock().unwrap(), [0, 1, 2]);
+    assert_eq!(queue.pop().unwrap(), 3);
+    assert_eq!(queue.pop().unwrap(), 4);
+}
@@ -12,6 +12,15 @@ where
 }
 
 pub fn spawn_named<F, N, T>(future: F, name: N) -> JoinHandle<T>
+where
+    F: 'static + Future<Output = T> + Send,
+    N: core::fmt::Display,
+    T: 'static + Send,
+{
+    spawn_task(future, name, false)
+}
+
+fn spawn_task<F, N, T>(future: F, name: N, daemon: bool) -> JoinHandle<T>
 where
     F: 'static + Future<Output = T> + Send,
     N: core::fmt::Display,
@@ -19,16 +28,67 @@ where
 {
     scope::borrow_with(|handle| {
         // try to inherit the parent group
-        crate::group::scope::try_borrow_with(|group| {
-            if let Some(group) = group {
+        crate::group::scope::try_borrow_with(|group| match (group, daemon) {
+            (Some(group), true) => {
+                handle.spawn_daemon_named(crate::group::Grouped::new(future, *group), name)
+            }
+            (Some(group), false) => {
                 handle.spawn_named(crate::group::Grouped::new(future, *group), name)
-            } else {
-                handle.spawn_named(future, name)
             }
+            (None, true) => handle.spawn_daemon_named(future, name),
+            (None, false) => handle.spawn_named(future, name),
         })
     })
 }
 
+/// Registers a function to run when the current task completes or is dropped before completing
+///
+/// Finalizers run in the reverse order they were registered, inside the task's scope, right after
+/// the task's future is dropped. This makes them useful for releasing per-task resources in
+/// simulations that cancel or abort tasks.
+///
+/// # Panics
+///
+/// Panics if called outside of a task.
+pub fn on_task_end<F: 'static + FnOnce() + Send>(f: F) {
+    info::scope::borrow_with(|info| info.finalizers().push(Box::new(f)))
+}
+
+/// Changes the display name of the current task
+///
+/// The new name shows up in diagnostics, like stall and orphan reports, and is recorded in the
+/// `name` field of the task's tracing span. [`Info::name`] and the `target` label of wake metrics
+/// keep the name the task was spawned with, so renaming doesn't create new metric series.
+///
+/// # Panics
+///
+/// Panics if called outside of a task.
+pub fn set_name<N: core::fmt::Display>(name: N) {
+    info::scope::borrow_with(|info| info.set_name(name.to_string()))
+}
+
+/// Attaches a key/value tag to the current task, replacing any previous value for `key`
+///
+/// Tags are shown next to the task's name in diagnostics, recorded in the `tags` field of the
+/// task's tracing span and added as labels to metrics emitted by the task. This is useful for
+/// long-lived workers to report the request they're currently processing.
+///
+/// # Panics
+///
+/// Panics if called outside of a task.
+pub fn set_tag<K: Into<String>, V: core::fmt::Display>(key: K, value: V) {
+    info::scope::borrow_with(|info| info.set_tag(key.into(), value.to_string()))
+}
+
+/// Removes a tag from the current task, returning its value
+///
+/// # Panics
+///
+/// Panics if called outside of a task.
+pub fn remove_tag(key: &str) -> Option<String> {
+    info::scope::borrow_with(|info| info.remove_tag(key))
+}
+
 pub mod primary {
     use super::*;
     use alloc::sync::Arc;
@@ -107,6 +167,145 @@ pub mod primary {
     }
 }
 
+pub mod daemon {
+    use super::*;
+    use pin_project_lite::pin_project;
+
+    pub fn spawn<F, T>(future: F) -> JoinHandle<T>
+    where
+        F: 'static + Future<Output = T> + Send,
+        T: 'static + Send,
+    {
+        spawn_named(future, "")
+    }
+
+    pub fn spawn_named<F, N, T>(future: F, name: N) -> JoinHandle<T>
+    where
+        F: 'static + Future<Output = T> + Send,
+        N: core::fmt::Display,
+        T: 'static + Send,
+    {
+        super::spawn_task(future, name, true)
+    }
+
+    pub fn create<F: Future>(future: F) -> Wrapped<F> {
+        Wrapped { inner: future }
+    }
+
+    pin_project! {
+        /// Marks the task as a daemon
+        ///
+        /// Spawning the wrapper directly marks the task right away. Otherwise, like when it's
+        /// wrapped in another future before being spawned, the task is marked on its first poll.
+        pub struct Wrapped<F> {
+            #[pin]
+            inner: F,
+        }
+    }
+
+    impl<F: Future> Wrapped<F> {
+        pub fn spawn(self) -> JoinHandle<F::Output>
+        where
+            F: 'static + Send,
+            F::Output: 'static + Send,
+        {
+            spawn(self.inner)
+        }
+
+        pub fn spawn_named<N: core::fmt::Display>(self, name: N) -> JoinHandle<F::Output>
+        where
+            F: 'static + Send,
+            F::Output: 'static + Send,
+        {
+            spawn_named(self.inner, name)
+        }
+
+        /// Runs the task in the group `name`, keeping it marked as a daemon
+        pub fn group(self, name: &str) -> Wrapped<crate::group::Grouped<F>> {
+            create(crate::group::GroupExt::group(self.inner, name))
+        }
+    }
+
+    impl<F: Future> Future for Wrapped<F> {
+        type Output = F::Output;
+
+        fn poll(
+            self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Self::Output> {
+            info::scope::try_borrow_with(|info| {
+                if let Some(info) = info {
+                    info.set_daemon();
+                }
+            });
+            self.project().inner.poll(cx)
+        }
+    }
+}
+
+/// Contains panics inside of a task so the rest of the simulation keeps running
+///
+/// A panic normally unwinds out of the executor and ends the simulation. A task wrapped with
+/// [`CatchUnwindExt::catch_unwind`](crate::ext::CatchUnwindExt::catch_unwind) instead completes
+/// with the panic payload, like joining a panicked thread, so other tasks can observe how the
+/// failure affected shared state.
+pub mod catch_unwind {
+    use super::*;
+    use core::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use pin_project_lite::pin_project;
+    use std::panic::{self, AssertUnwindSafe};
+
+    pub fn create<F: Future>(future: F) -> Wrapped<F> {
+        Wrapped {
+            inner: Some(future),
+        }
+    }
+
+    pin_project! {
+        pub struct Wrapped<F> {
+            #[pin]
+            inner: Option<F>,
+        }
+    }
+
+    impl<F: Future> Future for Wrapped<F> {
+        type Output = std::thread::Result<F::Output>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let inner = self.project().inner;
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut inner = DropOnUnwind(inner);
+                let future = inner.0.as_mut().as_pin_mut();
+                future.expect("polled after completion").poll(cx)
+            }));
+
+            match res {
+                Ok(Poll::Ready(value)) => Poll::Ready(Ok(value)),
+                Ok(Poll::Pending) => Poll::Pending,
+                Err(panic) => {
+                    count!("task_panic");
+                    Poll::Ready(Err(panic))
+                }
+            }
+        }
+    }
+
+    /// Drops the future while the panic unwinds, like the stack of a panicking thread, so any
+    /// guards it's holding across `.await` points observe [`std::thread::panicking`]
+    struct DropOnUnwind<'a, F>(Pin<&'a mut Option<F>>);
+
+    impl<F> Drop for DropOnUnwind<'_, F> {
+        fn drop(&mut self) {
+            if std::thread::panicking() {
+                self.0.set(None);
+            }
+        }
+    }
+}
+
 pub use info::Info;
 
 pub(crate) mod info {
@@ -115,15 +314,80 @@ pub(crate) mod info {
         define,
         tracing::{info_span, Span},
     };
+    use core::{
+        fmt,
+        sync::atomic::{AtomicBool, Ordering},
+    };
     use pin_project_lite::pin_project;
-    use std::sync::Arc;
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
 
     define!(scope, Info);
 
+    /// Cloned into the scope on every poll, so everything but the id is kept behind one `Arc`
     #[derive(Clone, Debug)]
     pub struct Info {
         id: u64,
+        inner: Arc<Inner>,
+    }
+
+    #[derive(Debug)]
+    struct Inner {
+        name: Option<Arc<str>>,
+        meta: Mutex<Meta>,
+        tagged: AtomicBool,
+        daemon: AtomicBool,
+        span: Span,
+        finalizers: Finalizers,
+    }
+
+    /// The parts of the info that the task can change while it's running
+    #[derive(Clone, Debug, Default)]
+    struct Meta {
         name: Option<Arc<str>>,
+        tags: BTreeMap<String, String>,
+    }
+
+    /// The tasks that haven't completed or been dropped yet
+    #[derive(Debug, Default)]
+    pub(crate) struct Live(Mutex<BTreeMap<u64, Info>>);
+
+    impl Live {
+        /// Returns the live tasks that weren't marked as daemons, ordered by id
+        pub fn orphans(&self) -> Vec<Info> {
+            self.0
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|info| !info.is_daemon())
+                .cloned()
+                .collect()
+        }
+    }
+
+    type Finalizer = Box<dyn FnOnce() + Send>;
+
+    #[derive(Default)]
+    pub(crate) struct Finalizers(Mutex<Vec<Finalizer>>);
+
+    impl Finalizers {
+        pub(super) fn push(&self, f: Finalizer) {
+            self.0.lock().unwrap().push(f);
+        }
+
+        fn pop(&self) -> Option<Finalizer> {
+            self.0.lock().unwrap().pop()
+        }
+    }
+
+    impl fmt::Debug for Finalizers {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Finalizers")
+                .field("len", &self.0.lock().unwrap().len())
+                .finish()
+        }
     }
 
     impl Info {
@@ -135,8 +399,136 @@ pub(crate) mod info {
             self.id
         }
 
+        /// Returns the name the task was spawned with
         pub fn name(&self) -> Option<&str> {
-            self.name.as_deref()
+            self.inner.name.as_deref()
+        }
+
+        /// Returns the name the task currently has, which reflects any calls to
+        /// [`set_name`](super::set_name)
+        pub fn current_name(&self) -> Option<Arc<str>> {
+            self.inner.meta.lock().unwrap().name.clone()
+        }
+
+        /// Returns the tags that are currently attached to the task, sorted by key
+        pub fn tags(&self) -> BTreeMap<String, String> {
+            self.inner.meta.lock().unwrap().tags.clone()
+        }
+
+        /// Returns the value of a tag attached to the task
+        pub fn tag(&self, key: &str) -> Option<String> {
+            self.inner.meta.lock().unwrap().tags.get(key).cloned()
+        }
+
+        /// Returns a copy of the info that isn't affected by later changes to the name or tags
+        // the span is `Copy` when tracing is disabled
+        #[allow(clippy::clone_on_copy)]
+        pub(crate) fn snapshot(&self) -> Self {
+            let meta = self.inner.meta.lock().unwrap().clone();
+            Self {
+                id: self.id,
+                inner: Arc::new(Inner {
+                    name: self.inner.name.clone(),
+                    meta: Mutex::new(meta),
+                    tagged: AtomicBool::new(self.is_tagged()),
+                    daemon: AtomicBool::new(self.is_daemon()),
+                    span: self.inner.span.clone(),
+                    finalizers: Default::default(),
+                }),
+            }
+        }
+
+        pub(super) fn finalizers(&self) -> &Finalizers {
+            &self.inner.finalizers
+        }
+
+        /// Cheap check to avoid locking the tags on every metric
+        #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+        fn is_tagged(&self) -> bool {
+            self.inner.tagged.load(Ordering::Relaxed)
+        }
+
+        /// Calls `f` with each tag on the task
+        #[cfg(feature = "metrics")]
+        pub(crate) fn for_each_tag<F: FnMut(&str, &str)>(&self, mut f: F) {
+            if !self.is_tagged() {
+                return;
+            }
+            for (key, value) in self.inner.meta.lock().unwrap().tags.iter() {
+                f(key, value);
+            }
+        }
+
+        pub(super) fn set_name(&self, name: String) {
+            crate::tracing::trace!(task = self.id, name = %name, "rename");
+            self.inner.span.record("name", name.as_str());
+            let name = if name.is_empty() {
+                None
+            } else {
+                Some(name.into())
+            };
+            self.inner.meta.lock().unwrap().name = name;
+        }
+
+        pub(super) fn set_tag(&self, key: String, value: String) {
+            crate::tracing::trace!(task = self.id, tag = %key, value = %value);
+            let mut meta = self.inner.meta.lock().unwrap();
+            meta.tags.insert(key, value);
+            self.inner.tagged.store(true, Ordering::Relaxed);
+            self.record_tags(&meta.tags);
+        }
+
+        pub(super) fn remove_tag(&self, key: &str) -> Option<String> {
+            let mut meta = self.inner.meta.lock().unwrap();
+            let value = meta.tags.remove(key);
+            self.inner
+                .tagged
+                .store(!meta.tags.is_empty(), Ordering::Relaxed);
+            self.record_tags(&meta.tags);
+            value
+        }
+
+        fn record_tags(&self, tags: &BTreeMap<String, String>) {
+            if self.inner.span.is_disabled() {
+                return;
+            }
+            let mut value = String::new();
+            for (key, v) in tags {
+                if !value.is_empty() {
+                    value.push_str(", ");
+                }
+                value.push_str(key);
+                value.push('=');
+                value.push_str(v);
+            }
+            self.inner.span.record("tags", value.as_str());
+        }
+
+        /// Returns `true` if the task was spawned as a [`daemon`](super::daemon)
+        pub fn is_daemon(&self) -> bool {
+            self.inner.daemon.load(Ordering::Relaxed)
+        }
+
+        pub(super) fn set_daemon(&self) {
+            self.inner.daemon.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl fmt::Display for Info {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let meta = self.inner.meta.lock().unwrap();
+            write!(f, "task {}", self.id)?;
+            if let Some(name) = &meta.name {
+                write!(f, " ({name})")?;
+            }
+            for (idx, (key, value)) in meta.tags.iter().enumerate() {
+                let prefix = if idx == 0 { " [" } else { ", " };
+                write!(f, "{prefix}{key}={value}")?;
+            }
+            if !meta.tags.is_empty() {
+                write!(f, "]")?;
+            }
+            Ok(())
         }
     }
 
@@ -144,13 +536,49 @@ pub(crate) mod info {
         pub struct WithInfo<F> {
             #[pin]
             inner: F,
-            info: Info,
-            span: Span,
+            // declared after `inner` so the finalizers run once the future has been dropped
+            task: Task,
+        }
+    }
+
+    struct Task {
+        info: Info,
+        /// Only set when the runtime is checking for orphans
+        live: Option<Arc<Live>>,
+    }
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            if let Some(live) = &self.live {
+                let info = live.0.lock().unwrap().remove(&self.info.id);
+                drop(info);
+            }
+
+            let Some(mut finalizer) = self.info.inner.finalizers.pop() else {
+                return;
+            };
+
+            scope::with(self.info.clone(), || {
+                self.info.inner.span.in_scope(|| loop {
+                    finalizer();
+                    // finalizers may register more finalizers
+                    let Some(next) = self.info.inner.finalizers.pop() else {
+                        break;
+                    };
+                    finalizer = next;
+                })
+            });
         }
     }
 
     impl<F> WithInfo<F> {
-        pub fn new(inner: F, id: u64, name: &Arc<str>) -> Self {
+        pub fn new(
+            inner: F,
+            id: u64,
+            name: &Arc<str>,
+            daemon: bool,
+            live: Option<&Arc<Live>>,
+        ) -> Self {
             let name = if name.is_empty() {
                 None
             } else {
@@ -158,12 +586,39 @@ pub(crate) mod info {
             };
             let span = if let Some(name) = &name {
                 let _ = name;
-                info_span!("task", task = %name)
+                info_span!("task", task = %name, name = crate::tracing::field::Empty, tags = crate::tracing::field::Empty)
             } else {
-                info_span!("task", task = id)
+                info_span!(
+                    "task",
+                    task = id,
+                    name = crate::tracing::field::Empty,
+                    tags = crate::tracing::field::Empty
+                )
+            };
+            let info = Info {
+                id,
+                inner: Arc::new(Inner {
+                    name: name.clone(),
+                    meta: Mutex::new(Meta {
+                        name,
+                        tags: BTreeMap::new(),
+                    }),
+                    tagged: AtomicBool::new(false),
+                    daemon: AtomicBool::new(daemon),
+                    span,
+                    finalizers: Default::default(),
+                }),
             };
-            let info = Info { id, name };
-            Self { inner, info, span }
+            if let Some(live) = live {
+                live.0.lock().unwrap().insert(id, info.clone());
+            }
+            Self {
+                inner,
+                task: Task {
+                    info,
+                    live: live.cloned(),
+                },
+            }
         }
     }
 
@@ -175,8 +630,9 @@ pub(crate) mod info {
             cx: &mut std::task::Context<'_>,
         ) -> std::task::Poll<Self::Output> {
             let this = self.project();
-            scope::with(this.info.clone(), || {
-                this.span.in_scope(|| this.inner.poll(cx))
+            let info = &this.task.info;
+            scope::with(info.clone(), || {
+                info.inner.span.in_scope(|| this.inner.poll(cx))
             })
         }
     }
@@ -0,0 +1,337 @@
+//! Utilities for writing tests against simulations
+
+pub mod fairness;
+pub mod faults;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod phaser;
+pub mod stats;
+pub mod watchdog;
+
+pub use fairness::Fairness;
+pub use faults::Topology;
+pub use phaser::Phaser;
+pub use stats::Estimate;
+pub use watchdog::Watchdog;
+
+use crate::time::{Duration, Instant};
+use core::fmt;
+
+#[cfg(feature = "tracing")]
+pub use capture::{capture_tracing, TracingCapture};
+
+/// Polls a condition until it holds, panicking if it doesn't within the given simulated time
+///
+/// The condition is checked with an exponential backoff, starting at 1ms and doubling up to 1s
+/// between checks. An optional message can be provided to include with the diagnostics.
+///
+/// ```no_run
+/// # use bach::{ext::*, sync::channel};
+/// # struct Leader;
+/// # impl Leader {
+/// #     fn is_elected(&self) -> bool {
+/// #         true
+/// #     }
+/// # }
+/// # async fn example(leader: Leader, queue: channel::Receiver<u64>) {
+/// bach::eventually!(leader.is_elected(), within: 5.s());
+/// bach::eventually!(queue.is_empty(), within: 1.s(), "queue still has {} items", queue.len());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! eventually {
+    ($cond:expr, within: $within:expr $(,)?) => {
+        $crate::testing::eventually(
+            || $cond,
+            $within,
+            stringify!($cond),
+            || None,
+            (file!(), line!()),
+        )
+        .await
+    };
+    ($cond:expr, within: $within:expr, $($arg:tt)+) => {
+        $crate::testing::eventually(
+            || $cond,
+            $within,
+            stringify!($cond),
+            || Some(format!($($arg)+)),
+            (file!(), line!()),
+        )
+        .await
+    };
+}
+
+#[doc(hidden)]
+pub async fn eventually<C, M>(
+    mut cond: C,
+    within: Duration,
+    expr: &str,
+    message: M,
+    location: (&str, u32),
+) where
+    C: FnMut() -> bool,
+    M: FnOnce() -> Option<String>,
+{
+    const MIN_BACKOFF: Duration = Duration::from_millis(1);
+    const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+    let start = Instant::now();
+    let mut backoff = MIN_BACKOFF;
+    let mut checks = 0u64;
+
+    loop {
+        checks += 1;
+        if cond() {
+            return;
+        }
+
+        let remaining = within.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            break;
+        }
+
+        crate::time::sleep(backoff.min(remaining)).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    let (file, line) = location;
+    let diagnostics = Diagnostics {
+        expr,
+        within,
+        start,
+        checks,
+    };
+
+    if let Some(message) = message() {
+        panic!("{diagnostics}: {message}\n  at {file}:{line}");
+    } else {
+        panic!("{diagnostics}\n  at {file}:{line}");
+    }
+}
+
+struct Diagnostics<'a> {
+    expr: &'a str,
+    within: Duration,
+    start: Instant,
+    checks: u64,
+}
+
+impl fmt::Display for Diagnostics<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "condition `{}` was not satisfied within {:?} (started at {}, gave up at {} after {} checks)",
+            self.expr,
+            self.within,
+            self.start,
+            Instant::now(),
+            self.checks,
+        )
+    }
+}
+
+#[cfg(feature = "tracing")]
+mod capture {
+    use crate::time::Instant;
+    use alloc::sync::Arc;
+    use std::{io, sync::Mutex};
+    use tracing::subscriber::DefaultGuard;
+    use tracing_subscriber::fmt::{format::Writer, time::FormatTime, MakeWriter};
+
+    /// Captures the spans and events emitted on the current thread into a buffer
+    ///
+    /// Each line is stamped with the simulated time it was emitted at rather than the wall clock,
+    /// which keeps the output deterministic enough for snapshots. Capturing stops when the
+    /// returned guard is dropped, so creating the guard inside of each iteration of a loop or a
+    /// `bolero` check gives each iteration its own buffer.
+    ///
+    /// ```no_run
+    /// for seed in 0..10 {
+    ///     let logs = bach::testing::capture_tracing();
+    ///     bach::environment::default::Runtime::new()
+    ///         .with_seed(seed)
+    ///         .run(|| { /* ... */ });
+    ///     assert!(logs.contents().contains("expected event"));
+    /// }
+    /// ```
+    pub fn capture_tracing() -> TracingCapture {
+        let buffer = Buffer::default();
+
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_level(false)
+            .with_timer(SimTime)
+            .with_ansi(false)
+            .with_writer(buffer.clone())
+            .compact()
+            .finish();
+
+        let guard = tracing::subscriber::set_default(subscriber);
+
+        TracingCapture {
+            buffer,
+            _guard: guard,
+        }
+    }
+
+    /// A guard returned by [`capture_tracing`]
+    pub struct TracingCapture {
+        buffer: Buffer,
+        _guard: DefaultGuard,
+    }
+
+    impl TracingCapture {
+        /// Returns all of the output captured so far
+        pub fn contents(&self) -> String {
+            let buffer = self.buffer.0.lock().unwrap();
+            String::from_utf8_lossy(&buffer).into_owned()
+        }
+
+        /// Returns the captured output split into lines
+        pub fn lines(&self) -> Vec<String> {
+            self.contents().lines().map(String::from).collect()
+        }
+
+        /// Returns all of the output captured so far and clears the buffer
+        pub fn take(&self) -> String {
+            let buffer = core::mem::take(&mut *self.buffer.0.lock().unwrap());
+            String::from_utf8_lossy(&buffer).into_owned()
+        }
+
+        /// Discards all of the output captured so far
+        pub fn clear(&self) {
+            self.buffer.0.lock().unwrap().clear();
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    struct SimTime;
+
+    impl FormatTime for SimTime {
+        fn format_time(&self, w: &mut Writer<'_>) -> core::fmt::Result {
+            if let Some(now) = Instant::try_now() {
+                write!(w, "{now}")
+            } else {
+                write!(w, "[UNKNOWN]")
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{environment::default::Runtime, ext::*, sync::queue::vec_deque::Queue};
+        use bolero::check;
+
+        #[test]
+        fn per_iteration() {
+            check!().with_type::<u8>().for_each(|delay| {
+                let logs = capture_tracing();
+
+                let mut rt = Runtime::new();
+                let woke = rt.run(|| {
+                    let (sender, receiver) = Queue::default().channel();
+                    let delay = *delay as u64;
+                    async move {
+                        delay.ms().sleep().await;
+                        tracing::info!(delay, "woke");
+                        sender.try_push(Instant::now()).unwrap();
+                    }
+                    .primary()
+                    .spawn();
+                    receiver
+                });
+                let woke = woke.try_pop().unwrap();
+
+                // only the current iteration should be captured
+                let lines = logs.lines();
+                let lines: Vec<_> = lines.iter().filter(|line| line.contains("woke")).collect();
+                assert_eq!(lines.len(), 1);
+                assert!(lines[0].starts_with(&woke.to_string()));
+                assert!(lines[0].contains(&format!("delay={delay}")));
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{environment::default::Runtime, ext::*, sync::queue::vec_deque::Queue};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn eventually_satisfied() {
+        let mut rt = Runtime::new();
+        rt.run(|| {
+            let flag = Arc::new(AtomicBool::new(false));
+
+            let setter = flag.clone();
+            async move {
+                3.s().sleep().await;
+                setter.store(true, Ordering::Relaxed);
+            }
+            .spawn();
+
+            async move {
+                crate::eventually!(flag.load(Ordering::Relaxed), within: 5.s());
+            }
+            .primary()
+            .spawn();
+        });
+
+        // the backoff caps at 1s so the condition is noticed shortly after it becomes true
+        let elapsed = rt.elapsed();
+        assert!(elapsed >= 3.s() && elapsed <= 4.s(), "{elapsed:?}");
+    }
+
+    #[test]
+    fn eventually_timeout() {
+        let mut rt = Runtime::new();
+        let message = rt.run(|| {
+            let (sender, receiver) = Queue::default().channel();
+            async move {
+                let check = async {
+                    crate::eventually!(false, within: 5.s(), "never true");
+                };
+                let message = match check.catch_unwind().await {
+                    Ok(()) => String::new(),
+                    Err(panic) => *panic.downcast::<String>().unwrap(),
+                };
+                sender.try_push(message).unwrap();
+            }
+            .primary()
+            .spawn();
+            receiver
+        });
+
+        let message = message.try_pop().unwrap();
+        assert!(message.starts_with("condition `false` was not satisfied within 5s"));
+        assert!(message.contains("never true"));
+        assert_eq!(rt.elapsed(), 5.s());
+    }
+}
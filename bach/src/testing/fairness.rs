@@ -0,0 +1,230 @@
+//! Per-window resource share reports for competing workloads
+//!
+//! Each workload records the amount of resource it consumed, like bytes sent or requests
+//! served, with [`Fairness::record`]. The amounts are bucketed into windows of simulated time
+//! and summarized with [Jain's fairness index], which ranges from `1 / n` when a single entity
+//! gets everything to `1.0` when all `n` entities get an equal share.
+//!
+//! ```no_run
+//! # use bach::{environment::default::Runtime, ext::*, testing::Fairness};
+//! # #[derive(Clone)]
+//! # struct Limiter;
+//! # impl Limiter {
+//! #     async fn send(&self, _flow: &str) -> usize {
+//! #         1
+//! #     }
+//! # }
+//! # let limiter = Limiter;
+//! # let mut rt = Runtime::new();
+//! # let fairness = rt.run(|| {
+//! let fairness = Fairness::new(100.ms());
+//!
+//! for flow in ["a", "b", "c"] {
+//!     let fairness = fairness.clone();
+//!     # let limiter = limiter.clone();
+//!     async move {
+//!         loop {
+//!             let len = limiter.send(flow).await;
+//!             fairness.record(flow, len as u64);
+//!         }
+//!     }
+//!     .spawn();
+//! }
+//! # fairness
+//! # });
+//!
+//! // after the simulation
+//! let report = fairness.report();
+//! assert!(report.min_index() > 0.9, "{report}");
+//! ```
+//!
+//! [Jain's fairness index]: https://en.wikipedia.org/wiki/Fairness_measure
+
+use crate::time::{Duration, Instant};
+use alloc::sync::Arc;
+use core::fmt;
+use std::{collections::BTreeMap, sync::Mutex};
+
+#[derive(Clone)]
+pub struct Fairness {
+    window: Duration,
+    state: Arc<Mutex<BTreeMap<u64, BTreeMap<String, u64>>>>,
+}
+
+impl fmt::Debug for Fairness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fairness")
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Fairness {
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(window: Duration) -> Self {
+        assert!(!window.is_zero(), "window must be greater than 0");
+        Self {
+            window,
+            state: Default::default(),
+        }
+    }
+
+    /// Records that `entity` consumed `amount` of the resource at the current time
+    pub fn record<E: fmt::Display>(&self, entity: E, amount: u64) {
+        let now = Instant::now().elapsed_since_start();
+        let index = (now.as_nanos() / self.window.as_nanos()) as u64;
+
+        *self
+            .state
+            .lock()
+            .unwrap()
+            .entry(index)
+            .or_default()
+            .entry(entity.to_string())
+            .or_default() += amount;
+    }
+
+    /// Returns the shares for every window from the first to the last recording
+    ///
+    /// Every entity that recorded anything is included in each window, even if it didn't
+    /// consume anything during it.
+    pub fn report(&self) -> Report {
+        let state = self.state.lock().unwrap();
+
+        let entities: Vec<String> = state
+            .values()
+            .flat_map(|window| window.keys())
+            .cloned()
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let (Some(first), Some(last)) = (state.keys().next(), state.keys().last()) else {
+            return Report::default();
+        };
+
+        let windows = (*first..=*last)
+            .map(|index| {
+                let recorded = state.get(&index);
+                let amounts = entities
+                    .iter()
+                    .map(|entity| {
+                        let amount = recorded
+                            .and_then(|window| window.get(entity))
+                            .copied()
+                            .unwrap_or(0);
+                        (entity.clone(), amount)
+                    })
+                    .collect();
+                Window {
+                    start: self.window * index as u32,
+                    amounts,
+                }
+            })
+            .collect();
+
+        Report { windows }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    windows: Vec<Window>,
+}
+
+impl Report {
+    pub fn windows(&self) -> &[Window] {
+        &self.windows
+    }
+
+    /// Returns the fairness index over the whole run
+    pub fn index(&self) -> f64 {
+        let mut totals = BTreeMap::<&str, u64>::new();
+        for window in &self.windows {
+            for (entity, amount) in &window.amounts {
+                *totals.entry(entity).or_default() += amount;
+            }
+        }
+        jain_index(totals.into_values())
+    }
+
+    /// Returns the lowest fairness index of any window, ignoring windows where nothing was
+    /// consumed
+    pub fn min_index(&self) -> f64 {
+        self.windows
+            .iter()
+            .filter(|window| window.total() > 0)
+            .map(Window::index)
+            .reduce(f64::min)
+            .unwrap_or(1.0)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "overall index: {:.3}", self.index())?;
+        for window in &self.windows {
+            write!(f, "{:?}: index={:.3}", window.start, window.index())?;
+            for entity in window.amounts.keys() {
+                write!(f, " {entity}={:.1}%", window.share(entity) * 100.0)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Window {
+    /// The time since the start of the simulation that the window began
+    pub start: Duration,
+    amounts: BTreeMap<String, u64>,
+}
+
+impl Window {
+    /// Returns the amount each entity consumed during the window
+    pub fn amounts(&self) -> &BTreeMap<String, u64> {
+        &self.amounts
+    }
+
+    pub fn total(&self) -> u64 {
+        self.amounts.values().sum()
+    }
+
+    /// Returns the fraction of the window's total that was consumed by `entity`
+    ///
+    /// Returns `0.0` if nothing was consumed during the window.
+    pub fn share(&self, entity: &str) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        let amount = self.amounts.get(entity).copied().unwrap_or(0);
+        amount as f64 / total as f64
+    }
+
+    pub fn index(&self) -> f64 {
+        jain_index(self.amounts.values().copied())
+    }
+}
+
+/// Computes `(sum x)^2 / (n * sum x^2)`, treating an empty or all-zero set as perfectly fair
+fn jain_index<I: IntoIterator<Item = u64>>(amounts: I) -> f64 {
+    let mut n = 0.0;
+    let mut sum = 0.0;
+    let mut sum_squares = 0.0;
+    for amount in amounts {
+        let amount = amount as f64;
+        n += 1.0;
+        sum += amount;
+        sum_squares += amount * amount;
+    }
+
+    if sum_squares == 0.0 {
+        return 1.0;
+    }
+
+    sum * sum / (n * sum_squares)
+}
@@ -0,0 +1,250 @@
+//! Correlated fault plans based on failure domains
+//!
+//! Real outages rarely take down a single node at a time. Power, networking, and deploys are
+//! usually shared by a whole rack or availability zone, so a [`Topology`] tags each group with
+//! the failure domains it belongs to and the generated [`Plan`] takes out every group in a domain
+//! at once.
+//!
+//! ```no_run
+//! # use bach::{environment::default::Runtime, ext::*, group::Group, testing::Topology};
+//! # Runtime::new().run(|| {
+//! let topology = Topology::new()
+//!     .with_node(Group::new("node-0"), [("az", "a"), ("rack", "a1")])
+//!     .with_node(Group::new("node-1"), [("az", "a"), ("rack", "a2")])
+//!     .with_node(Group::new("node-2"), [("az", "b"), ("rack", "b1")]);
+//!
+//! let plan = topology
+//!     .planner("az")
+//!     .with_faults(3)
+//!     .with_window(60.s())
+//!     .with_duration(1.s()..=10.s())
+//!     .plan();
+//!
+//! plan.inject();
+//! # });
+//! ```
+//!
+//! Plans are generated with [`crate::rand`] so the same seed always produces the same faults.
+//! [`Plan::inject`] models each fault by pausing the affected groups. Scenarios with their own
+//! network model can instead iterate over [`Plan::faults`] to partition the groups.
+
+use crate::{
+    executor::JoinHandle,
+    group::Group,
+    rand::*,
+    time::{
+        resolution::{duration_to_ticks, ticks_to_duration},
+        Duration, Instant,
+    },
+};
+use core::{fmt, ops::RangeInclusive};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The failure domains that each group belongs to
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    nodes: BTreeMap<Group, BTreeMap<String, String>>,
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a group with its failure domains, given as `(level, domain)` pairs like `("az", "a")`
+    pub fn with_node<I, L, D>(mut self, group: Group, domains: I) -> Self
+    where
+        I: IntoIterator<Item = (L, D)>,
+        L: Into<String>,
+        D: Into<String>,
+    {
+        self.add_node(group, domains);
+        self
+    }
+
+    /// Adds a group with its failure domains, replacing any that were previously added for it
+    pub fn add_node<I, L, D>(&mut self, group: Group, domains: I)
+    where
+        I: IntoIterator<Item = (L, D)>,
+        L: Into<String>,
+        D: Into<String>,
+    {
+        let domains = domains
+            .into_iter()
+            .map(|(level, domain)| (level.into(), domain.into()))
+            .collect();
+        self.nodes.insert(group, domains);
+    }
+
+    /// Returns the distinct domains at the given level, sorted by name
+    pub fn domains(&self, level: &str) -> Vec<&str> {
+        let domains: BTreeSet<_> = self
+            .nodes
+            .values()
+            .filter_map(|domains| domains.get(level))
+            .map(String::as_str)
+            .collect();
+        domains.into_iter().collect()
+    }
+
+    /// Returns the groups in the given domain, in creation order
+    pub fn groups_in(&self, level: &str, domain: &str) -> Vec<Group> {
+        self.nodes
+            .iter()
+            .filter(|(_, domains)| domains.get(level).is_some_and(|d| d == domain))
+            .map(|(group, _)| *group)
+            .collect()
+    }
+
+    /// Returns a planner for faults that take out a whole domain at the given level
+    pub fn planner(&self, level: &str) -> Planner<'_> {
+        Planner {
+            topology: self,
+            level: level.to_owned(),
+            faults: 1,
+            window: Duration::from_secs(10),
+            duration: Duration::from_secs(1)..=Duration::from_secs(1),
+        }
+    }
+}
+
+/// Generates a [`Plan`] for a single level of a [`Topology`]
+#[derive(Clone, Debug)]
+pub struct Planner<'a> {
+    topology: &'a Topology,
+    level: String,
+    faults: usize,
+    window: Duration,
+    duration: RangeInclusive<Duration>,
+}
+
+impl Planner<'_> {
+    /// Sets the number of faults to generate, which defaults to `1`
+    ///
+    /// The same domain can be picked more than once.
+    pub fn with_faults(mut self, faults: usize) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Sets the window of time, starting from when the plan is injected, that faults can start
+    /// in, which defaults to 10s
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the range of time that each fault lasts, which defaults to 1s
+    pub fn with_duration(mut self, duration: RangeInclusive<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Generates the faults with the simulation RNG
+    ///
+    /// # Panics
+    ///
+    /// Panics if the topology doesn't have any domains at the planner's level.
+    pub fn plan(&self) -> Plan {
+        let domains = self.topology.domains(&self.level);
+        assert!(
+            !domains.is_empty(),
+            "the topology doesn't have any domains at level {:?}",
+            self.level
+        );
+
+        let mut faults: Vec<_> = (0..self.faults)
+            .map(|_| {
+                let domain = *choose(&domains).unwrap();
+                Fault {
+                    start: between(Duration::ZERO..=self.window),
+                    duration: between(self.duration.clone()),
+                    domain: domain.to_owned(),
+                    groups: self.topology.groups_in(&self.level, domain),
+                }
+            })
+            .collect();
+
+        faults.sort_by_key(|fault| fault.start);
+
+        Plan {
+            level: self.level.clone(),
+            faults,
+        }
+    }
+}
+
+/// Picks a duration in the range, rounded down to the timer resolution
+fn between(range: RangeInclusive<Duration>) -> Duration {
+    let start = duration_to_ticks(*range.start());
+    let end = duration_to_ticks(*range.end());
+    ticks_to_duration((start..=end.max(start)).any())
+}
+
+/// A set of correlated faults, ordered by when they start
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plan {
+    level: String,
+    faults: Vec<Fault>,
+}
+
+/// An outage of every group in a single failure domain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fault {
+    /// The time after the plan is injected that the fault starts
+    pub start: Duration,
+    /// How long the fault lasts
+    pub duration: Duration,
+    /// The domain that was taken out
+    pub domain: String,
+    /// The groups in the domain
+    pub groups: Vec<Group>,
+}
+
+impl Plan {
+    /// Returns the level of the domains that the faults take out
+    pub fn level(&self) -> &str {
+        &self.level
+    }
+
+    pub fn faults(&self) -> &[Fault] {
+        &self.faults
+    }
+
+    /// Pauses the groups in each domain for the duration of its fault
+    ///
+    /// The returned handle can be cancelled to stop injecting any faults that haven't started
+    /// yet.
+    pub fn inject(&self) -> JoinHandle<()> {
+        let start = Instant::now();
+        let faults = self.faults.clone();
+        let inject = async move {
+            for fault in faults {
+                crate::time::sleep_until(start + fault.start).await;
+                for group in &fault.groups {
+                    group.pause(fault.duration);
+                }
+            }
+        };
+        // spawned outside of the current group so the plan isn't frozen by one of its own faults
+        crate::task::scope::borrow_with(|handle| handle.spawn_daemon_named(inject, "fault_plan"))
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for fault in &self.faults {
+            write!(
+                f,
+                "{:?} for {:?}: {}={}",
+                fault.start, fault.duration, self.level, fault.domain
+            )?;
+            for (idx, group) in fault.groups.iter().enumerate() {
+                let sep = if idx == 0 { " " } else { ", " };
+                write!(f, "{sep}{group}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
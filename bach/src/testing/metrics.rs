@@ -0,0 +1,695 @@
+//! An in-memory metrics registry for asserting on simulation metrics
+//!
+//! Calling [`capture_metrics`] records every [`count!`](crate::count) and
+//! [`measure!`](crate::measure) emitted on the current thread, which can then be checked with the
+//! [`assert_counter!`](crate::assert_counter) and
+//! [`assert_measure_within!`](crate::assert_measure_within) macros.
+//!
+//! ```no_run
+//! # use bach::ext::*;
+//! let metrics = bach::testing::metrics::capture_metrics();
+//! bach::environment::default::Runtime::new().run(|| { /* ... */ });
+//! bach::assert_counter!(metrics, "spawn", 3);
+//! bach::assert_measure_within!(metrics, "sleep", max, ..=1.0);
+//! ```
+//!
+//! Measured durations are recorded in seconds.
+//!
+//! The registry is scoped to a single simulation: it is cleared whenever a
+//! [`Runtime`](crate::environment::default::Runtime) is created, and a snapshot is kept when the
+//! runtime is dropped. This keeps the values meaningful when a test runs many iterations, e.g.
+//! with `bolero`. The snapshot of the last completed run is available through
+//! [`MetricsCapture::previous_run`].
+//!
+//! Startup behavior can be excluded from the statistics with [`MetricsCapture::with_window`],
+//! which only records metrics emitted within a window of simulated time.
+//!
+//! Key metrics can also be checked against a recorded [`Baseline`] to catch performance
+//! regressions.
+
+use super::stats::Estimate;
+use crate::time::{Duration, Instant};
+use alloc::sync::Arc;
+use core::{
+    cell::RefCell,
+    fmt,
+    ops::{Range, RangeBounds},
+    sync::atomic::Ordering,
+};
+use metrics::{
+    atomics::AtomicU64, Counter, Gauge, Histogram, HistogramFn, Key, KeyName, LocalRecorderGuard,
+    Metadata, Recorder, SharedString, Unit,
+};
+use std::{collections::BTreeMap, sync::Mutex};
+
+pub mod baseline;
+
+pub use baseline::Baseline;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Capture>>> = const { RefCell::new(None) };
+}
+
+#[derive(Default)]
+struct Capture {
+    registry: Registry,
+    previous: Mutex<Option<Arc<Registry>>>,
+}
+
+/// Records the metrics emitted on the current thread until the returned guard is dropped
+pub fn capture_metrics() -> MetricsCapture {
+    static DISPATCH: Dispatch = Dispatch;
+
+    let capture = Arc::new(Capture::default());
+    let prev = CURRENT.with(|current| current.borrow_mut().replace(capture.clone()));
+    let guard = metrics::set_default_local_recorder(&DISPATCH);
+
+    MetricsCapture {
+        capture,
+        prev,
+        _guard: guard,
+    }
+}
+
+/// Clears the current registry at the start of a simulation
+pub(crate) fn start_run() {
+    CURRENT.with(|current| {
+        if let Some(capture) = current.borrow().as_ref() {
+            capture.registry.clear();
+        }
+    });
+}
+
+/// Keeps a snapshot of the current registry at the end of a simulation
+pub(crate) fn finish_run() {
+    CURRENT.with(|current| {
+        if let Some(capture) = current.borrow().as_ref() {
+            let snapshot = Arc::new(capture.registry.snapshot());
+            *capture.previous.lock().unwrap() = Some(snapshot);
+        }
+    });
+}
+
+/// A guard returned by [`capture_metrics`]
+pub struct MetricsCapture {
+    capture: Arc<Capture>,
+    prev: Option<Arc<Capture>>,
+    _guard: LocalRecorderGuard<'static>,
+}
+
+impl MetricsCapture {
+    /// Only records metrics emitted after `warmup` has elapsed in each simulation, and for
+    /// `duration` after that, if provided
+    ///
+    /// Metrics emitted outside of a simulation are always recorded.
+    pub fn with_window(self, warmup: Duration, duration: Option<Duration>) -> Self {
+        let end = duration.map_or(Duration::MAX, |duration| warmup.saturating_add(duration));
+        *self.capture.registry.window.lock().unwrap() = Some(warmup..end);
+        self
+    }
+
+    /// Returns the metrics of the last simulation that completed
+    pub fn previous_run(&self) -> Option<Arc<Registry>> {
+        self.capture.previous.lock().unwrap().clone()
+    }
+}
+
+impl core::ops::Deref for MetricsCapture {
+    type Target = Registry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.capture.registry
+    }
+}
+
+impl Drop for MetricsCapture {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT.with(|current| *current.borrow_mut() = prev);
+    }
+}
+
+/// The recorded values of every metric
+#[derive(Debug, Default)]
+pub struct Registry {
+    counters: Mutex<BTreeMap<Key, Arc<AtomicU64>>>,
+    measures: Mutex<BTreeMap<Key, Arc<Samples>>>,
+    /// The period of simulated time that metrics are recorded for
+    window: Mutex<Option<Range<Duration>>>,
+}
+
+impl Registry {
+    /// Returns the total of all counters with the given name and labels
+    ///
+    /// Counters that have additional labels beyond the ones provided are included in the total.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, _)| matches(key, name, labels))
+            .map(|(_, value)| value.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Returns a summary of all of the measurements with the given name and labels
+    ///
+    /// Measurements that have additional labels beyond the ones provided are included in the
+    /// summary.
+    pub fn measure(&self, name: &str, labels: &[(&str, &str)]) -> Summary {
+        let mut summary = Summary::default();
+        for (key, samples) in self.measures.lock().unwrap().iter() {
+            if matches(key, name, labels) {
+                summary
+                    .samples
+                    .extend_from_slice(&samples.0.lock().unwrap());
+            }
+        }
+        summary
+    }
+
+    /// Returns a copy of the current values
+    pub fn snapshot(&self) -> Registry {
+        let counters = self
+            .counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| {
+                let value = AtomicU64::new(value.load(Ordering::Relaxed));
+                (key.clone(), Arc::new(value))
+            })
+            .collect();
+        let measures = self
+            .measures
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, samples)| {
+                let samples = samples.0.lock().unwrap().clone();
+                (key.clone(), Arc::new(Samples(Mutex::new(samples))))
+            })
+            .collect();
+        Registry {
+            counters: Mutex::new(counters),
+            measures: Mutex::new(measures),
+            window: Mutex::new(None),
+        }
+    }
+
+    fn clear(&self) {
+        self.counters.lock().unwrap().clear();
+        self.measures.lock().unwrap().clear();
+    }
+
+    /// Returns `true` if metrics emitted at the current time should be recorded
+    fn is_recording(&self) -> bool {
+        let Some(window) = self.window.lock().unwrap().clone() else {
+            return true;
+        };
+        Instant::try_now().map_or(true, |now| window.contains(&now.elapsed_since_start()))
+    }
+
+    fn register_counter(&self, key: &Key) -> Counter {
+        if !self.is_recording() {
+            return Counter::noop();
+        }
+
+        let counter = self
+            .counters
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        Counter::from_arc(counter)
+    }
+
+    fn register_measure(&self, key: &Key) -> Histogram {
+        if !self.is_recording() {
+            return Histogram::noop();
+        }
+
+        let samples = self
+            .measures
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        Histogram::from_arc(samples)
+    }
+}
+
+impl fmt::Display for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in self.counters.lock().unwrap().iter() {
+            writeln!(f, "{} = {}", DisplayKey(key), value.load(Ordering::Relaxed))?;
+        }
+        for (key, samples) in self.measures.lock().unwrap().iter() {
+            let summary = Summary {
+                samples: samples.0.lock().unwrap().clone(),
+            };
+            writeln!(f, "{} = {summary}", DisplayKey(key))?;
+        }
+        Ok(())
+    }
+}
+
+/// Aggregates over a set of measurements
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    samples: Vec<f64>,
+}
+
+impl Summary {
+    /// Returns all of the recorded measurements
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+
+    pub fn count(&self) -> f64 {
+        self.samples.len() as f64
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.samples.iter().sum()
+    }
+
+    /// Returns the smallest measurement, or `NaN` if there are none
+    pub fn min(&self) -> f64 {
+        self.samples
+            .iter()
+            .copied()
+            .reduce(f64::min)
+            .unwrap_or(f64::NAN)
+    }
+
+    /// Returns the largest measurement, or `NaN` if there are none
+    pub fn max(&self) -> f64 {
+        self.samples
+            .iter()
+            .copied()
+            .reduce(f64::max)
+            .unwrap_or(f64::NAN)
+    }
+
+    /// Returns the average measurement, or `NaN` if there are none
+    pub fn mean(&self) -> f64 {
+        self.sum() / self.count()
+    }
+
+    /// Returns the measurement at the given quantile, between `0.0` and `1.0`, or `NaN` if there
+    /// are none
+    ///
+    /// Uses the nearest-rank method so the result is always one of the samples.
+    pub fn quantile(&self, quantile: f64) -> f64 {
+        if self.samples.is_empty() {
+            return f64::NAN;
+        }
+
+        let mut samples = self.samples.clone();
+        samples.sort_by(f64::total_cmp);
+
+        let rank = (quantile.clamp(0.0, 1.0) * samples.len() as f64).ceil() as usize;
+        samples[rank.saturating_sub(1)]
+    }
+
+    pub fn p50(&self) -> f64 {
+        self.quantile(0.5)
+    }
+
+    pub fn p90(&self) -> f64 {
+        self.quantile(0.9)
+    }
+
+    pub fn p99(&self) -> f64 {
+        self.quantile(0.99)
+    }
+
+    /// Estimates the mean measurement, treating every sample as an independent observation
+    pub fn estimate(&self) -> Estimate {
+        Estimate::new(self.samples.iter().copied())
+    }
+
+    /// Estimates the mean measurement from the averages of `batches` consecutive groups of samples
+    ///
+    /// See [`stats::batch_means`](super::stats::batch_means).
+    pub fn batch_means(&self, batches: usize) -> Estimate {
+        Estimate::from_batch_means(&self.samples, batches)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count={} sum={} min={} max={} mean={} p50={} p99={}",
+            self.count(),
+            self.sum(),
+            self.min(),
+            self.max(),
+            self.mean(),
+            self.p50(),
+            self.p99(),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Samples(Mutex<Vec<f64>>);
+
+impl HistogramFn for Samples {
+    fn record(&self, value: f64) {
+        self.0.lock().unwrap().push(value);
+    }
+}
+
+/// Forwards metrics to the registry installed on the current thread
+struct Dispatch;
+
+impl Recorder for Dispatch {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        CURRENT
+            .with(|current| Some(current.borrow().as_ref()?.registry.register_counter(key)))
+            .unwrap_or_else(Counter::noop)
+    }
+
+    fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+        // bach doesn't currently emit any gauges
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        CURRENT
+            .with(|current| Some(current.borrow().as_ref()?.registry.register_measure(key)))
+            .unwrap_or_else(Histogram::noop)
+    }
+}
+
+fn matches(key: &Key, name: &str, labels: &[(&str, &str)]) -> bool {
+    key.name() == name
+        && labels.iter().all(|(k, v)| {
+            key.labels()
+                .any(|label| label.key() == *k && label.value() == *v)
+        })
+}
+
+struct DisplayKey<'a>(&'a Key);
+
+impl fmt::Display for DisplayKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.name())?;
+        let mut labels = self.0.labels().peekable();
+        if labels.peek().is_some() {
+            write!(f, "{{")?;
+            for (idx, label) in labels.enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}={:?}", label.key(), label.value())?;
+            }
+            write!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+struct DisplayLabels<'a>(&'a [(&'a str, &'a str)]);
+
+impl fmt::Display for DisplayLabels<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "{{")?;
+        for (idx, (key, value)) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{key}={value:?}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_counter(registry: &Registry, name: &str, labels: &[(&str, &str)], expected: u64) {
+    let actual = registry.counter(name, labels);
+    if actual != expected {
+        panic!(
+            "counter `{name}{}` mismatch\n  expected: {expected}\n    actual: {actual}\n\nrecorded metrics:\n{registry}",
+            DisplayLabels(labels),
+        );
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_measure_within<R: RangeBounds<f64> + fmt::Debug>(
+    registry: &Registry,
+    name: &str,
+    labels: &[(&str, &str)],
+    stat: &str,
+    aggregate: fn(&Summary) -> f64,
+    range: R,
+) {
+    let summary = registry.measure(name, labels);
+    let actual = aggregate(&summary);
+    if !range.contains(&actual) {
+        panic!(
+            "measure `{name}{}` {stat} out of range\n  expected: {range:?}\n    actual: {actual}\n\nrecorded metrics:\n{registry}",
+            DisplayLabels(labels),
+        );
+    }
+}
+
+/// Asserts that the total of a counter equals the expected value
+///
+/// Labels can optionally be provided to narrow down which counters are included.
+///
+/// ```no_run
+/// # let metrics = bach::testing::metrics::capture_metrics();
+/// bach::assert_counter!(metrics, "wake", 2, "target" = "server");
+/// ```
+#[macro_export]
+macro_rules! assert_counter {
+    ($registry:expr, $name:literal, $expected:expr $(, $key:literal = $v:expr)* $(,)?) => {
+        $crate::testing::metrics::assert_counter(
+            &$registry,
+            $name,
+            &[$(($key, $v.to_string().as_str())),*],
+            $expected,
+        )
+    };
+}
+
+/// Asserts that an aggregate of a measurement falls within the expected range
+///
+/// The aggregate is one of `count`, `sum`, `min`, `max`, `mean`, `p50`, `p90` or `p99`. Labels
+/// can optionally be provided to narrow down which measurements are included.
+///
+/// ```no_run
+/// # let metrics = bach::testing::metrics::capture_metrics();
+/// bach::assert_measure_within!(metrics, "sojourn_time", mean, 0.0..0.5);
+/// ```
+#[macro_export]
+macro_rules! assert_measure_within {
+    ($registry:expr, $name:literal, $stat:ident, $range:expr $(, $key:literal = $v:expr)* $(,)?) => {
+        $crate::testing::metrics::assert_measure_within(
+            &$registry,
+            $name,
+            &[$(($key, $v.to_string().as_str())),*],
+            stringify!($stat),
+            $crate::testing::metrics::Summary::$stat,
+            $range,
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::default::Runtime, ext::*};
+
+    fn run() {
+        Runtime::new().run(|| {
+            for delay in [1, 2, 3] {
+                async move {
+                    delay.s().sleep().await;
+                }
+                .primary()
+                .spawn_named("sleeper");
+            }
+        });
+    }
+
+    #[test]
+    fn counters_and_measures() {
+        let metrics = capture_metrics();
+        run();
+
+        crate::assert_counter!(metrics, "spawn", 3);
+        crate::assert_measure_within!(metrics, "sleep", count, 3.0..=3.0);
+        crate::assert_measure_within!(metrics, "sleep", max, 2.9..3.1);
+        crate::assert_measure_within!(metrics, "sleep", mean, 1.9..2.1);
+    }
+
+    #[test]
+    fn scoped_to_guard() {
+        {
+            let metrics = capture_metrics();
+            run();
+            crate::assert_counter!(metrics, "spawn", 3);
+        }
+
+        let metrics = capture_metrics();
+        crate::assert_counter!(metrics, "spawn", 0);
+    }
+
+    #[test]
+    fn flow_percentiles() {
+        use crate::sync::{channel, queue::vec_deque};
+
+        let metrics = capture_metrics();
+
+        Runtime::new().run(|| {
+            for (flow, delay) in [("a->b", 10), ("b->a", 50)] {
+                let queue = vec_deque::Queue::default().sojourn().with_flow(flow);
+                let (sender, receiver) = channel::new(queue);
+
+                async move {
+                    for i in 0..10 {
+                        sender.push(i).await.unwrap();
+                    }
+                }
+                .primary()
+                .spawn();
+
+                async move {
+                    while receiver.pop().await.is_ok() {
+                        delay.ms().sleep().await;
+                    }
+                }
+                .primary()
+                .spawn();
+            }
+        });
+
+        // items wait behind each other in the queue so the latencies grow linearly
+        crate::assert_measure_within!(metrics, "sojourn_time", p50, 0.03..0.06, "flow" = "a->b");
+        crate::assert_measure_within!(metrics, "sojourn_time", p99, 0.08..0.1, "flow" = "a->b");
+        crate::assert_measure_within!(metrics, "sojourn_time", p99, 0.4..0.5, "flow" = "b->a");
+
+        let summary = metrics.measure("sojourn_time", &[("flow", "a->b")]);
+        assert_eq!(summary.quantile(0.0), summary.min());
+        assert_eq!(summary.quantile(1.0), summary.max());
+    }
+
+    #[test]
+    fn per_run() {
+        let metrics = capture_metrics();
+
+        run();
+        assert!(metrics.previous_run().is_some());
+
+        // each runtime starts with an empty registry
+        let mut rt = Runtime::new();
+        crate::assert_counter!(metrics, "spawn", 0);
+
+        rt.run(|| {
+            async {}.primary().spawn();
+        });
+        crate::assert_counter!(metrics, "spawn", 1);
+
+        // the previous run is still available while the next is running
+        let previous = metrics.previous_run().unwrap();
+        crate::assert_counter!(previous, "spawn", 3);
+
+        drop(rt);
+        crate::assert_counter!(metrics.previous_run().unwrap(), "spawn", 1);
+    }
+
+    #[test]
+    fn task_tags() {
+        let metrics = capture_metrics();
+
+        Runtime::new().run(|| {
+            for request in [1, 2] {
+                async move {
+                    crate::task::set_tag("request", request);
+                    count!("work");
+                    count!("work", "request" = "explicit");
+                    crate::task::remove_tag("request");
+                    count!("work");
+                }
+                .primary()
+                .spawn();
+            }
+        });
+
+        crate::assert_counter!(metrics, "work", 6);
+        crate::assert_counter!(metrics, "work", 1, "request" = 1);
+        crate::assert_counter!(metrics, "work", 1, "request" = 2);
+        crate::assert_counter!(metrics, "work", 2, "request" = "explicit");
+    }
+
+    #[test]
+    fn federated_run() {
+        use crate::environment::federation::Federation;
+
+        let metrics = capture_metrics();
+
+        let mut federation = Federation::new();
+        let a = federation.add(Runtime::new());
+        federation.enter(a, || {
+            count!("work");
+        });
+
+        // the members of a federation share a single run
+        let b = federation.add(Runtime::new());
+        federation.enter(b, || {
+            count!("work");
+        });
+        crate::assert_counter!(metrics, "work", 2);
+
+        drop(federation);
+        crate::assert_counter!(metrics.previous_run().unwrap(), "work", 2);
+    }
+
+    #[test]
+    fn measurement_window() {
+        let metrics = capture_metrics().with_window(1500.ms(), Some(1.s()));
+
+        Runtime::new().run(|| {
+            async {
+                for _ in 0..5 {
+                    count!("tick");
+                    1.s().sleep().await;
+                }
+            }
+            .primary()
+            .spawn();
+        });
+
+        // ticks happen every second, so only the one at 2s is within the window
+        crate::assert_counter!(metrics, "tick", 1);
+        // the task is spawned during the warmup
+        crate::assert_counter!(metrics, "spawn", 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "counter `spawn` mismatch")]
+    fn counter_mismatch() {
+        let metrics = capture_metrics();
+        run();
+        crate::assert_counter!(metrics, "spawn", 4);
+    }
+}
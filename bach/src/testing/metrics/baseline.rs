@@ -0,0 +1,454 @@
+//! Regression checks against recorded metrics
+//!
+//! A [`Baseline`] tracks a handful of key metrics, like tail latency, throughput, or drops, and
+//! compares them against the values stored in a file, which is meant to be checked in alongside
+//! the test. Runs fail if any of the metrics regressed by more than its [`Tolerance`].
+//!
+//! ```no_run
+//! # use bach::{
+//! #     environment::default::Runtime,
+//! #     testing::metrics::baseline::{Baseline, Stat, Tolerance},
+//! # };
+//! let metrics = bach::testing::metrics::capture_metrics();
+//! Runtime::new().run(|| { /* ... */ });
+//!
+//! Baseline::new("tests/baselines/link.json")
+//!     .with_measure("sojourn_time", &[("flow", "a->b")], Stat::P99, Tolerance::higher(0.1))
+//!     .with_measure("throughput_items", &[], Stat::Mean, Tolerance::lower(0.05))
+//!     .with_counter("drop", &[], Tolerance::higher(0.0).with_absolute(5.0))
+//!     .check(&metrics);
+//! ```
+//!
+//! Setting the `BACH_UPDATE_BASELINES` environment variable to `1` records the current values
+//! instead of checking them, which is how new baselines are created and intentional changes are
+//! accepted.
+//!
+//! The file is a JSON object that maps each metric, written as `name{labels} stat`, to its value.
+//! It has one metric per line so changes are easy to review.
+
+use super::{DisplayLabels, Registry, Summary};
+use core::fmt;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// The environment variable that records the current values instead of checking them
+pub const UPDATE_ENV: &str = "BACH_UPDATE_BASELINES";
+
+/// The aggregate of a metric that is compared against the baseline
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    /// The total of a counter
+    Total,
+    Count,
+    Sum,
+    Min,
+    Max,
+    Mean,
+    P50,
+    P90,
+    P99,
+}
+
+impl Stat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Total => "total",
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Mean => "mean",
+            Self::P50 => "p50",
+            Self::P90 => "p90",
+            Self::P99 => "p99",
+        }
+    }
+
+    fn aggregate(&self, summary: &Summary) -> f64 {
+        match self {
+            Self::Total | Self::Sum => summary.sum(),
+            Self::Count => summary.count(),
+            Self::Min => summary.min(),
+            Self::Max => summary.max(),
+            Self::Mean => summary.mean(),
+            Self::P50 => summary.p50(),
+            Self::P90 => summary.p90(),
+            Self::P99 => summary.p99(),
+        }
+    }
+}
+
+/// How far a metric can move in the wrong direction before it's considered a regression
+///
+/// Movement in the other direction is never a regression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    higher_is_worse: bool,
+    relative: f64,
+    absolute: f64,
+}
+
+impl Tolerance {
+    /// Allows the metric to increase by a `relative` fraction of the baseline, for metrics like
+    /// latency or drops where lower is better
+    pub fn higher(relative: f64) -> Self {
+        Self {
+            higher_is_worse: true,
+            relative,
+            absolute: 0.0,
+        }
+    }
+
+    /// Allows the metric to decrease by a `relative` fraction of the baseline, for metrics like
+    /// throughput where higher is better
+    pub fn lower(relative: f64) -> Self {
+        Self {
+            higher_is_worse: false,
+            relative,
+            absolute: 0.0,
+        }
+    }
+
+    /// Allows an additional fixed amount of movement
+    ///
+    /// This is useful for metrics with a baseline at or near zero, where any relative tolerance
+    /// would be too strict.
+    pub fn with_absolute(mut self, absolute: f64) -> Self {
+        self.absolute = absolute;
+        self
+    }
+
+    /// Returns the worst value that is still acceptable for the baseline
+    fn limit(&self, baseline: f64) -> f64 {
+        let slack = baseline.abs() * self.relative + self.absolute;
+        if self.higher_is_worse {
+            baseline + slack
+        } else {
+            baseline - slack
+        }
+    }
+
+    fn is_regression(&self, baseline: f64, actual: f64) -> bool {
+        let limit = self.limit(baseline);
+        if self.higher_is_worse {
+            actual > limit
+        } else {
+            actual < limit
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Metric {
+    name: String,
+    labels: Vec<(String, String)>,
+    stat: Stat,
+    tolerance: Tolerance,
+}
+
+impl Metric {
+    fn value(&self, registry: &Registry) -> f64 {
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        match self.stat {
+            Stat::Total => registry.counter(&self.name, &labels) as f64,
+            stat => stat.aggregate(&registry.measure(&self.name, &labels)),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        write!(
+            f,
+            "{}{} {}",
+            self.name,
+            DisplayLabels(&labels),
+            self.stat.as_str()
+        )
+    }
+}
+
+/// A set of key metrics that are checked against a recorded baseline
+#[derive(Clone, Debug)]
+pub struct Baseline {
+    path: PathBuf,
+    metrics: Vec<Metric>,
+}
+
+impl Baseline {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            metrics: vec![],
+        }
+    }
+
+    /// Tracks an aggregate of the measurements with the given name and labels
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stat` is [`Stat::Total`], which only applies to counters.
+    pub fn with_measure(
+        mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        stat: Stat,
+        tolerance: Tolerance,
+    ) -> Self {
+        assert_ne!(stat, Stat::Total, "use `with_counter` for counters");
+        self.push(name, labels, stat, tolerance);
+        self
+    }
+
+    /// Tracks the total of the counters with the given name and labels
+    pub fn with_counter(
+        mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        tolerance: Tolerance,
+    ) -> Self {
+        self.push(name, labels, Stat::Total, tolerance);
+        self
+    }
+
+    fn push(&mut self, name: &str, labels: &[(&str, &str)], stat: Stat, tolerance: Tolerance) {
+        self.metrics.push(Metric {
+            name: name.to_owned(),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            stat,
+            tolerance,
+        });
+    }
+
+    /// Returns the current values of the tracked metrics, in the format of the baseline file
+    pub fn render(&self, registry: &Registry) -> String {
+        let mut out = String::from("{");
+        for (idx, metric) in self.metrics.iter().enumerate() {
+            let sep = if idx == 0 { "" } else { "," };
+            let key = escape(&metric.to_string());
+            let value = metric.value(registry);
+            // JSON can't represent NaN or infinities
+            if value.is_finite() {
+                out.push_str(&format!("{sep}\n  \"{key}\": {value:?}"));
+            } else {
+                out.push_str(&format!("{sep}\n  \"{key}\": null"));
+            }
+        }
+        out.push_str("\n}\n");
+        out
+    }
+
+    /// Compares the tracked metrics against the baseline file
+    ///
+    /// The file is written instead if [`UPDATE_ENV`] is set to `1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baseline file can't be read, or any of the metrics regressed or are missing
+    /// from it.
+    #[track_caller]
+    pub fn check(&self, registry: &Registry) {
+        let update = std::env::var(UPDATE_ENV).map_or(false, |value| value == "1");
+        self.check_with(registry, update)
+    }
+
+    #[track_caller]
+    fn check_with(&self, registry: &Registry, update: bool) {
+        if update {
+            self.write(registry);
+            return;
+        }
+
+        let contents = std::fs::read_to_string(&self.path).unwrap_or_else(|err| {
+            panic!(
+                "could not read baseline {}: {err}\n\nrun with {UPDATE_ENV}=1 to record it",
+                self.path.display()
+            )
+        });
+
+        let Some(baseline) = parse(&contents) else {
+            panic!(
+                "baseline {} is not a valid JSON object",
+                self.path.display()
+            );
+        };
+        let mut failures = vec![];
+
+        for metric in &self.metrics {
+            let key = metric.to_string();
+            let actual = metric.value(registry);
+            let Some(&expected) = baseline.get(&key) else {
+                failures.push(format!("  {key}: missing from the baseline"));
+                continue;
+            };
+            if metric.tolerance.is_regression(expected, actual) {
+                failures.push(format!(
+                    "  {key}: regressed from {expected} to {actual} (limit {})",
+                    metric.tolerance.limit(expected)
+                ));
+            }
+        }
+
+        if !failures.is_empty() {
+            panic!(
+                "metrics regressed against {}\n{}\n\nrerun with {UPDATE_ENV}=1 to accept the new values",
+                self.path.display(),
+                failures.join("\n"),
+            );
+        }
+    }
+
+    fn write(&self, registry: &Registry) {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(&self.path, self.render(registry)).unwrap_or_else(|err| {
+            panic!("could not write baseline {}: {err}", self.path.display())
+        });
+    }
+}
+
+fn escape(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    for c in key.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Parses the flat object of strings to numbers written by [`Baseline::render`]
+fn parse(contents: &str) -> Option<BTreeMap<String, f64>> {
+    let contents = contents.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let mut chars = contents.chars();
+    let mut out = BTreeMap::new();
+
+    loop {
+        match chars.by_ref().find(|c| !c.is_whitespace()) {
+            None => return Some(out),
+            Some('"') => {}
+            Some(_) => return None,
+        }
+
+        let mut key = String::new();
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => match chars.next()? {
+                    'n' => key.push('\n'),
+                    c => key.push(c),
+                },
+                c => key.push(c),
+            }
+        }
+
+        let value: String = chars.by_ref().take_while(|c| *c != ',').collect();
+        let value = value.trim().strip_prefix(':')?.trim();
+        let value = if value == "null" {
+            f64::NAN
+        } else {
+            value.parse().ok()?
+        };
+        out.insert(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::default::Runtime, ext::*, testing::metrics::capture_metrics};
+
+    fn run(delay: u64) -> Baseline {
+        let metrics = capture_metrics();
+        Runtime::new().run(|| {
+            for _ in 0..3 {
+                async move {
+                    delay.ms().sleep().await;
+                }
+                .primary()
+                .spawn();
+            }
+        });
+
+        let path = std::env::temp_dir().join(format!("bach-baseline-{}.json", std::process::id()));
+        let baseline = Baseline::new(path)
+            .with_measure("sleep", &[], Stat::P99, Tolerance::higher(0.1))
+            .with_counter("spawn", &[], Tolerance::higher(0.0));
+        let update = !baseline.path.exists();
+        baseline.check_with(&metrics, update);
+        baseline
+    }
+
+    #[test]
+    fn regression() {
+        let baseline = run(100);
+        let contents = std::fs::read_to_string(&baseline.path).unwrap();
+        assert_eq!(
+            contents,
+            "{\n  \"sleep p99\": 0.1,\n  \"spawn total\": 3.0\n}\n"
+        );
+
+        // within the tolerance
+        run(105);
+        // improvements always pass
+        run(50);
+
+        let result = std::panic::catch_unwind(|| run(120));
+        std::fs::remove_file(&baseline.path).unwrap();
+
+        let err = result.unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(
+            message.contains("sleep p99: regressed from 0.1 to 0.12"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn missing_baseline() {
+        let metrics = capture_metrics();
+        let baseline = Baseline::new(std::env::temp_dir().join("bach-baseline-missing.json"))
+            .with_counter("spawn", &[], Tolerance::higher(0.0));
+
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            baseline.check_with(&metrics, false)
+        }))
+        .unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(message.contains("could not read baseline"), "{message}");
+        assert!(!baseline.path.exists());
+    }
+
+    #[test]
+    fn round_trip() {
+        let labels = [("flow", "a->\"b\"")];
+        let baseline = Baseline::new("unused.json")
+            .with_measure("sojourn_time", &labels, Stat::P99, Tolerance::higher(0.1))
+            .with_counter("drop", &[], Tolerance::higher(0.0));
+        let rendered = baseline.render(&capture_metrics());
+
+        let parsed = parse(&rendered).unwrap();
+        let mut keys: Vec<_> = baseline.metrics.iter().map(|m| m.to_string()).collect();
+        keys.sort();
+        assert_eq!(parsed.keys().cloned().collect::<Vec<_>>(), keys);
+        assert_eq!(parsed["drop total"], 0.0);
+    }
+}
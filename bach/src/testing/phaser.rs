@@ -0,0 +1,182 @@
+//! A barrier for structuring multi-node scenarios into phases
+
+use crate::{
+    group::{self, Group},
+    time::{scheduler::Timer, Duration},
+};
+use alloc::sync::Arc;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use event_listener_strategy::event_listener::Event;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+};
+
+/// Coordinates a set of groups through a sequence of phases
+///
+/// Each registered group calls [`Phaser::arrive`] at the end of a phase and is released once all
+/// of the other groups have arrived.
+///
+/// ```no_run
+/// # use bach::{ext::*, group::Group, testing::Phaser};
+/// # async fn example() {
+/// let phaser = Phaser::new().with_timeout(10.s());
+/// phaser.register(Group::new("server"));
+/// phaser.register(Group::new("client"));
+///
+/// // in each group
+/// phaser.arrive("started").await;
+/// phaser.arrive("load applied").await;
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Phaser(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    advanced: Event,
+    timeout: Option<Duration>,
+}
+
+#[derive(Default)]
+struct State {
+    phase: u64,
+    name: Option<String>,
+    participants: BTreeMap<u64, Group>,
+    arrived: BTreeSet<u64>,
+}
+
+impl Phaser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Panics if a phase isn't completed within `timeout` of a group arriving at it
+    ///
+    /// The panic message lists the groups that haven't arrived yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the phaser has been cloned.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("the timeout must be set before cloning the phaser")
+            .timeout = Some(timeout);
+        self
+    }
+
+    /// Adds a group that needs to arrive before each phase completes
+    pub fn register(&self, group: Group) {
+        self.0
+            .state
+            .lock()
+            .unwrap()
+            .participants
+            .insert(group.id(), group);
+    }
+
+    /// Returns the index of the current phase
+    pub fn phase(&self) -> u64 {
+        self.0.state.lock().unwrap().phase
+    }
+
+    /// Returns the registered groups that haven't arrived at the current phase
+    pub fn pending(&self) -> Vec<Group> {
+        let state = self.0.state.lock().unwrap();
+        state
+            .participants
+            .iter()
+            .filter(|(id, _)| !state.arrived.contains(id))
+            .map(|(_, group)| *group)
+            .collect()
+    }
+
+    /// Marks the current group as having reached the end of `phase`, waiting for the rest of
+    /// the groups to arrive
+    ///
+    /// Returns the index of the completed phase.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current group isn't registered, if the groups disagree on the name of the
+    /// phase, or if the timeout elapses.
+    pub async fn arrive(&self, phase: &str) -> u64 {
+        let group = group::current();
+
+        let target = {
+            let mut state = self.0.state.lock().unwrap();
+
+            assert!(
+                state.participants.contains_key(&group.id()),
+                "group {group} is not registered with the phaser"
+            );
+
+            match &state.name {
+                Some(name) => assert_eq!(
+                    name, phase,
+                    "group {group} arrived at phase {phase:?} while the others are at {name:?}"
+                ),
+                None => state.name = Some(phase.to_string()),
+            }
+
+            state.arrived.insert(group.id());
+
+            let target = state.phase;
+
+            if state.arrived.len() == state.participants.len() {
+                state.phase += 1;
+                state.name = None;
+                state.arrived.clear();
+                drop(state);
+
+                count!("phase", "name" = phase.to_string());
+                self.0.advanced.notify(usize::MAX);
+                return target;
+            }
+
+            target
+        };
+
+        let mut timeout = self.0.timeout.map(crate::time::sleep);
+
+        loop {
+            let mut listener = self.0.advanced.listen();
+
+            if self.phase() > target {
+                return target;
+            }
+
+            let timed_out = core::future::poll_fn(|cx| {
+                if Pin::new(&mut listener).poll(cx).is_ready() {
+                    return Poll::Ready(false);
+                }
+                if poll_timeout(&mut timeout, cx).is_ready() {
+                    return Poll::Ready(true);
+                }
+                Poll::Pending
+            })
+            .await;
+
+            if timed_out && self.phase() == target {
+                let pending: Vec<_> = self.pending().iter().map(Group::name).collect();
+                panic!(
+                    "phase {phase:?} timed out after {:?} waiting for: {}",
+                    self.0.timeout.unwrap(),
+                    pending.join(", ")
+                );
+            }
+        }
+    }
+}
+
+fn poll_timeout(timeout: &mut Option<Timer>, cx: &mut Context<'_>) -> Poll<()> {
+    match timeout {
+        Some(timer) => Pin::new(timer).poll(cx),
+        None => Poll::Pending,
+    }
+}
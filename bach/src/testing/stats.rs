@@ -0,0 +1,279 @@
+//! Interval estimates for simulation outputs
+//!
+//! A single run only produces a point estimate, which can be misleading when the result depends
+//! on the seed. [`Estimate`] summarizes independent observations, usually one per seed, with a
+//! standard error and a Student's t confidence interval.
+//!
+//! ```no_run
+//! # use bach::{environment::default::Runtime, testing::stats::Estimate};
+//! # fn run_scenario(_rt: &mut Runtime) -> f64 {
+//! #     0.1
+//! # }
+//! let latencies = Estimate::new((0..10).map(|seed| {
+//!     let mut rt = Runtime::new().with_seed(seed);
+//!     run_scenario(&mut rt)
+//! }));
+//!
+//! let interval = latencies.confidence_interval(0.95);
+//! assert!(*interval.end() < 0.2, "{latencies}");
+//! ```
+//!
+//! Samples from a single long run are usually correlated with each other, which makes the
+//! standard error look smaller than it really is. [`batch_means`] groups consecutive samples into
+//! batches so that the batch averages can be treated as independent observations.
+
+use core::{fmt, ops::RangeInclusive};
+
+/// Splits `samples` into `batches` consecutive groups of equal size and returns the mean of each
+///
+/// Any samples left over after dividing them evenly are dropped from the end. Fewer batches are
+/// returned if there are fewer samples than requested batches.
+pub fn batch_means(samples: &[f64], batches: usize) -> Vec<f64> {
+    if batches == 0 {
+        return vec![];
+    }
+
+    let size = (samples.len() / batches).max(1);
+    samples
+        .chunks_exact(size)
+        .take(batches)
+        .map(|batch| batch.iter().sum::<f64>() / size as f64)
+        .collect()
+}
+
+/// The mean of a set of independent observations, along with its uncertainty
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Estimate {
+    count: usize,
+    mean: f64,
+    variance: f64,
+}
+
+impl Estimate {
+    pub fn new<I: IntoIterator<Item = f64>>(observations: I) -> Self {
+        // Welford's algorithm
+        let mut count = 0;
+        let mut mean = 0.0;
+        let mut m2 = 0.0;
+        for value in observations {
+            count += 1;
+            let delta = value - mean;
+            mean += delta / count as f64;
+            m2 += delta * (value - mean);
+        }
+
+        let variance = if count > 1 {
+            m2 / (count - 1) as f64
+        } else {
+            f64::NAN
+        };
+
+        if count == 0 {
+            mean = f64::NAN;
+        }
+
+        Self {
+            count,
+            mean,
+            variance,
+        }
+    }
+
+    /// Estimates the mean of correlated samples from a single run using the method of batch
+    /// means
+    pub fn from_batch_means(samples: &[f64], batches: usize) -> Self {
+        Self::new(batch_means(samples, batches))
+    }
+
+    /// Returns the number of observations
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the mean of the observations, or `NaN` if there are none
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Returns the sample standard deviation, or `NaN` if there are fewer than 2 observations
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    /// Returns the standard error of the mean, or `NaN` if there are fewer than 2 observations
+    pub fn std_error(&self) -> f64 {
+        self.std_dev() / (self.count as f64).sqrt()
+    }
+
+    /// Returns the distance from the mean to either end of the confidence interval
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` isn't between `0.0` and `1.0`, exclusive.
+    pub fn half_width(&self, level: f64) -> f64 {
+        assert!(
+            level > 0.0 && level < 1.0,
+            "confidence level must be between 0 and 1, got {level}"
+        );
+
+        if self.count < 2 {
+            return f64::NAN;
+        }
+
+        let degrees = (self.count - 1) as f64;
+        students_t_quantile(0.5 + level / 2.0, degrees) * self.std_error()
+    }
+
+    /// Returns the interval that contains the true mean with the given confidence `level`, like
+    /// `0.95`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` isn't between `0.0` and `1.0`, exclusive.
+    pub fn confidence_interval(&self, level: f64) -> RangeInclusive<f64> {
+        let half_width = self.half_width(level);
+        (self.mean - half_width)..=(self.mean + half_width)
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ± {} (95% CI, n={})",
+            self.mean,
+            self.half_width(0.95),
+            self.count
+        )
+    }
+}
+
+/// Approximates the inverse CDF of Student's t distribution
+///
+/// The 1 and 2 degree cases have closed forms. Otherwise, the normal quantile is corrected with
+/// a Cornish-Fisher expansion, which is accurate to about 3 significant digits at 3 degrees of
+/// freedom and improves from there.
+fn students_t_quantile(p: f64, degrees: f64) -> f64 {
+    if degrees == 1.0 {
+        return (core::f64::consts::PI * (p - 0.5)).tan();
+    }
+
+    if degrees == 2.0 {
+        return (2.0 * p - 1.0) * (2.0 / (4.0 * p * (1.0 - p))).sqrt();
+    }
+
+    let z = normal_quantile(p);
+    let z2 = z * z;
+    let g1 = (z2 + 1.0) * z / 4.0;
+    let g2 = ((5.0 * z2 + 16.0) * z2 + 3.0) * z / 96.0;
+    let g3 = (((3.0 * z2 + 19.0) * z2 + 17.0) * z2 - 15.0) * z / 384.0;
+    let g4 = ((((79.0 * z2 + 776.0) * z2 + 1482.0) * z2 - 1920.0) * z2 - 945.0) * z / 92160.0;
+
+    z + g1 / degrees + g2 / degrees.powi(2) + g3 / degrees.powi(3) + g4 / degrees.powi(4)
+}
+
+/// Approximates the inverse CDF of the standard normal distribution
+///
+/// Uses Acklam's rational approximation, which has a relative error below `1.2e-9`.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    if p < LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn t_quantiles() {
+        // reference values from a t table
+        for (degrees, expected) in [
+            (1.0, 12.706),
+            (2.0, 4.303),
+            (5.0, 2.571),
+            (10.0, 2.228),
+            (30.0, 2.042),
+            (1000.0, 1.962),
+        ] {
+            assert_close(students_t_quantile(0.975, degrees), expected);
+        }
+        assert_close(students_t_quantile(0.995, 20.0), 2.845);
+        assert_close(normal_quantile(0.5), 0.0);
+        assert_close(normal_quantile(0.01), -2.326);
+    }
+
+    #[test]
+    fn estimate() {
+        let estimate = Estimate::new([2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(estimate.count(), 8);
+        assert_close(estimate.mean(), 5.0);
+        assert_close(estimate.std_dev(), 2.138);
+        assert_close(estimate.std_error(), 0.756);
+
+        let interval = estimate.confidence_interval(0.95);
+        assert_close(*interval.start(), 3.2125);
+        assert_close(*interval.end(), 6.7875);
+
+        assert!(Estimate::new([1.0]).half_width(0.95).is_nan());
+        assert!(Estimate::new([]).mean().is_nan());
+    }
+
+    #[test]
+    fn batches() {
+        let samples: Vec<f64> = (0..10).map(f64::from).collect();
+        assert_eq!(batch_means(&samples, 3), [1.0, 4.0, 7.0]);
+        assert_eq!(batch_means(&samples, 20).len(), 10);
+        assert!(batch_means(&samples, 0).is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+//! Assertions on the rate of progress of a simulation
+//!
+//! The runtime's stall detector only fires when no task can make progress at all. A system that
+//! is livelocked, or whose throughput collapsed while its tasks stay busy, keeps the executor
+//! running without getting any work done. A [`Watchdog`] catches those by checking that a
+//! progress counter, like the number of committed entries or completed requests, advances at a
+//! minimum rate.
+//!
+//! ```no_run
+//! # use bach::{environment::default::Runtime, ext::*, testing::Watchdog};
+//! # Runtime::new().run(|| {
+//! let commits = Watchdog::new("commits", 100.0).with_grace(5.s());
+//! commits.spawn();
+//!
+//! // in the state machine
+//! commits.increment();
+//! # });
+//! ```
+
+use crate::{
+    executor::JoinHandle,
+    time::{Duration, Instant},
+};
+use alloc::sync::Arc;
+use core::fmt;
+use std::{collections::VecDeque, sync::Mutex};
+
+/// The number of samples included in the diagnostics of a [`Stall`]
+const HISTORY: usize = 8;
+
+/// Checks that a progress counter advances at a minimum rate of simulated time
+#[derive(Clone)]
+pub struct Watchdog {
+    name: Arc<str>,
+    min_rate: f64,
+    window: Duration,
+    grace: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    total: u64,
+    start: Option<Instant>,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("name", &self.name)
+            .field("min_rate", &self.min_rate)
+            .field("window", &self.window)
+            .field("grace", &self.grace)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Watchdog {
+    /// Returns a watchdog that expects `name` to advance by at least `min_rate` per simulated
+    /// second
+    pub fn new(name: &str, min_rate: f64) -> Self {
+        Self {
+            name: name.into(),
+            min_rate,
+            window: Duration::from_secs(1),
+            grace: Duration::ZERO,
+            state: Default::default(),
+        }
+    }
+
+    /// Sets the amount of simulated time between checks, which defaults to 1s
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        assert!(!window.is_zero(), "window must be greater than 0");
+        self.window = window;
+        self
+    }
+
+    /// Sets how long the system has to warm up before the rate is checked, which defaults to
+    /// zero
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Records `amount` of progress
+    pub fn record(&self, amount: u64) {
+        self.state.lock().unwrap().total += amount;
+    }
+
+    /// Records a single unit of progress
+    pub fn increment(&self) {
+        self.record(1);
+    }
+
+    /// Returns the progress recorded so far
+    pub fn total(&self) -> u64 {
+        self.state.lock().unwrap().total
+    }
+
+    /// Checks the progress made since the previous sample
+    ///
+    /// The first sample only records a baseline, and intervals that start during the grace period
+    /// always pass. [`Self::spawn`] takes a sample at the end of every window.
+    pub fn sample(&self) -> Result<(), Stall> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let total = state.total;
+        let start = *state.start.get_or_insert(now);
+        let previous = state.samples.back().copied();
+
+        state.samples.push_back((now, total));
+        if state.samples.len() > HISTORY {
+            state.samples.pop_front();
+        }
+
+        let Some((since, baseline)) = previous else {
+            return Ok(());
+        };
+
+        // only check windows that started after the system warmed up
+        if since.saturating_duration_since(start) < self.grace {
+            return Ok(());
+        }
+
+        let interval = now.saturating_duration_since(since);
+        let progress = total - baseline;
+        let expected = self.min_rate * interval.as_secs_f64();
+
+        if progress as f64 >= expected {
+            return Ok(());
+        }
+
+        count!("watchdog_stall", "name" = self.name.to_string());
+
+        Err(Stall {
+            name: self.name.clone(),
+            at: now,
+            interval,
+            progress,
+            min_rate: self.min_rate,
+            total,
+            history: state
+                .samples
+                .iter()
+                .zip(state.samples.iter().skip(1))
+                .map(|(a, b)| b.1 - a.1)
+                .collect(),
+        })
+    }
+
+    /// Spawns a daemon that samples the progress at the end of every window
+    ///
+    /// # Panics
+    ///
+    /// The daemon panics with the [`Stall`] diagnostics the first time the progress falls below
+    /// the minimum rate.
+    pub fn spawn(&self) -> JoinHandle<()> {
+        let watchdog = self.clone();
+        let name = format!("watchdog_{}", self.name);
+        crate::task::daemon::spawn_named(
+            async move {
+                loop {
+                    if let Err(stall) = watchdog.sample() {
+                        panic!("{stall}");
+                    }
+                    crate::time::sleep(watchdog.window).await;
+                }
+            },
+            name,
+        )
+    }
+}
+
+/// The diagnostics for a [`Watchdog`] whose progress fell below its minimum rate
+#[derive(Clone, Debug)]
+pub struct Stall {
+    name: Arc<str>,
+    at: Instant,
+    interval: Duration,
+    progress: u64,
+    min_rate: f64,
+    total: u64,
+    history: Vec<u64>,
+}
+
+impl Stall {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns when the stall was detected
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+
+    /// Returns the progress made since the previous sample
+    pub fn progress(&self) -> u64 {
+        self.progress
+    }
+
+    /// Returns the progress made between each of the most recent samples, oldest first
+    pub fn history(&self) -> &[u64] {
+        &self.history
+    }
+}
+
+impl std::error::Error for Stall {}
+
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` advanced by {} in the {:?} before {}, expected at least {} per second (total {}, recent progress {:?})",
+            self.name,
+            self.progress,
+            self.interval,
+            self.at,
+            self.min_rate,
+            self.total,
+            self.history,
+        )
+    }
+}
@@ -1,12 +1,14 @@
 use core::{fmt, ops};
 
 mod bitset;
+pub mod driver;
 mod entry;
 pub mod scheduler;
 mod stack;
 mod wheel;
 
 pub use core::time::Duration;
+pub use driver::TimeDriver;
 
 pub fn sleep(duration: Duration) -> scheduler::Timer {
     measure!("sleep", duration);
@@ -47,6 +49,11 @@ impl Instant {
     pub fn has_elapsed(&self) -> bool {
         Self::now().ge(self)
     }
+
+    /// Returns the time between `earlier` and `self`, or zero if `earlier` is later
+    pub fn saturating_duration_since(self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
 }
 
 impl ops::Add<Duration> for Instant {
@@ -93,6 +100,16 @@ pub(crate) mod resolution {
         Duration::from_nanos(nanos)
     }
 
+    /// Rounds `duration` up to a whole number of ticks
+    ///
+    /// Timers round down to whole ticks, so deadlines that a loop waits to pass need to be
+    /// rounded up or the loop never observes them.
+    pub fn round_up(duration: Duration) -> Duration {
+        let nanos_per_tick = tick_duration().as_nanos();
+        let ticks = duration.as_nanos().div_ceil(nanos_per_tick);
+        Duration::from_nanos((ticks * nanos_per_tick) as u64)
+    }
+
     pub fn duration_to_ticks(duration: Duration) -> u64 {
         let nanos = duration.as_nanos();
         let nanos_per_tick = tick_duration().as_nanos();
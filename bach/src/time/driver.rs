@@ -0,0 +1,58 @@
+use super::{scheduler, Duration, Instant};
+use crate::executor::{self, JoinHandle};
+use core::task::Waker;
+
+/// A handle for scheduling work into the simulation from outside of it
+///
+/// The handle is `Send + Sync` so it can be moved to other threads or handed to FFI code.
+/// Events are pushed onto the executor queue and run once the simulation reaches the
+/// requested virtual time. Note that events only make progress while the runtime is being
+/// driven.
+#[derive(Clone)]
+pub struct TimeDriver {
+    executor: executor::Handle,
+    scheduler: scheduler::Handle,
+}
+
+impl TimeDriver {
+    pub(crate) fn new(executor: executor::Handle, scheduler: scheduler::Handle) -> Self {
+        Self {
+            executor,
+            scheduler,
+        }
+    }
+
+    /// Returns the current virtual time of the simulation
+    pub fn now(&self) -> Instant {
+        self.scheduler.now()
+    }
+
+    /// Wakes the `waker` once the simulation reaches `target`
+    pub fn wake_at(&self, target: Instant, waker: Waker) -> JoinHandle<()> {
+        self.post_at(target, move || waker.wake())
+    }
+
+    /// Runs `event` inside the simulation once it reaches `target`
+    ///
+    /// If `target` is in the past, the event runs on the next macrostep.
+    pub fn post_at<F>(&self, target: Instant, event: F) -> JoinHandle<()>
+    where
+        F: 'static + FnOnce() + Send,
+    {
+        let event = async move {
+            super::sleep_until(target).await;
+            event();
+        };
+        // events can be scheduled past the end of the run
+        self.executor.spawn_daemon_named(event, "time_driver")
+    }
+
+    /// Runs `event` inside the simulation after `delay` has passed, relative to the current
+    /// virtual time
+    pub fn post_after<F>(&self, delay: Duration, event: F) -> JoinHandle<()>
+    where
+        F: 'static + FnOnce() + Send,
+    {
+        self.post_at(self.now() + delay, event)
+    }
+}
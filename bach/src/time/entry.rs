@@ -12,6 +12,8 @@ pub trait Queue<Entry> {
     fn push(&mut self, entry: Entry);
     fn pop(&mut self) -> Option<Entry>;
     fn take(&mut self) -> Self;
+    /// Returns the earliest absolute tick that an entry in the queue expires at
+    fn min_expiration(&self) -> Option<u64>;
 }
 
 pub mod atomic {
@@ -125,5 +127,14 @@ pub mod atomic {
         fn take(&mut self) -> Self {
             LinkedList::take(self)
         }
+
+        fn min_expiration(&self) -> Option<u64> {
+            self.iter()
+                .map(|entry| {
+                    let start_tick = entry.start_tick.load(Ordering::SeqCst);
+                    start_tick.wrapping_add(entry.delay)
+                })
+                .min()
+        }
     }
 }
@@ -78,6 +78,18 @@ impl Scheduler {
         Some(ticks)
     }
 
+    /// Returns the time that the next timer expires, without advancing the clock
+    ///
+    /// Timers that were cancelled after being registered are still included until their
+    /// expiration is reached.
+    pub fn next_expiration(&mut self) -> Option<super::Instant> {
+        self.collect();
+
+        let ticks = self.wheel.next_expiration()?;
+        let duration = crate::time::resolution::ticks_to_duration(ticks);
+        Some(super::Instant(duration))
+    }
+
     /// Wakes all of the expired tasks
     pub fn wake(&mut self) -> usize {
         scope::with(self.handle(), || self.wheel.wake(atomic::wake))
@@ -92,6 +104,27 @@ impl Scheduler {
         })
     }
 
+    /// Returns a timer that stops the clock on `target`
+    ///
+    /// Unlike [`super::sleep_until`], the timer isn't coalesced and is inserted into the wheel
+    /// right away, so the clock can't advance past `target` without it expiring, even if it's
+    /// never polled.
+    pub fn stop_at(&mut self, target: super::Instant) -> Timer {
+        let duration = target.0.saturating_sub(self.handle.now().0);
+        let ticks = crate::time::resolution::duration_to_ticks(duration);
+        let entry = atomic::Entry::new(ticks);
+
+        // mark the entry as registered so polling the timer doesn't insert it again
+        entry.should_register();
+        self.collect();
+        self.wheel.insert(entry.clone());
+
+        Timer {
+            handle: self.handle(),
+            entry,
+        }
+    }
+
     pub fn close(&mut self) {
         scope::with(self.handle(), || {
             self.wheel.close(|entry| {
@@ -104,6 +137,16 @@ impl Scheduler {
     pub fn reset(&mut self) {
         self.wheel.reset();
     }
+
+    /// Coalesces timer expirations to the next multiple of `granularity`
+    ///
+    /// Timers that would expire within the same window fire on the same tick, which reduces the
+    /// number of macrosteps in simulations with many timers at the cost of some timing accuracy.
+    /// A granularity of zero disables coalescing.
+    pub fn set_coalescing(&mut self, granularity: core::time::Duration) {
+        let nanos = granularity.as_nanos().try_into().unwrap_or(u64::MAX);
+        self.handle.0.coalescing.store(nanos, Ordering::Relaxed);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -113,6 +156,7 @@ impl Handle {
     fn new(queue: Queue) -> Self {
         let inner = InnerHandle {
             ticks: AtomicU64::new(0),
+            coalescing: AtomicU64::new(0),
             queue,
         };
         Self(Arc::new(inner))
@@ -120,6 +164,7 @@ impl Handle {
 
     /// Returns a future that sleeps for the given number of ticks
     pub fn delay(&self, ticks: u64) -> Timer {
+        let ticks = self.coalesce(ticks);
         let entry = atomic::Entry::new(ticks);
         let handle = self.clone();
         Timer { handle, entry }
@@ -137,6 +182,35 @@ impl Handle {
         super::Instant(duration)
     }
 
+    /// Rounds the expiration up to the coalescing window, if enabled
+    fn coalesce(&self, ticks: u64) -> u64 {
+        let nanos = self.0.coalescing.load(Ordering::Relaxed);
+
+        // immediate timers are never delayed
+        if nanos == 0 || ticks == 0 {
+            return ticks;
+        }
+
+        let granularity =
+            crate::time::resolution::duration_to_ticks(core::time::Duration::from_nanos(nanos))
+                .max(1);
+
+        let now = self.ticks();
+        let target = now.saturating_add(ticks);
+        let coalesced = target.div_ceil(granularity).saturating_mul(granularity);
+        let error = coalesced - target;
+
+        if error > 0 {
+            count!("timer_coalesced");
+        }
+        measure!(
+            "timer_coalescing_error",
+            crate::time::resolution::ticks_to_duration(error)
+        );
+
+        coalesced - now
+    }
+
     fn advance(&self, ticks: u64) {
         if cfg!(test) {
             self.0
@@ -152,6 +226,8 @@ impl Handle {
 #[derive(Debug)]
 struct InnerHandle {
     ticks: AtomicU64,
+    /// The coalescing window in nanoseconds, or `0` if disabled
+    coalescing: AtomicU64,
     queue: Queue,
 }
 
@@ -173,6 +249,11 @@ impl Timer {
     pub fn cancel(&mut self) {
         self.entry.cancel();
     }
+
+    /// Returns `true` if the timer expired since the last check, without registering a waker
+    pub(crate) fn take_expired(&self) -> bool {
+        self.entry.take_expired()
+    }
 }
 
 impl Drop for Timer {
@@ -46,6 +46,14 @@ impl<E: Entry> Stack<E> {
         self.occupied.is_empty()
     }
 
+    /// Returns the earliest absolute tick that an entry in the stack expires at
+    pub fn min_expiration(&self) -> Option<u64> {
+        (0..=u8::MAX)
+            .filter(|index| self.occupied.get(*index))
+            .filter_map(|index| self.slots[index as usize].min_expiration())
+            .min()
+    }
+
     pub fn insert(&mut self, index: u8, entry: E) {
         self.occupied.insert(index);
         let list = self.slot_mut(index);
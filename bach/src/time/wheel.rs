@@ -51,6 +51,18 @@ impl<E: Entry> Wheel<E> {
         is_empty!()
     }
 
+    /// Returns the absolute tick of the next entry to expire without advancing the wheel
+    pub fn next_expiration(&self) -> Option<u64> {
+        if !self.pending_wake.is_empty() {
+            return Some(self.ticks());
+        }
+
+        self.stacks
+            .iter()
+            .filter_map(|stack| stack.min_expiration())
+            .min()
+    }
+
     pub fn insert(&mut self, mut entry: E) {
         let ticks = self.ticks();
         entry.set_start_tick(ticks);
@@ -238,6 +250,7 @@ mod tests {
 
             while let Some(expected) = sorted.next() {
                 let delta = expected - elapsed;
+                assert_eq!(wheel.next_expiration(), Some(total_ticks + expected));
                 assert_eq!(wheel.advance(), Some(delta));
                 elapsed += delta;
 
@@ -257,6 +270,7 @@ mod tests {
             }
 
             assert!(wheel.is_empty());
+            assert_eq!(wheel.next_expiration(), None);
             assert_eq!(wheel.advance(), None);
             assert_eq!(wheel.wake(atomic::wake), 0);
             assert!(wheel.is_empty());
@@ -12,9 +12,19 @@ mod shim {
             Self(())
         }
 
+        pub fn is_disabled(&self) -> bool {
+            true
+        }
+
         pub fn in_scope<F: FnOnce() -> R, R>(&self, f: F) -> R {
             f()
         }
+
+        pub fn record<Q: ?Sized, V>(&self, field: &Q, value: V) -> &Self {
+            let _ = field;
+            let _ = value;
+            self
+        }
     }
 
     #[macro_export]
@@ -42,6 +52,13 @@ mod shim {
 
     pub use crate::trace_ as trace;
 
+    #[macro_export]
+    macro_rules! warn_ {
+        ($($tt:tt)*) => {};
+    }
+
+    pub use crate::warn_ as warn;
+
     pub trait Instrument {
         fn instrument(self, span: Span) -> Self;
     }
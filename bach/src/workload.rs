@@ -0,0 +1,339 @@
+//! Arrival processes that follow a rate over simulated time
+//!
+//! Traffic in production is rarely flat. A [`Pattern`] describes the expected number of arrivals
+//! per second at each point in simulated time, like a daily [`Sinusoid`] or a [`Trace`] derived
+//! from recorded traffic, and [`Arrivals`] turns it into a stream of Poisson arrivals so
+//! long-horizon capacity simulations see the same peaks and troughs as the real system.
+//!
+//! ```no_run
+//! # use bach::{
+//! #     environment::default::Runtime,
+//! #     ext::*,
+//! #     workload::{Arrivals, Sinusoid, HOUR},
+//! # };
+//! # async fn request() {}
+//! # Runtime::new().run(|| {
+//! // 100 req/s on average, peaking at 180 req/s at 14:00
+//! let pattern = Sinusoid::diurnal(100.0, 80.0).with_peak_at(14 * HOUR);
+//!
+//! async move {
+//!     // the simulation starts at 06:00
+//!     let mut arrivals = Arrivals::new(pattern).with_offset(6 * HOUR);
+//!     while arrivals.tick().await.is_some() {
+//!         request().spawn();
+//!     }
+//! }
+//! .primary()
+//! .spawn();
+//! # });
+//! ```
+
+use crate::{
+    rand::*,
+    time::{
+        resolution::{duration_to_ticks, ticks_to_duration},
+        sleep_until, Duration, Instant,
+    },
+};
+use core::f64::consts::TAU;
+
+pub const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// The length of a day, which is the period of diurnal patterns
+pub const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A rate of arrivals that changes over time
+pub trait Pattern {
+    /// Returns the expected number of arrivals per second at `at`
+    ///
+    /// Negative rates are treated as zero.
+    fn rate(&self, at: Duration) -> f64;
+
+    /// Returns an upper bound on the rate at any point in time
+    fn peak(&self) -> f64;
+
+    /// Returns the time after which the rate stays at zero, or `None` if it never does
+    ///
+    /// [`Arrivals`] stops producing arrivals once it's past this point rather than searching for
+    /// one forever.
+    fn end(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl<P: Pattern + ?Sized> Pattern for Box<P> {
+    fn rate(&self, at: Duration) -> f64 {
+        (**self).rate(at)
+    }
+
+    fn peak(&self) -> f64 {
+        (**self).peak()
+    }
+
+    fn end(&self) -> Option<Duration> {
+        (**self).end()
+    }
+}
+
+/// A rate that never changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Constant(pub f64);
+
+impl Pattern for Constant {
+    fn rate(&self, _at: Duration) -> f64 {
+        self.0
+    }
+
+    fn peak(&self) -> f64 {
+        self.0
+    }
+}
+
+/// A rate that oscillates around a mean
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sinusoid {
+    mean: f64,
+    amplitude: f64,
+    period: Duration,
+    peak_at: Duration,
+}
+
+impl Sinusoid {
+    /// Returns a pattern that swings `amplitude` above and below `mean` every `period`
+    ///
+    /// The rate peaks at the start of each period unless [`Self::with_peak_at`] is set. Rates
+    /// that would dip below zero are clamped to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(mean: f64, amplitude: f64, period: Duration) -> Self {
+        assert!(!period.is_zero(), "period must be greater than 0");
+        Self {
+            mean,
+            amplitude: amplitude.abs(),
+            period,
+            peak_at: Duration::ZERO,
+        }
+    }
+
+    /// Returns a pattern with a period of one [`DAY`] that peaks at noon
+    pub fn diurnal(mean: f64, amplitude: f64) -> Self {
+        Self::new(mean, amplitude, DAY).with_peak_at(DAY / 2)
+    }
+
+    /// Sets the offset into each period where the rate is highest
+    pub fn with_peak_at(mut self, peak_at: Duration) -> Self {
+        self.peak_at = peak_at;
+        self
+    }
+}
+
+impl Pattern for Sinusoid {
+    fn rate(&self, at: Duration) -> f64 {
+        let phase = (at.as_secs_f64() - self.peak_at.as_secs_f64()) / self.period.as_secs_f64();
+        self.mean + self.amplitude * (phase * TAU).cos()
+    }
+
+    fn peak(&self) -> f64 {
+        self.mean + self.amplitude
+    }
+}
+
+/// A rate interpolated between recorded points
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    points: Vec<(Duration, f64)>,
+    period: Option<Duration>,
+}
+
+impl Trace {
+    /// Returns a pattern that linearly interpolates between `(time, rate)` points
+    ///
+    /// The rate before the first point is the first rate and the rate after the last point is
+    /// the last rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no points.
+    pub fn new<I: IntoIterator<Item = (Duration, f64)>>(points: I) -> Self {
+        let mut points: Vec<_> = points.into_iter().collect();
+        assert!(!points.is_empty(), "a trace needs at least one point");
+        points.sort_by_key(|(at, _)| *at);
+        Self {
+            points,
+            period: None,
+        }
+    }
+
+    /// Returns a pattern with a rate for each hour that repeats every `rates.len()` hours
+    ///
+    /// Each rate applies to the start of its hour, so a trace of 24 hourly averages from a
+    /// dashboard produces a smooth daily pattern.
+    pub fn hourly<I: IntoIterator<Item = f64>>(rates: I) -> Self {
+        let trace = Self::new(
+            rates
+                .into_iter()
+                .enumerate()
+                .map(|(idx, rate)| (HOUR * idx as u32, rate)),
+        );
+        let period = HOUR * trace.points.len() as u32;
+        trace.with_period(period)
+    }
+
+    /// Repeats the trace every `period`, interpolating from the last point back to the first
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn with_period(mut self, period: Duration) -> Self {
+        assert!(!period.is_zero(), "period must be greater than 0");
+        self.period = Some(period);
+        self
+    }
+}
+
+impl Pattern for Trace {
+    fn rate(&self, at: Duration) -> f64 {
+        let point = |(time, rate): (Duration, f64)| (time.as_secs_f64(), rate);
+        let first = point(self.points[0]);
+        let last = point(self.points[self.points.len() - 1]);
+        let mut at = at.as_secs_f64();
+
+        // the points that bound the trace on either side
+        let (before, after) = match self.period {
+            Some(period) => {
+                let period = period.as_secs_f64();
+                at %= period;
+                ((last.0 - period, last.1), (first.0 + period, first.1))
+            }
+            None => (first, last),
+        };
+
+        let idx = self
+            .points
+            .partition_point(|(time, _)| time.as_secs_f64() <= at);
+        let (from, to) = match idx {
+            0 => (before, first),
+            idx if idx == self.points.len() => (last, after),
+            idx => (point(self.points[idx - 1]), point(self.points[idx])),
+        };
+
+        let span = to.0 - from.0;
+        if span <= 0.0 {
+            return to.1;
+        }
+        let progress = ((at - from.0) / span).clamp(0.0, 1.0);
+        from.1 + (to.1 - from.1) * progress
+    }
+
+    fn peak(&self) -> f64 {
+        self.points
+            .iter()
+            .map(|(_, rate)| *rate)
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    fn end(&self) -> Option<Duration> {
+        if self.period.is_some() {
+            return None;
+        }
+
+        // the rate reaches zero at the point after the last positive one and stays there
+        let last = self.points.iter().rposition(|(_, rate)| *rate > 0.0)?;
+        let (end, _) = self.points.get(last + 1)?;
+        Some(*end)
+    }
+}
+
+/// Poisson arrivals that follow a [`Pattern`]
+///
+/// Arrivals are generated by thinning: candidates are drawn at the pattern's peak rate and each is
+/// kept with a probability of the rate at that time over the peak. The simulation RNG is used so
+/// the same seed always produces the same arrivals.
+#[derive(Clone, Debug)]
+pub struct Arrivals<P> {
+    pattern: P,
+    offset: Duration,
+    /// The time of the previous arrival, in seconds since the start of the simulation
+    last: Option<f64>,
+}
+
+impl<P: Pattern> Arrivals<P> {
+    pub fn new(pattern: P) -> Self {
+        Self {
+            pattern,
+            offset: Duration::ZERO,
+            last: None,
+        }
+    }
+
+    /// Sets the time in the pattern that corresponds to the start of the simulation
+    ///
+    /// For diurnal patterns, this is the time of day that the simulation starts at.
+    pub fn with_offset(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn pattern(&self) -> &P {
+        &self.pattern
+    }
+
+    /// Returns the rate of the pattern at `at`
+    pub fn rate_at(&self, at: Instant) -> f64 {
+        self.pattern
+            .rate(at.elapsed_since_start() + self.offset)
+            .max(0.0)
+    }
+
+    /// Returns the time of the next arrival, or `None` if the pattern doesn't produce any more
+    ///
+    /// The first arrival is generated from the current time.
+    pub fn next_arrival(&mut self) -> Option<Instant> {
+        let peak = self.pattern.peak();
+        if !(peak > 0.0 && peak.is_finite()) {
+            return None;
+        }
+
+        let mut at = match self.last {
+            Some(last) => last,
+            None => Instant::now().elapsed_since_start().as_secs_f64(),
+        };
+
+        let end = self.pattern.end();
+
+        loop {
+            at += -unit().ln() / peak;
+            let time = Duration::from_secs_f64(at) + self.offset;
+            if end.is_some_and(|end| time >= end) {
+                return None;
+            }
+            let rate = self.pattern.rate(time);
+            if unit() * peak <= rate {
+                break;
+            }
+        }
+
+        self.last = Some(at);
+        let now = Instant::now();
+        let delay = Duration::from_secs_f64(at).saturating_sub(now.elapsed_since_start());
+        // arrivals are rounded down to the timer resolution
+        Some(now + ticks_to_duration(duration_to_ticks(delay)))
+    }
+
+    /// Waits for the next arrival and returns its time
+    ///
+    /// Returns `None` without waiting if the pattern never produces any arrivals.
+    pub async fn tick(&mut self) -> Option<Instant> {
+        let at = self.next_arrival()?;
+        sleep_until(at).await;
+        Some(at)
+    }
+}
+
+/// Returns a uniformly distributed value in `(0, 1]`
+fn unit() -> f64 {
+    const SCALE: u64 = 1 << 53;
+    (1..=SCALE).any() as f64 / SCALE as f64
+}